@@ -0,0 +1,244 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    atoi::{floor_div, floor_rem, Atoi},
+    ir::{CacheTag, Ir, Operator},
+    parse::{span::SpannedError, Type},
+};
+
+use super::read_def::ConstValue;
+
+/// 默认的定点数倍数，小数点后保留3位
+pub const DEFAULT_FIXED_SCALE: i32 = 1000;
+
+const CONST_FIXED_SCALE: CacheTag = CacheTag::StaticBuiltin("FixedScale");
+
+/// 把小数字面量转换为定点数，小数位数不能超过倍数的精度
+pub(super) fn parse_decimal(num: &str, scale: i32) -> Result<i32> {
+    let (int, frac) = num.split_once('.').unwrap();
+    let mut value = int
+        .parse::<i32>()
+        .ok()
+        .and_then(|int| int.checked_mul(scale));
+    let mut weight = scale;
+    for digit in frac.trim_end_matches('0').bytes() {
+        weight /= 10;
+        if weight == 0 {
+            return Err(SpannedError::at(
+                num,
+                format_args!("`{num}` has more decimal places than the fixed-point scale {scale}"),
+            ));
+        }
+        value = value.and_then(|v| v.checked_add((digit - b'0') as i32 * weight));
+    }
+    value.ok_or_else(|| SpannedError::at(num, format_args!("`{num}` is out of range")))
+}
+
+/// 整数转换为定点数
+pub(super) fn const_promote(value: i32, scale: i32) -> Result<i32> {
+    value
+        .checked_mul(scale)
+        .ok_or_else(|| anyhow!("`{value}` is out of the range of fixed-point value"))
+}
+
+/// 编译期计算有定点数参与的二元运算
+pub(super) fn const_fixed_binary<'a>(
+    lhs: ConstValue<'a>,
+    rhs: ConstValue<'a>,
+    opr: Operator,
+    scale: i32,
+) -> Result<ConstValue<'a>> {
+    let (lhs, lhs_ty) = const_number(lhs)?;
+    let (rhs, rhs_ty) = const_number(rhs)?;
+    if is_bitwise(opr) {
+        return Err(bitwise_error());
+    }
+
+    let scale = scale as i64;
+    let (lhs, rhs) = (lhs as i64, rhs as i64);
+    let r = match (opr, lhs_ty, rhs_ty) {
+        (Operator::Mul, Type::Fixed, Type::Fixed) => floor_div(lhs * rhs, scale),
+        (Operator::Mul, _, _) => lhs * rhs,
+        (Operator::Div | Operator::Rem, _, _) if rhs == 0 => {
+            return Err(anyhow!("attempt to divide by zero"))
+        }
+        (Operator::Div, Type::Int, _) => floor_div(lhs * scale * scale, rhs),
+        (Operator::Div, _, Type::Fixed) => floor_div(lhs * scale, rhs),
+        (Operator::Div, _, _) => floor_div(lhs, rhs),
+        _ => {
+            let promote = |value, ty| match ty {
+                Type::Fixed => value,
+                _ => value * scale,
+            };
+            let (lhs, rhs) = (promote(lhs, lhs_ty), promote(rhs, rhs_ty));
+            match opr {
+                Operator::Add => lhs + rhs,
+                Operator::Sub => lhs - rhs,
+                Operator::Rem => floor_rem(lhs, rhs),
+                Operator::Max => lhs.max(rhs),
+                Operator::Min => lhs.min(rhs),
+                _ => unreachable!("`{opr:?}` is not an arithmetical operator"),
+            }
+        }
+    };
+    Ok(ConstValue::Fixed(r as i32))
+}
+
+/// 有定点数参与的比较，整数先转换为定点数
+pub(super) fn const_fixed_comparison<'a>(
+    lhs: ConstValue<'a>,
+    rhs: ConstValue<'a>,
+    scale: i32,
+) -> Result<(i32, i32)> {
+    let promote = |value: ConstValue<'a>| -> Result<i32> {
+        match const_number(value)? {
+            (value, Type::Fixed) => Ok(value),
+            (value, _) => const_promote(value, scale),
+        }
+    };
+    Ok((promote(lhs)?, promote(rhs)?))
+}
+
+fn const_number(value: ConstValue) -> Result<(i32, Type)> {
+    match value {
+        ConstValue::Int(int) => Ok((int, Type::Int)),
+        ConstValue::Fixed(fixed) => Ok((fixed, Type::Fixed)),
+        ConstValue::Bool(_) => Err(anyhow!(
+            "mismatched types: expected `int`, found `bool`, use `int(...)` to convert"
+        )),
+        ConstValue::Str(_) => Err(anyhow!("string cannot do binary operation")),
+    }
+}
+
+fn is_bitwise(opr: Operator) -> bool {
+    matches!(
+        opr,
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor | Operator::Shl | Operator::Shr
+    )
+}
+
+fn bitwise_error() -> anyhow::Error {
+    anyhow!("bitwise operators cannot be applied to fixed-point values")
+}
+
+impl<'a> Atoi<'a> {
+    /// 保存倍数的寄存器，第一次使用时创建
+    pub(super) fn fixed_scale_reg(&mut self) -> Result<CacheTag<'a>> {
+        if !self
+            .label_map
+            .statics()
+            .any(|(tag, _)| *tag == CONST_FIXED_SCALE)
+        {
+            self.label_map
+                .insert_static(CONST_FIXED_SCALE, self.fixed_scale)?;
+        }
+        Ok(CONST_FIXED_SCALE)
+    }
+
+    /// `int(x)`，记分板的除法向负无穷取整，因此负数同样向下取整
+    pub(super) fn fixed_to_int(
+        &mut self,
+        insts: &mut Vec<Ir<'a>>,
+        reg: CacheTag<'a>,
+    ) -> Result<()> {
+        let src = self.fixed_scale_reg()?;
+        insts.push(Ir::Operation {
+            dst: reg,
+            opr: Operator::Div,
+            src,
+        });
+        Ok(())
+    }
+
+    /// 计算`dst opr src`并返回结果的类型，`cache_offset`之后的寄存器用于定点数的乘除法。
+    ///
+    /// 定点数相乘时结果需要除以倍数，相除时被除数需要乘以倍数。为了不在中间结果溢出，
+    /// 操作数都拆分为商与余数分别计算，例如`a * b / S = a / S * b + a % S * (b / S) + a % S * (b % S) / S`
+    pub(super) fn fixed_binary(
+        &mut self,
+        insts: &mut Vec<Ir<'a>>,
+        (dst, lhs_ty): (CacheTag<'a>, Type),
+        opr: Operator,
+        (src, rhs_ty): (CacheTag<'a>, Type),
+        cache_offset: u32,
+    ) -> Result<Type> {
+        if lhs_ty == Type::Int && rhs_ty == Type::Int {
+            insts.push(Ir::Operation { dst, opr, src });
+            return Ok(Type::Int);
+        }
+        if is_bitwise(opr) {
+            return Err(bitwise_error());
+        }
+
+        let scale = self.fixed_scale_reg()?;
+        let (temp, temp2) = (
+            CacheTag::Regular(cache_offset),
+            CacheTag::Regular(cache_offset + 1),
+        );
+        let op = |dst, opr, src| Ir::Operation { dst, opr, src };
+        match opr {
+            // 余数都小于倍数，它们的乘积不会溢出
+            Operator::Mul if lhs_ty == rhs_ty => insts.extend([
+                op(temp, Operator::Set, dst),
+                op(temp, Operator::Rem, scale),
+                op(dst, Operator::Div, scale),
+                op(dst, Operator::Mul, src),
+                op(temp2, Operator::Set, src),
+                op(temp2, Operator::Div, scale),
+                op(temp2, Operator::Mul, temp),
+                op(dst, Operator::Add, temp2),
+                op(temp2, Operator::Set, src),
+                op(temp2, Operator::Rem, scale),
+                op(temp2, Operator::Mul, temp),
+                op(temp2, Operator::Div, scale),
+                op(dst, Operator::Add, temp2),
+            ]),
+            Operator::Mul => insts.push(op(dst, opr, src)),
+            Operator::Div if rhs_ty == Type::Fixed => {
+                if lhs_ty == Type::Int {
+                    insts.push(op(dst, Operator::Mul, scale));
+                }
+                insts.extend([
+                    op(temp, Operator::Set, dst),
+                    op(temp, Operator::Rem, src),
+                    op(temp, Operator::Mul, scale),
+                    op(temp, Operator::Div, src),
+                    op(dst, Operator::Div, src),
+                    op(dst, Operator::Mul, scale),
+                    op(dst, Operator::Add, temp),
+                ]);
+            }
+            Operator::Div => insts.push(op(dst, opr, src)),
+            _ => {
+                if lhs_ty == Type::Int {
+                    insts.push(op(dst, Operator::Mul, scale));
+                }
+                if rhs_ty == Type::Int {
+                    insts.push(op(src, Operator::Mul, scale));
+                }
+                insts.push(op(dst, opr, src));
+            }
+        }
+        Ok(Type::Fixed)
+    }
+
+    /// 整数与定点数比较前，把整数一侧转换为定点数
+    pub(super) fn fixed_comparison(
+        &mut self,
+        insts: &mut Vec<Ir<'a>>,
+        (lhs, lhs_ty): (CacheTag<'a>, Type),
+        (rhs, rhs_ty): (CacheTag<'a>, Type),
+    ) -> Result<()> {
+        for (reg, ty, other) in [(lhs, lhs_ty, rhs_ty), (rhs, rhs_ty, lhs_ty)] {
+            if ty == Type::Int && other == Type::Fixed {
+                let scale = self.fixed_scale_reg()?;
+                insts.push(Ir::Operation {
+                    dst: reg,
+                    opr: Operator::Mul,
+                    src: scale,
+                });
+            }
+        }
+        Ok(())
+    }
+}
@@ -10,18 +10,33 @@ use nom::{
 use std::fmt::Write;
 
 use crate::{
-    atoi::{Atoi, Binding},
-    format::FormatStyle,
-    ir::{FormatArgument, Ir},
+    atoi::{get_anonymous_id, Atoi, Binding},
+    format::{format_fixed, FormatStyle},
+    ir::{
+        ArmRange, BoolOperator, BoolOprRhs, CacheTag, FmtCommand, FormatArgument, Ir, LabelKind,
+        Operator,
+    },
     parse::{
         entity_selector::entity_selector,
         lexer::{ident, parse_tokens, specified_punct, string, Lexer, Punct},
         parse_file::{parse_expr, to_anyhow_result},
-        MacroCall,
+        span::SpannedError,
+        MacroCall, Type,
     },
 };
 
-use super::read_def::ConstValue;
+use super::{read_def::ConstValue, CONST_MINUS_ONE};
+
+/// 一条消息中最多的运行时定点数与`bool`，每多一个生成的命令数量翻倍
+const MAX_FMT_SWITCHES: usize = 5;
+
+/// 格式化字符串中的一段，定点数需要先拆分
+enum FmtPiece<'a> {
+    Arg(FormatArgument<'a>),
+    Fixed(CacheTag<'a>),
+    ConstFixed(i32),
+    Bool(CacheTag<'a>),
+}
 
 impl<'a> Atoi<'a> {
     pub(super) fn call_macro(&self, MacroCall { .. }: &MacroCall<'a>) -> Option<Lexer<'a>> {
@@ -38,6 +53,8 @@ impl<'a> Atoi<'a> {
         for c in consts {
             match c {
                 ConstValue::Int(i) => write!(output, "{i}").unwrap(),
+                ConstValue::Fixed(i) => output.push_str(&format_fixed(i, self.fixed_scale)),
+                ConstValue::Bool(b) => write!(output, "{b}").unwrap(),
                 ConstValue::Str(s) => output.push_str(s),
             }
         }
@@ -63,24 +80,34 @@ impl<'a> Atoi<'a> {
         Ok(())
     }
 
-    pub(super) fn macro_print(&self, insts: &mut Vec<Ir<'a>>, lexer: Lexer<'a>) -> Result<()> {
-        let (mut prefix, string) = to_anyhow_result(separated_pair(
+    pub(super) fn macro_print(
+        &mut self,
+        insts: &mut Vec<Ir<'a>>,
+        lexer: Lexer<'a>,
+        cache_offset: u32,
+    ) -> Result<()> {
+        let (selector, string) = to_anyhow_result(separated_pair(
             entity_selector,
             specified_punct(Punct::Comma),
             string,
         )(lexer))?;
 
-        prefix.insert_str(0, "tellraw ");
-
         let formatted = self.formatted_args(string)?;
-        insts.push(Ir::CmdFmt {
-            prefix,
-            args: formatted,
-        });
-        Ok(())
+        self.push_fmt(
+            insts,
+            FmtCommand::Tellraw,
+            selector,
+            formatted,
+            cache_offset,
+        )
     }
 
-    pub(super) fn macro_title(&self, insts: &mut Vec<Ir<'a>>, lexer: Lexer<'a>) -> Result<()> {
+    pub(super) fn macro_title(
+        &mut self,
+        insts: &mut Vec<Ir<'a>>,
+        lexer: Lexer<'a>,
+        cache_offset: u32,
+    ) -> Result<()> {
         let (selector, (position, fmt_str)) = to_anyhow_result(separated_pair(
             entity_selector,
             specified_punct(Punct::Comma),
@@ -92,26 +119,176 @@ impl<'a> Atoi<'a> {
         )(lexer))?;
 
         let formatted = self.formatted_args(fmt_str)?;
-        insts.push(Ir::CmdFmt {
-            prefix: format!("titleraw {selector} {position}"),
-            args: formatted,
+        let cmd = FmtCommand::Titleraw(position);
+        self.push_fmt(insts, cmd, selector, formatted, cache_offset)
+    }
+
+    /// 定点数拆分为整数部分与小数的每一位分别显示。整数部分取绝对值，负号与`bool`的`true`、`false`
+    /// 作为文本显示，因此按每个定点数的符号与`bool`的值生成一条命令，由这些位组成的下标查表选择。
+    /// 命令数量随这些值的个数指数增长，个数由[`MAX_FMT_SWITCHES`]限制
+    fn push_fmt(
+        &mut self,
+        insts: &mut Vec<Ir<'a>>,
+        cmd: FmtCommand<'a>,
+        selector: String,
+        pieces: Vec<FmtPiece<'a>>,
+        mut cache_offset: u32,
+    ) -> Result<()> {
+        let digits = self.fixed_scale.ilog10();
+        let mut new_reg = || CacheTag::Regular(get_anonymous_id(&mut cache_offset));
+        let op = |dst, opr, src| Ir::Operation { dst, opr, src };
+
+        let mut bits = Vec::new();
+        let mut fixed = Vec::new();
+        for piece in &pieces {
+            let value = match *piece {
+                FmtPiece::Fixed(value) => value,
+                FmtPiece::Bool(value) => {
+                    bits.push(value);
+                    continue;
+                }
+                _ => continue,
+            };
+            let scale = self.fixed_scale_reg()?;
+            let (abs, neg, temp, int) = (new_reg(), new_reg(), new_reg(), new_reg());
+            insts.extend([
+                op(abs, Operator::Set, value),
+                Ir::BoolOperation {
+                    dst: neg,
+                    lhs: abs,
+                    opr: BoolOperator::Lt,
+                    rhs: BoolOprRhs::Constant(0),
+                },
+                op(temp, Operator::Set, abs),
+                op(temp, Operator::Mul, CONST_MINUS_ONE),
+                op(abs, Operator::Max, temp),
+                op(int, Operator::Set, abs),
+                op(int, Operator::Div, scale),
+                op(abs, Operator::Rem, scale),
+                Ir::Assign {
+                    dst: temp,
+                    value: 10,
+                },
+            ]);
+            // 从最低位开始取出小数的每一位
+            let frac: Vec<_> = (0..digits).map(|_| new_reg()).collect();
+            for &digit in frac.iter().rev() {
+                insts.extend([
+                    op(digit, Operator::Set, abs),
+                    op(digit, Operator::Rem, temp),
+                    op(abs, Operator::Div, temp),
+                ]);
+            }
+            bits.push(neg);
+            fixed.push((int, frac));
+        }
+
+        let scale = self.fixed_scale.unsigned_abs();
+        let args = |mask: u32| {
+            let mut args = Vec::new();
+            let mut bits = (0..).map(|index| mask >> index & 1 == 1);
+            let mut fixed = fixed.iter();
+            for piece in &pieces {
+                match piece {
+                    FmtPiece::Arg(arg) => args.push(arg.clone()),
+                    FmtPiece::Bool(_) => {
+                        let text = if bits.next().unwrap() {
+                            "true"
+                        } else {
+                            "false"
+                        };
+                        args.push(FormatArgument::Text(text));
+                    }
+                    FmtPiece::Fixed(_) => {
+                        let (int, frac) = fixed.next().unwrap();
+                        if bits.next().unwrap() {
+                            args.push(FormatArgument::Text("-"));
+                        }
+                        args.push(FormatArgument::CacheTag(*int));
+                        args.push(FormatArgument::Text("."));
+                        args.extend(frac.iter().map(|&digit| FormatArgument::CacheTag(digit)));
+                    }
+                    FmtPiece::ConstFixed(value) => {
+                        if *value < 0 {
+                            args.push(FormatArgument::Text("-"));
+                        }
+                        let int = (value.unsigned_abs() / scale) as i32;
+                        args.push(FormatArgument::ConstInt(int));
+                        args.push(FormatArgument::Text("."));
+                        let mut frac = value.unsigned_abs() % scale;
+                        let mut frac_digits = Vec::new();
+                        for _ in 0..digits {
+                            frac_digits.push(FormatArgument::ConstInt((frac % 10) as i32));
+                            frac /= 10;
+                        }
+                        args.extend(frac_digits.into_iter().rev());
+                    }
+                }
+            }
+            args
+        };
+
+        if bits.is_empty() {
+            insts.push(Ir::CmdFmt {
+                cmd,
+                selector,
+                args: args(0),
+            });
+            return Ok(());
+        }
+
+        // 下标的第`k`位为第`k`个`bool`的值或定点数是否为负数
+        let index = new_reg();
+        insts.push(Ir::Assign {
+            dst: index,
+            value: 0,
+        });
+        for bit in bits.iter().rev() {
+            insts.push(op(index, Operator::Add, index));
+            insts.push(op(index, Operator::Add, *bit));
+        }
+
+        let mut sorted_arms = Vec::new();
+        for mask in 0..1 << bits.len() {
+            let mut info = self.new_label(LabelKind::Print);
+            info.insts.push(Ir::CmdFmt {
+                cmd,
+                selector: selector.clone(),
+                args: args(mask as u32),
+            });
+            let range = ArmRange {
+                start: mask,
+                end: mask,
+            };
+            sorted_arms.push((Some(range), info.label));
+            self.label_map.insert_label(info)?;
+        }
+        insts.push(Ir::Table {
+            cond: index,
+            sorted_arms,
         });
         Ok(())
     }
 
-    fn formatted_args(&self, input: &'a str) -> Result<Vec<FormatArgument<'a>>> {
-        let get_bind = |name: &str| {
-            self.bindings.find_newest(name).map(|bind| match bind {
-                Binding::Cache(c) => FormatArgument::CacheTag(*c),
-                Binding::Constant(i) => FormatArgument::ConstInt(*i),
-                Binding::String(s) => FormatArgument::Text(s),
+    fn formatted_args(&self, input: &'a str) -> Result<Vec<FmtPiece<'a>>> {
+        let get_bind = |name: &'a str| {
+            self.find_binding(name).map(|bind| match bind {
+                Binding::Cache(c, Type::Int) => FmtPiece::Arg(FormatArgument::CacheTag(c)),
+                Binding::Cache(c, Type::Fixed) => FmtPiece::Fixed(c),
+                Binding::Constant(i, Type::Int) => FmtPiece::Arg(FormatArgument::ConstInt(i)),
+                Binding::Constant(i, Type::Fixed) => FmtPiece::ConstFixed(i),
+                Binding::Cache(c, Type::Bool) => FmtPiece::Bool(c),
+                Binding::Constant(i, Type::Bool) => {
+                    FmtPiece::Arg(FormatArgument::Text(if i != 0 { "true" } else { "false" }))
+                }
+                Binding::String(s) => FmtPiece::Arg(FormatArgument::Text(s)),
             })
         };
 
         let selector = map_opt(parse_tokens, |tokens| {
             pair(entity_selector, eof)(Lexer::from(tokens))
                 .ok()
-                .map(|(_, (s, _))| FormatArgument::Selector(s))
+                .map(|(_, (s, _))| FmtPiece::Arg(FormatArgument::Selector(s)))
         });
 
         let parse_ident = || recognize(many0_count(alt((alphanumeric1, tag("_")))));
@@ -119,28 +296,53 @@ impl<'a> Atoi<'a> {
         let parse_option = alt((
             map_opt(parse_ident(), get_bind),
             map_opt(preceded(tag("#"), parse_ident()), |name| {
-                FormatStyle::from_name(name).map(FormatArgument::Style)
+                FormatStyle::from_name(name)
+                    .map(|style| FmtPiece::Arg(FormatArgument::Style(style)))
             }),
             selector,
         ));
 
+        let text = |s| FmtPiece::Arg(FormatArgument::Text(s));
         let r: nom::IResult<_, _> = terminated(
             many0(alt((
-                map(tag("{{"), |_| FormatArgument::Text("{")),
-                map(tag("}}"), |_| FormatArgument::Text("}")),
-                map(is_not("{}"), FormatArgument::Text),
+                map(tag("{{"), |_| text("{")),
+                map(tag("}}"), |_| text("}")),
+                map(is_not("{}"), text),
                 delimited(tag("{"), parse_option, tag("}")),
             ))),
             eof,
         )(input);
 
-        match r {
-            Ok((_, r)) => Ok(r),
-            Err(err) => Err(anyhow!("cannot format the string: {err}")),
+        let pieces = match r {
+            Ok((_, r)) => r,
+            Err(err) => {
+                return Err(SpannedError::at(
+                    input,
+                    format_args!("cannot format the string: {err}"),
+                ))
+            }
+        };
+
+        let switches = pieces
+            .iter()
+            .filter(|piece| matches!(piece, FmtPiece::Fixed(_) | FmtPiece::Bool(_)))
+            .count();
+        if switches > MAX_FMT_SWITCHES {
+            return Err(SpannedError::at(
+                input,
+                format_args!(
+                    "a message can show at most {MAX_FMT_SWITCHES} runtime `fixed` or `bool` \
+                    values, found {switches}, split it into several messages"
+                ),
+            ));
         }
+        Ok(pieces)
     }
 }
 
 pub(super) fn macro_not_found(name: &str) -> anyhow::Error {
-    anyhow!("macro `{name}` not defined or not available on this situation")
+    SpannedError::at(
+        name,
+        format_args!("macro `{name}` not defined or not available on this situation"),
+    )
 }
@@ -0,0 +1,648 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    ir::{ArmRange, BoolOperator, BoolOprRhs, CacheTag, Ir, Label, LabelInfo, Operator},
+    parse::{span::SpannedError, Expr, ExprFnCall, Type},
+};
+
+use super::{fixed::const_promote, types::join_ty, CONST_MINUS_ONE};
+use crate::atoi::{get_anonymous_id, lint::expr_fragment, Atoi};
+
+/// `lerp`的`t`为千分比
+const LERP_SCALE: i32 = 1000;
+
+/// 数学函数与参数个数
+const MATH_FUNCTIONS: &[(&str, usize)] = &[
+    ("abs", 1),
+    ("sign", 1),
+    ("clamp", 3),
+    ("pow", 2),
+    ("sqrt", 1),
+    ("gcd", 2),
+    ("lerp", 3),
+    ("sin", 1),
+    ("cos", 1),
+];
+
+/// 辅助函数的参数与结果，辅助函数只使用这些寄存器，调用时不需要保存缓存
+const REG_MATH_A: CacheTag = CacheTag::StaticBuiltin("MathA");
+const REG_MATH_B: CacheTag = CacheTag::StaticBuiltin("MathB");
+const REG_MATH_C: CacheTag = CacheTag::StaticBuiltin("MathC");
+const REG_MATH_RESULT: CacheTag = CacheTag::StaticBuiltin("MathResult");
+const REG_MATH_TEMP: CacheTag = CacheTag::StaticBuiltin("MathTemp");
+const REG_MATH_CONST: CacheTag = CacheTag::StaticBuiltin("MathConst");
+
+fn find_math_function(name: &str) -> Option<(&'static str, usize)> {
+    MATH_FUNCTIONS.iter().find(|(n, _)| *n == name).copied()
+}
+
+fn check_arity<T>(name: &str, args: &[T], arity: usize) -> Result<()> {
+    if args.len() == arity {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "builtin function `{name}` requires {arity} arguments, but {} was provided",
+            args.len()
+        ))
+    }
+}
+
+/// `sin`与`cos`的结果为定点数，其他函数的结果为整数
+fn returns_fixed(name: &str) -> bool {
+    matches!(name, "sin" | "cos")
+}
+
+/// 编译期计算数学函数，`name`不是数学函数时返回`None`
+pub(in crate::atoi) fn const_math(name: &str, args: &[i32], scale: i32) -> Option<Result<i32>> {
+    let arity = match name {
+        "min" | "max" => 2,
+        _ => find_math_function(name)?.1,
+    };
+    if let Err(err) = check_arity(name, args, arity) {
+        return Some(Err(err));
+    }
+
+    let r = match (name, args) {
+        ("min", &[a, b]) => a.min(b),
+        ("max", &[a, b]) => a.max(b),
+        ("abs", &[x]) => x.wrapping_abs(),
+        ("sign", &[x]) => x.signum(),
+        ("clamp", &[x, min, max]) => x.max(min).min(max),
+        ("pow", &[base, exp]) => match u32::try_from(exp) {
+            Ok(exp) => base.wrapping_pow(exp),
+            Err(_) => 0,
+        },
+        ("sqrt", &[x]) => isqrt(x),
+        ("gcd", &[a, b]) => gcd(a, b),
+        // 与记分板的除法相同，向下取整
+        ("lerp", &[a, b, t]) => {
+            a.wrapping_add(b.wrapping_sub(a).wrapping_mul(t).div_euclid(LERP_SCALE))
+        }
+        ("sin", &[deg]) => sin(deg, scale),
+        ("cos", &[deg]) => sin(deg.wrapping_add(90), scale),
+        _ => unreachable!(),
+    };
+    Some(Ok(r))
+}
+
+/// 向下取整的平方根，负数为0
+fn isqrt(x: i32) -> i32 {
+    if x < 2 {
+        return x.max(0);
+    }
+    // 与运行时的牛顿迭代相同
+    let mut r = x / 2 + 1;
+    loop {
+        let y = (r + x / r) / 2;
+        if y >= r {
+            return r;
+        }
+        r = y;
+    }
+}
+
+/// 结果为`2^31`时回绕为`i32::MIN`（如`gcd(i32::MIN, 0)`），其余结果非负，与运行时相同
+fn gcd(a: i32, b: i32) -> i32 {
+    let (mut a, mut b) = ((a as i64).abs(), (b as i64).abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a as i32
+}
+
+/// 角度制，结果为以`scale`为倍数的定点数，四舍五入
+fn sin(deg: i32, scale: i32) -> i32 {
+    let deg = deg.rem_euclid(360);
+    let (sign, deg) = if deg >= 180 {
+        (-1, deg - 180)
+    } else {
+        (1, deg)
+    };
+    sign * sin_table(deg.min(180 - deg), scale)
+}
+
+/// `0..=90`度的正弦值
+fn sin_table(deg: i32, scale: i32) -> i32 {
+    ((deg as f64).to_radians().sin() * scale as f64).round() as i32
+}
+
+impl<'a> Atoi<'a> {
+    /// 在运行时调用数学函数，`name`不是数学函数时返回`false`
+    pub(super) fn call_math_function(
+        &mut self,
+        ExprFnCall {
+            name: call_name,
+            args,
+        }: &ExprFnCall<'a>,
+        insts: &mut Vec<Ir<'a>>,
+        dst: CacheTag<'a>,
+        mut cache_offset: u32,
+    ) -> Result<Option<Type>> {
+        let Some((name, arity)) = find_math_function(call_name) else {
+            return Ok(None);
+        };
+        check_arity(name, args, arity).map_err(|err| SpannedError::at(call_name, err))?;
+
+        let mut regs = Vec::new();
+        let mut tys = Vec::new();
+        for arg in args {
+            let (reg, ty) = self.read_typed_expr_at_next_reg(arg, insts, &mut cache_offset)?;
+            regs.push(reg);
+            tys.push(ty);
+        }
+
+        // `abs`、`clamp`与`lerp`的端点可以是定点数，`t`与其他函数的参数只能是整数
+        let ty = match name {
+            "abs" | "clamp" => tys.iter().copied().fold(Type::Int, join_ty),
+            "lerp" => join_ty(tys[0], tys[1]),
+            _ => Type::Int,
+        };
+        for (index, (arg, (&reg, &arg_ty))) in args.iter().zip(regs.iter().zip(&tys)).enumerate() {
+            let expected = match name {
+                "sign" => join_ty(arg_ty, arg_ty),
+                "lerp" if index == 2 => Type::Int,
+                _ => ty,
+            };
+            let at = expr_fragment(arg).unwrap_or(call_name);
+            self.convert_ty(insts, reg, arg_ty, expected, at)?;
+        }
+
+        // 简单的函数直接展开
+        match (name, &*regs) {
+            ("abs", &[x]) => {
+                insts.push(op(dst, Operator::Set, x));
+                insts.push(op(x, Operator::Mul, CONST_MINUS_ONE));
+                insts.push(op(dst, Operator::Max, x));
+            }
+            ("sign", &[x]) => {
+                insts.push(op(dst, Operator::Set, x));
+                insts.push(Ir::Assign { dst: x, value: 1 });
+                insts.push(op(dst, Operator::Min, x));
+                insts.push(op(dst, Operator::Max, CONST_MINUS_ONE));
+            }
+            ("clamp", &[x, min, max]) => {
+                insts.push(op(dst, Operator::Set, x));
+                insts.push(op(dst, Operator::Max, min));
+                insts.push(op(dst, Operator::Min, max));
+            }
+            ("lerp", &[a, b, t]) => {
+                insts.push(op(dst, Operator::Set, b));
+                insts.push(op(dst, Operator::Sub, a));
+                insts.push(op(dst, Operator::Mul, t));
+                insts.push(Ir::Assign {
+                    dst: t,
+                    value: LERP_SCALE,
+                });
+                insts.push(op(dst, Operator::Div, t));
+                insts.push(op(dst, Operator::Add, a));
+            }
+            _ => self.call_math_helper(name, &regs, insts, dst)?,
+        }
+        Ok(Some(if returns_fixed(name) { Type::Fixed } else { ty }))
+    }
+
+    /// 把参数传入`MathA`与`MathB`后调用辅助标签，结果写入`dst`
+    pub(super) fn call_math_helper(
+        &mut self,
+        name: &'static str,
+        regs: &[CacheTag<'a>],
+        insts: &mut Vec<Ir<'a>>,
+        dst: CacheTag<'a>,
+    ) -> Result<()> {
+        if self.math_helpers.is_empty() {
+            for reg in [
+                REG_MATH_A,
+                REG_MATH_B,
+                REG_MATH_C,
+                REG_MATH_RESULT,
+                REG_MATH_TEMP,
+                REG_MATH_CONST,
+            ] {
+                self.label_map.insert_static(reg, 0)?;
+            }
+        }
+        let helper = self.math_helper(name)?;
+        for (reg, arg) in [REG_MATH_A, REG_MATH_B].into_iter().zip(regs) {
+            insts.push(op(reg, Operator::Set, *arg));
+        }
+        insts.push(Ir::Call { label: helper });
+        insts.push(op(dst, Operator::Set, REG_MATH_RESULT));
+        Ok(())
+    }
+
+    /// 需要循环或查表的函数生成为辅助标签，每个程序只生成一次
+    fn math_helper(&mut self, name: &'static str) -> Result<Label<'a>> {
+        if let Some(label) = self.math_helpers.get(name) {
+            return Ok(*label);
+        }
+        let label = match name {
+            "pow" => self.helper_pow()?,
+            "sqrt" => self.helper_sqrt()?,
+            "gcd" => self.helper_gcd()?,
+            "sin" => self.helper_sin()?,
+            "random" => self.helper_random()?,
+            "cos" => {
+                let sin = self.math_helper("sin")?;
+                self.helper(vec![
+                    Ir::Increase {
+                        dst: REG_MATH_A,
+                        value: 90,
+                    },
+                    Ir::Call { label: sin },
+                ])?
+            }
+            _ => unreachable!("`{name}` has no helper"),
+        };
+        self.math_helpers.insert(name, label);
+        Ok(label)
+    }
+
+    fn helper(&mut self, insts: Vec<Ir<'a>>) -> Result<Label<'a>> {
+        let mut info = LabelInfo::new(Label::Anonymous(get_anonymous_id(
+            &mut self.anonymous_label_pool,
+        )));
+        info.insts = insts;
+        let label = info.label;
+        self.label_map.insert_label(info)?;
+        Ok(label)
+    }
+
+    /// 快速幂，指数为负数时结果为0
+    fn helper_pow(&mut self) -> Result<Label<'a>> {
+        let (a, b, r, t, k) = (
+            REG_MATH_A,
+            REG_MATH_B,
+            REG_MATH_RESULT,
+            REG_MATH_TEMP,
+            REG_MATH_CONST,
+        );
+        let multiply = self.helper(vec![op(r, Operator::Mul, a)])?;
+        let mut step = LabelInfo::new(Label::Anonymous(get_anonymous_id(
+            &mut self.anonymous_label_pool,
+        )));
+        let step_label = step.label;
+        let check = self.helper(vec![
+            compare(t, b, BoolOperator::Gt, 0),
+            cond(t, step_label),
+        ])?;
+        step.insts = vec![
+            Ir::Assign { dst: k, value: 2 },
+            op(t, Operator::Set, b),
+            op(t, Operator::Rem, k),
+            cond(t, multiply),
+            op(a, Operator::Mul, a),
+            op(b, Operator::Div, k),
+            Ir::Call { label: check },
+        ];
+        self.label_map.insert_label(step)?;
+
+        self.helper(vec![
+            compare(r, b, BoolOperator::Ge, 0),
+            Ir::Call { label: check },
+        ])
+    }
+
+    /// 牛顿迭代，从`x / 2 + 1`开始逼近，负数为0
+    fn helper_sqrt(&mut self) -> Result<Label<'a>> {
+        let (a, b, r, t, k) = (
+            REG_MATH_A,
+            REG_MATH_B,
+            REG_MATH_RESULT,
+            REG_MATH_TEMP,
+            REG_MATH_CONST,
+        );
+        let mut iterate = LabelInfo::new(Label::Anonymous(get_anonymous_id(
+            &mut self.anonymous_label_pool,
+        )));
+        let iterate_label = iterate.label;
+        let next = self.helper(vec![
+            op(r, Operator::Set, b),
+            Ir::Call {
+                label: iterate_label,
+            },
+        ])?;
+        iterate.insts = vec![
+            op(b, Operator::Set, a),
+            op(b, Operator::Div, r),
+            op(b, Operator::Add, r),
+            Ir::Assign { dst: k, value: 2 },
+            op(b, Operator::Div, k),
+            Ir::BoolOperation {
+                dst: t,
+                lhs: b,
+                opr: BoolOperator::Lt,
+                rhs: BoolOprRhs::CacheTag(r),
+            },
+            cond(t, next),
+        ];
+        self.label_map.insert_label(iterate)?;
+
+        let init = self.helper(vec![
+            op(r, Operator::Set, a),
+            Ir::Assign { dst: k, value: 2 },
+            op(r, Operator::Div, k),
+            Ir::Increase { dst: r, value: 1 },
+            Ir::Call {
+                label: iterate_label,
+            },
+        ])?;
+        self.helper(vec![
+            op(r, Operator::Set, a),
+            Ir::Assign { dst: k, value: 0 },
+            op(r, Operator::Max, k),
+            compare(t, a, BoolOperator::Gt, 1),
+            cond(t, init),
+        ])
+    }
+
+    /// 辗转相除，与编译期的[`gcd`]相同，只有结果为`2^31`时回绕为`i32::MIN`
+    fn helper_gcd(&mut self) -> Result<Label<'a>> {
+        let (a, b, r, t) = (REG_MATH_A, REG_MATH_B, REG_MATH_RESULT, REG_MATH_TEMP);
+        let mut check = LabelInfo::new(Label::Anonymous(get_anonymous_id(
+            &mut self.anonymous_label_pool,
+        )));
+        let check_label = check.label;
+        let step = self.helper(vec![
+            op(t, Operator::Set, a),
+            op(t, Operator::Rem, b),
+            op(a, Operator::Set, b),
+            op(b, Operator::Set, t),
+            Ir::Call { label: check_label },
+        ])?;
+        check.insts = vec![
+            op(r, Operator::Set, a),
+            compare(t, b, BoolOperator::NotEqual, 0),
+            cond(t, step),
+        ];
+        self.label_map.insert_label(check)?;
+
+        // 负数参与时余数可能为负，最后再取绝对值。
+        // 结果为`i32::MIN`时乘以-1仍为`i32::MIN`，例如`gcd(i32::MIN, 0)`
+        self.helper(vec![
+            Ir::Call { label: check_label },
+            op(t, Operator::Set, r),
+            op(t, Operator::Mul, CONST_MINUS_ONE),
+            op(r, Operator::Max, t),
+        ])
+    }
+
+    /// 把角度化到`0..=90`后查表，`MathB`记录符号
+    fn helper_sin(&mut self) -> Result<Label<'a>> {
+        let (a, b, r, t, k) = (
+            REG_MATH_A,
+            REG_MATH_B,
+            REG_MATH_RESULT,
+            REG_MATH_TEMP,
+            REG_MATH_CONST,
+        );
+        let negate = self.helper(vec![
+            Ir::Increase {
+                dst: a,
+                value: -180,
+            },
+            Ir::Assign { dst: b, value: -1 },
+        ])?;
+        let mirror = self.helper(vec![
+            op(a, Operator::Mul, CONST_MINUS_ONE),
+            Ir::Increase { dst: a, value: 180 },
+        ])?;
+
+        // 相邻的相同取值合并为一个区间
+        let mut sorted_arms: Vec<(Option<ArmRange>, Label<'a>)> = Vec::new();
+        let mut last_value = None;
+        for deg in 0..=90 {
+            let value = sin_table(deg, self.fixed_scale);
+            if last_value == Some(value) {
+                if let Some((Some(range), _)) = sorted_arms.last_mut() {
+                    range.end = deg;
+                }
+                continue;
+            }
+            last_value = Some(value);
+            let label = self.helper(vec![Ir::Assign { dst: r, value }])?;
+            sorted_arms.push((
+                Some(ArmRange {
+                    start: deg,
+                    end: deg,
+                }),
+                label,
+            ));
+        }
+
+        self.helper(vec![
+            // `%=`向负无穷取整，余数总在`0..360`中
+            Ir::Assign { dst: k, value: 360 },
+            op(a, Operator::Rem, k),
+            Ir::Assign { dst: b, value: 1 },
+            compare(t, a, BoolOperator::Ge, 180),
+            cond(t, negate),
+            compare(t, a, BoolOperator::Gt, 90),
+            cond(t, mirror),
+            Ir::Table {
+                cond: a,
+                sorted_arms,
+            },
+            op(r, Operator::Mul, b),
+        ])
+    }
+
+    /// `MathA..=MathB`中的均匀随机数，`MathA > MathB`时结果为`MathA`。
+    ///
+    /// 单条命令的范围最多有`i32::MAX`个值：不超过该长度时在`0..i32::MAX`中取值，
+    /// 丢弃末尾不足一个周期的部分后取余；否则拼接两个16位的随机数得到任意`i32`，丢弃范围外的值。
+    /// 两种情况每次被丢弃的概率都小于一半
+    fn helper_random(&mut self) -> Result<Label<'a>> {
+        let (a, b, c, r, t, k) = (
+            REG_MATH_A,
+            REG_MATH_B,
+            REG_MATH_C,
+            REG_MATH_RESULT,
+            REG_MATH_TEMP,
+            REG_MATH_CONST,
+        );
+        let mut draw = LabelInfo::new(Label::Anonymous(get_anonymous_id(
+            &mut self.anonymous_label_pool,
+        )));
+        let draw_label = draw.label;
+        draw.insts = vec![
+            Ir::Random {
+                dst: r,
+                min: 0,
+                max: i32::MAX - 1,
+            },
+            Ir::BoolOperation {
+                dst: t,
+                lhs: r,
+                opr: BoolOperator::Ge,
+                rhs: BoolOprRhs::CacheTag(k),
+            },
+            cond(t, draw_label),
+        ];
+        self.label_map.insert_label(draw)?;
+        let small = self.helper(vec![
+            Ir::Increase { dst: c, value: 1 },
+            Ir::Assign {
+                dst: k,
+                value: i32::MAX,
+            },
+            op(k, Operator::Div, c),
+            op(k, Operator::Mul, c),
+            Ir::Call { label: draw_label },
+            op(r, Operator::Rem, c),
+            op(r, Operator::Add, a),
+        ])?;
+
+        let mut draw_full = LabelInfo::new(Label::Anonymous(get_anonymous_id(
+            &mut self.anonymous_label_pool,
+        )));
+        let draw_full_label = draw_full.label;
+        draw_full.insts = vec![
+            Ir::Random {
+                dst: r,
+                min: -32768,
+                max: 32767,
+            },
+            Ir::Assign {
+                dst: k,
+                value: 65536,
+            },
+            op(r, Operator::Mul, k),
+            Ir::Random {
+                dst: t,
+                min: 0,
+                max: 65535,
+            },
+            op(r, Operator::Add, t),
+            Ir::BoolOperation {
+                dst: t,
+                lhs: r,
+                opr: BoolOperator::Lt,
+                rhs: BoolOprRhs::CacheTag(a),
+            },
+            Ir::BoolOperation {
+                dst: k,
+                lhs: r,
+                opr: BoolOperator::Gt,
+                rhs: BoolOprRhs::CacheTag(b),
+            },
+            op(t, Operator::Add, k),
+            cond(t, draw_full_label),
+        ];
+        self.label_map.insert_label(draw_full)?;
+
+        // `MathC`为`max - min`，溢出时为负数
+        let ordered = self.helper(vec![
+            op(c, Operator::Set, b),
+            op(c, Operator::Sub, a),
+            compare(t, c, BoolOperator::Ge, 0),
+            compare(k, c, BoolOperator::Lt, i32::MAX),
+            op(t, Operator::Mul, k),
+            // 接受时`MathTemp`为0，因此不会再进入下一个分支
+            Ir::Cond {
+                positive: false,
+                cond: t,
+                then: draw_full_label,
+            },
+            cond(t, small),
+        ])?;
+        self.helper(vec![
+            op(r, Operator::Set, a),
+            Ir::BoolOperation {
+                dst: t,
+                lhs: b,
+                opr: BoolOperator::Ge,
+                rhs: BoolOprRhs::CacheTag(a),
+            },
+            cond(t, ordered),
+        ])
+    }
+
+    /// 编译期计算数学函数的调用
+    pub(super) fn read_const_call(
+        &self,
+        ExprFnCall { name, args }: &ExprFnCall<'a>,
+    ) -> Result<super::ConstValue<'a>> {
+        let calling_error = || {
+            SpannedError::at(
+                name,
+                "calling a function is cannot be a constant operation yet",
+            )
+        };
+        if self.functions.find_newest_entry(name).is_some() {
+            return Err(calling_error());
+        }
+
+        // 类型转换
+        if let ("int" | "fixed" | "bool", [arg]) = (*name, &**args) {
+            return match (*name, self.read_constant(arg)?) {
+                (_, super::ConstValue::Str(_)) => {
+                    Err(anyhow!("string cannot be a function argument"))
+                }
+                ("int", super::ConstValue::Fixed(value)) => {
+                    Ok(super::ConstValue::Int(value.div_euclid(self.fixed_scale)))
+                }
+                ("int", super::ConstValue::Bool(value)) => Ok(super::ConstValue::Int(value as _)),
+                ("fixed", super::ConstValue::Int(value)) => Ok(super::ConstValue::Fixed(
+                    const_promote(value, self.fixed_scale)?,
+                )),
+                ("fixed", super::ConstValue::Bool(_)) => Err(anyhow!(
+                    "mismatched types: expected `fixed`, found `bool`, \
+                    use `fixed(int(...))` to convert"
+                )),
+                ("bool", super::ConstValue::Int(value) | super::ConstValue::Fixed(value)) => {
+                    Ok(super::ConstValue::Bool(value != 0))
+                }
+                (_, value) => Ok(value),
+            };
+        }
+
+        let args = args
+            .iter()
+            .map(|arg| self.read_const_int(arg))
+            .collect::<Result<Vec<_>>>()?;
+        match const_math(name, &args, self.fixed_scale) {
+            Some(r) => r
+                .map(if returns_fixed(name) {
+                    super::ConstValue::Fixed
+                } else {
+                    super::ConstValue::Int
+                })
+                .map_err(|err| SpannedError::at(name, err)),
+            None => Err(calling_error()),
+        }
+    }
+
+    fn read_const_int(&self, expr: &Expr<'a>) -> Result<i32> {
+        match self.read_constant(expr)? {
+            super::ConstValue::Int(int) => Ok(int),
+            super::ConstValue::Fixed(_) => Err(anyhow!(
+                "fixed-point value cannot be an argument of constant math functions"
+            )),
+            super::ConstValue::Bool(_) => Err(anyhow!(
+                "mismatched types: expected `int`, found `bool`, use `int(...)` to convert"
+            )),
+            super::ConstValue::Str(_) => Err(anyhow!("string cannot be a function argument")),
+        }
+    }
+}
+
+fn op<'a>(dst: CacheTag<'a>, opr: Operator, src: CacheTag<'a>) -> Ir<'a> {
+    Ir::Operation { dst, opr, src }
+}
+
+fn compare<'a>(dst: CacheTag<'a>, lhs: CacheTag<'a>, opr: BoolOperator, rhs: i32) -> Ir<'a> {
+    Ir::BoolOperation {
+        dst,
+        lhs,
+        opr,
+        rhs: BoolOprRhs::Constant(rhs),
+    }
+}
+
+fn cond<'a>(cond: CacheTag<'a>, then: Label<'a>) -> Ir<'a> {
+    Ir::Cond {
+        positive: true,
+        cond,
+        then,
+    }
+}
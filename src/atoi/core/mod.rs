@@ -1,24 +1,33 @@
 use anyhow::{anyhow, Result};
 
 use crate::{
-    ir::{BoolOperator, CacheTag, Ir, Label, LabelInfo, Operator},
+    ir::{BoolOperator, CacheTag, Ir, Label, LabelInfo, LabelKind, LabelOrigin, Operator},
     parse::{lexer::Punct, ItemFn},
 };
 
+pub use self::fixed::DEFAULT_FIXED_SCALE;
+pub(super) use self::read_def::ConstValue;
+
 use self::read_stmt::ReadStmtWorkflow;
 
-use super::{get_fn_label, Atoi, Binding, FuncDef};
+use super::{get_fn_label, symbols::SymbolKind, Atoi, Binding};
 
+mod fixed;
 mod macros;
+mod math;
 mod read_def;
 mod read_expr;
+mod read_match;
 mod read_stmt;
+mod types;
 
 const FRAME_HEAD_LENGTH: u32 = 1;
 
 pub(super) const REG_CURRENT_MEM_OFFSET: CacheTag = CacheTag::StaticBuiltin("CurrentMemoryOffset");
 pub(super) const REG_RETURNED_VALUE: CacheTag = CacheTag::StaticBuiltin("ReturnedValue");
 pub(super) const REG_COND_ENABLE: CacheTag = CacheTag::StaticBuiltin("CondEnable");
+/// 与`REG_COND_ENABLE`分开，使循环不影响外层`if`的判断
+pub(super) const REG_LOOP_ENABLE: CacheTag = CacheTag::StaticBuiltin("LoopEnable");
 pub(super) const CONST_MINUS_ONE: CacheTag = CacheTag::StaticBuiltin("MinusOne");
 const REG_PARENT_MEM_OFFSET: CacheTag = CacheTag::Regular(0);
 
@@ -26,14 +35,31 @@ impl<'a> Atoi<'a> {
     pub fn insert_fn(
         &mut self,
         item_fn @ ItemFn {
+            attrs,
             export,
             name,
             args,
             body,
+            ..
         }: &ItemFn<'a>,
     ) -> Result<()> {
         self.bindings.delimite();
-        let info = self.new_label();
+        self.current_fn = name;
+        self.anonymous_branch_pool = 0;
+        let (_, def) = self
+            .functions
+            .find_newest_entry(name)
+            .expect("function should be defined by `read_def` before inserted");
+        let def = def.clone();
+        self.current_ret = def.ret;
+        let info = LabelInfo::with_origin(
+            def.label,
+            LabelOrigin {
+                function: name,
+                kind: LabelKind::Function,
+                at: None,
+            },
+        );
 
         if *export {
             if !args.is_empty() {
@@ -45,31 +71,26 @@ impl<'a> Atoi<'a> {
             self.insert_entry_fn(get_fn_label(item_fn), info.label)?;
         }
 
-        self.functions.push(
-            name,
-            FuncDef {
-                label: info.label,
-                arg_count: args.len() as _,
-            },
-        );
-
         let mut cache_offset = FRAME_HEAD_LENGTH;
 
-        for arg in args.iter().copied() {
-            self.bindings
-                .push(arg, Binding::Cache(CacheTag::Regular(cache_offset)));
+        for (arg, &ty) in args.iter().zip(def.args.iter()) {
+            self.bindings.push(
+                arg.name,
+                Binding::Cache(CacheTag::Regular(cache_offset), ty),
+            );
+            self.symbols
+                .get_mut()
+                .define(arg.name, SymbolKind::Argument, None);
             cache_offset += 1;
         }
 
         let mut wf = ReadStmtWorkflow {
             label: Some(info),
-            continue_break_points: None,
+            loops: Vec::new(),
             cache_offset,
         };
 
-        for stmt in body {
-            self.read_stmt(stmt, &mut wf)?;
-        }
+        self.with_attrs(attrs, |this| this.read_stmts(body, &mut wf))?;
         self.bindings.pop_block();
 
         if let Some(mut info) = wf.label.take() {
@@ -113,11 +134,30 @@ pub(super) fn convert_opr(p: &Punct) -> Option<Operator> {
         Punct::Star => Operator::Mul,
         Punct::Slash => Operator::Div,
         Punct::Percent => Operator::Rem,
+        Punct::And => Operator::BitAnd,
+        Punct::Or => Operator::BitOr,
+        Punct::Caret => Operator::BitXor,
+        Punct::Shl => Operator::Shl,
+        Punct::Shr => Operator::Shr,
         _ => return None,
     };
     Some(opr)
 }
 
+/// `!(a < b)`等于`a >= b`，不需要再取反
+fn invert_comparison(p: Punct) -> Option<Punct> {
+    let inverted = match p {
+        Punct::GreaterThan => Punct::LessEq,
+        Punct::LessThan => Punct::GreaterEq,
+        Punct::GreaterEq => Punct::LessThan,
+        Punct::LessEq => Punct::GreaterThan,
+        Punct::Equal2 => Punct::NotEq,
+        Punct::NotEq => Punct::Equal2,
+        _ => return None,
+    };
+    Some(inverted)
+}
+
 pub(super) fn convert_bool_opr(p: &Punct) -> Option<BoolOperator> {
     let opr = match p {
         Punct::GreaterThan => BoolOperator::Gt,
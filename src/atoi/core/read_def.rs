@@ -2,49 +2,68 @@ use anyhow::{anyhow, Result};
 
 use crate::{
     atoi::{
-        calculate_arithmetical_bin_expr, calculate_bool_bin_expr, get_anonymous_id, get_fn_label,
-        no_string_error, variable_not_found, Atoi, Binding, FuncDef,
+        calculate_arithmetical_bin_expr, calculate_bool_bin_expr, get_anonymous_id,
+        no_string_error, symbols::SymbolKind, variable_not_found, Atoi, Binding, FuncDef,
     },
-    ir::CacheTag,
+    format::format_fixed,
+    ir::{CacheTag, Label},
     parse::{
         lexer::Punct,
         parse_file::{parse_expr, to_anyhow_result},
-        Definition, Expr, ExprBinary, ExprBlock, ExprUnary, ItemConstant, ItemStatic,
+        span::SpannedError,
+        Definition, Expr, ExprBinary, ExprBlock, ExprUnary, ItemConstant, ItemStatic, Type,
     },
 };
 
-use super::{convert_bool_opr, convert_opr, macros::macro_not_found};
+use super::{
+    convert_bool_opr, convert_opr,
+    fixed::{const_fixed_binary, const_fixed_comparison, parse_decimal},
+    macros::macro_not_found,
+};
 
 #[derive(Clone, Copy)]
 pub enum ConstValue<'a> {
     Int(i32),
+    /// 乘以倍数后的定点数
+    Fixed(i32),
+    Bool(bool),
     Str(&'a str),
 }
 
 impl<'a> Atoi<'a> {
     pub fn read_def(&mut self, def: &Definition<'a>) -> Result<()> {
         match def {
-            Definition::Constant(ItemConstant { name, expr }) => {
+            Definition::Constant(ItemConstant { name, ty, expr, .. }) => {
                 if self.bindings.has_sibling_namesake(name) {
-                    return Err(anyhow!("constant or static `{name}` has been defined"));
+                    return Err(already_defined(name));
                 }
 
-                let value = match self.read_constant(expr)? {
-                    ConstValue::Int(int) => Binding::Constant(int),
-                    ConstValue::Str(s) => Binding::String(s),
+                let (value, display) = match self.read_constant(expr)? {
+                    ConstValue::Str(s) if ty.is_none() => (Binding::String(s), format!("\"{s}\"")),
+                    value => {
+                        let (value, ty) = self.typed_constant(value, *ty, name)?;
+                        (Binding::Constant(value, ty), self.display_number(value, ty))
+                    }
                 };
 
                 self.bindings.push(name, value);
+                self.symbols
+                    .get_mut()
+                    .define(name, SymbolKind::Constant, Some(display));
             }
 
-            Definition::Static(ItemStatic { name, expr, export }) => {
+            Definition::Static(ItemStatic {
+                name,
+                ty,
+                expr,
+                export,
+                ..
+            }) => {
                 if self.bindings.has_sibling_namesake(name) {
-                    return Err(anyhow!("constant or static `{name}` has been defined"));
+                    return Err(already_defined(name));
                 }
 
-                let ConstValue::Int(value) = self.read_constant(expr)? else {
-                    return Err(no_string_error());
-                };
+                let (value, ty) = self.typed_constant(self.read_constant(expr)?, *ty, name)?;
 
                 let cache_tag = if *export {
                     CacheTag::StaticExport(name)
@@ -53,22 +72,41 @@ impl<'a> Atoi<'a> {
                 };
 
                 self.label_map.insert_static(cache_tag, value)?;
-                self.bindings.push(name, Binding::Cache(cache_tag));
+                self.bindings.push(name, Binding::Cache(cache_tag, ty));
+                let display = self.display_number(value, ty);
+                self.symbols
+                    .get_mut()
+                    .define(name, SymbolKind::Static, Some(display));
+                if !*export {
+                    self.track_usage(name, SymbolKind::Static);
+                }
             }
 
             Definition::Function(item_fn) => {
                 if self.functions.has_sibling_namesake(item_fn.name) {
-                    return Err(anyhow!(
-                        "function or macro `{}` has been defined",
-                        item_fn.name
+                    return Err(SpannedError::at(
+                        item_fn.name,
+                        format_args!("function or macro `{}` has been defined", item_fn.name),
                     ));
                 }
+                self.symbols
+                    .get_mut()
+                    .define(item_fn.name, SymbolKind::Function, None);
+                if !item_fn.export {
+                    self.track_usage(item_fn.name, SymbolKind::Function);
+                }
 
+                // 函数体的标签在此分配，使先于被调用函数读取的函数也能调用它
                 self.functions.push(
                     item_fn.name,
                     FuncDef {
-                        label: get_fn_label(item_fn),
-                        arg_count: item_fn.args.len() as _,
+                        label: Label::Anonymous(get_anonymous_id(&mut self.anonymous_label_pool)),
+                        args: item_fn
+                            .args
+                            .iter()
+                            .map(|arg| arg.ty.unwrap_or(Type::Int))
+                            .collect(),
+                        ret: item_fn.ret.unwrap_or(Type::Int),
                     },
                 )
             }
@@ -76,52 +114,102 @@ impl<'a> Atoi<'a> {
         Ok(())
     }
 
-    pub(super) fn read_constant(&self, expr: &Expr<'a>) -> Result<ConstValue<'a>> {
+    pub(in crate::atoi) fn read_constant(&self, expr: &Expr<'a>) -> Result<ConstValue<'a>> {
         match expr {
             Expr::Integer(int) => Ok(ConstValue::Int(*int)),
+            Expr::Decimal(num) => Ok(ConstValue::Fixed(parse_decimal(num, self.fixed_scale)?)),
+            Expr::Bool(value) => Ok(ConstValue::Bool(*value)),
             Expr::Binary(ExprBinary { bin_op, lhs, rhs }) => {
-                let (ConstValue::Int(lhs), ConstValue::Int(rhs)) =
-                    (self.read_constant(lhs)?, self.read_constant(rhs)?)
-                else {
-                    return Err(anyhow!("string cannot do binary operation"));
+                let (lhs, rhs) = (self.read_constant(lhs)?, self.read_constant(rhs)?);
+                let (lhs, rhs) = match (bin_op, lhs, rhs) {
+                    (Punct::And2 | Punct::Or2, _, _) => {
+                        let (lhs, rhs) = (const_truth(lhs)?, const_truth(rhs)?);
+                        let r = if *bin_op == Punct::And2 {
+                            lhs && rhs
+                        } else {
+                            lhs || rhs
+                        };
+                        return Ok(ConstValue::Bool(r));
+                    }
+                    (
+                        Punct::Equal2 | Punct::NotEq,
+                        ConstValue::Bool(lhs),
+                        ConstValue::Bool(rhs),
+                    ) => {
+                        return Ok(ConstValue::Bool((lhs == rhs) == (*bin_op == Punct::Equal2)));
+                    }
+                    (_, ConstValue::Int(lhs), ConstValue::Int(rhs)) => (lhs, rhs),
+                    _ => {
+                        return if let Some(op) = convert_opr(bin_op) {
+                            const_fixed_binary(lhs, rhs, op, self.fixed_scale)
+                        } else if let Some(op) = convert_bool_opr(bin_op) {
+                            let (lhs, rhs) = const_fixed_comparison(lhs, rhs, self.fixed_scale)?;
+                            Ok(ConstValue::Bool(calculate_bool_bin_expr(lhs, rhs, op) != 0))
+                        } else {
+                            Err(anyhow!("unrecognized binary operator `{bin_op}`"))
+                        };
+                    }
                 };
 
-                let r = if let Some(op) = convert_opr(bin_op) {
-                    calculate_arithmetical_bin_expr(lhs, rhs, op)
+                if let Some(op) = convert_opr(bin_op) {
+                    Ok(ConstValue::Int(calculate_arithmetical_bin_expr(
+                        lhs, rhs, op,
+                    )?))
                 } else if let Some(op) = convert_bool_opr(bin_op) {
-                    calculate_bool_bin_expr(lhs, rhs, op)
+                    Ok(ConstValue::Bool(calculate_bool_bin_expr(lhs, rhs, op) != 0))
                 } else {
-                    return Err(anyhow!("unrecognized binary operator `{bin_op}`"));
-                };
-                Ok(ConstValue::Int(r))
+                    Err(anyhow!("unrecognized binary operator `{bin_op}`"))
+                }
             }
             Expr::Unary(ExprUnary { op, expr }) => {
-                let ConstValue::Int(val) = self.read_constant(expr)? else {
-                    return Err(anyhow!("string cannot do unary operation"));
+                let val = match self.read_constant(expr)? {
+                    ConstValue::Int(val) => val,
+                    ConstValue::Bool(val) => {
+                        return match op {
+                            Punct::Bang => Ok(ConstValue::Bool(!val)),
+                            _ => Err(anyhow!(
+                                "mismatched types: expected `int`, found `bool`, \
+                                use `int(...)` to convert"
+                            )),
+                        }
+                    }
+                    ConstValue::Fixed(val) => {
+                        return match op {
+                            Punct::Bang => Ok(ConstValue::Bool(val == 0)),
+                            Punct::Minus => Ok(ConstValue::Fixed(val.wrapping_neg())),
+                            Punct::Tilde => Err(anyhow!(
+                                "bitwise operators cannot be applied to fixed-point values"
+                            )),
+                            _ => Err(anyhow!("unrecognized unary operator `{op}`")),
+                        }
+                    }
+                    ConstValue::Str(_) => {
+                        return Err(anyhow!("string cannot do unary operation"));
+                    }
                 };
 
                 let r = match op {
-                    Punct::Bang => {
-                        if val != 0 {
-                            0
-                        } else {
-                            1
-                        }
-                    }
+                    Punct::Bang => return Ok(ConstValue::Bool(val == 0)),
                     Punct::Minus => -val,
+                    Punct::Tilde => !val,
                     _ => return Err(anyhow!("unrecognized unary operator `{op}`")),
                 };
 
                 Ok(ConstValue::Int(r))
             }
             Expr::Var(id) => {
-                let Some(bind) = self.bindings.find_newest(id) else {
+                let Some(bind) = self.find_binding(id) else {
                     return Err(variable_not_found(id));
                 };
 
                 match bind {
-                    Binding::Cache(_) => Err(anyhow!("identifier `{id}` is not a constant")),
-                    Binding::Constant(val) => Ok(ConstValue::Int(*val)),
+                    Binding::Cache(..) => Err(SpannedError::at(
+                        id,
+                        format_args!("identifier `{id}` is not a constant"),
+                    )),
+                    Binding::Constant(val, Type::Int) => Ok(ConstValue::Int(val)),
+                    Binding::Constant(val, Type::Fixed) => Ok(ConstValue::Fixed(val)),
+                    Binding::Constant(val, Type::Bool) => Ok(ConstValue::Bool(val != 0)),
                     Binding::String(val) => Ok(ConstValue::Str(val)),
                 }
             }
@@ -135,9 +223,8 @@ impl<'a> Atoi<'a> {
                     ))
                 }
             }
-            Expr::Call(_) => Err(anyhow!(
-                "calling a function is cannot be a constant operation yet"
-            )),
+            Expr::Call(call) => self.read_const_call(call),
+            Expr::Loop(_) => Err(anyhow!("a loop cannot be a constant operation")),
             Expr::MacroCall(m) => {
                 let Some(lexer) = self.call_macro(m) else {
                     return Err(macro_not_found(m.name));
@@ -150,4 +237,49 @@ impl<'a> Atoi<'a> {
     }
 }
 
+impl<'a> Atoi<'a> {
+    /// 常量与静态变量的值，有类型标注时转换为标注的类型
+    fn typed_constant(
+        &self,
+        value: ConstValue<'a>,
+        annotation: Option<Type>,
+        name: &'a str,
+    ) -> Result<(i32, Type)> {
+        let (value, ty) = match value {
+            ConstValue::Int(int) => (int, Type::Int),
+            ConstValue::Fixed(fixed) => (fixed, Type::Fixed),
+            ConstValue::Bool(value) => (value as i32, Type::Bool),
+            ConstValue::Str(_) => return Err(no_string_error()),
+        };
+        match annotation {
+            Some(expected) => Ok((self.convert_const_ty(value, ty, expected, name)?, expected)),
+            None => Ok((value, ty)),
+        }
+    }
+
+    fn display_number(&self, value: i32, ty: Type) -> String {
+        match ty {
+            Type::Int => value.to_string(),
+            Type::Fixed => format_fixed(value, self.fixed_scale),
+            Type::Bool => (value != 0).to_string(),
+        }
+    }
+}
+
+/// `&&`与`||`的操作数按是否为0判断
+fn const_truth(value: ConstValue) -> Result<bool> {
+    match value {
+        ConstValue::Int(value) | ConstValue::Fixed(value) => Ok(value != 0),
+        ConstValue::Bool(value) => Ok(value),
+        ConstValue::Str(_) => Err(anyhow!("string cannot do binary operation")),
+    }
+}
+
+fn already_defined(name: &str) -> anyhow::Error {
+    SpannedError::at(
+        name,
+        format_args!("constant or static `{name}` has been defined"),
+    )
+}
+
 //fn read_constant(expr: &ConstExpr) -> Result<Const>
@@ -1,18 +1,28 @@
 use anyhow::{anyhow, Result};
 
 use crate::{
-    atoi::{get_anonymous_id, no_string_error, variable_not_found, Atoi, Binding},
-    ir::{BoolOprRhs, CacheTag, Ir, Operator},
+    atoi::{
+        get_anonymous_id,
+        lint::{expr_fragment, Lint},
+        no_string_error, variable_not_found, Atoi, Binding,
+    },
+    ir::{BoolOprRhs, CacheTag, Ir, LabelKind, Operator},
     parse::{
         lexer::Punct,
         parse_file::{parse_expr, to_anyhow_result},
-        Expr, ExprBinary, ExprBlock, ExprFnCall, ExprUnary,
+        span::SpannedError,
+        Expr, ExprBinary, ExprBlock, ExprFnCall, ExprUnary, Type,
     },
 };
 
 use super::{
-    convert_bool_opr, convert_opr, macros::macro_not_found, read_def::ConstValue, CONST_MINUS_ONE,
-    FRAME_HEAD_LENGTH, REG_CURRENT_MEM_OFFSET, REG_PARENT_MEM_OFFSET, REG_RETURNED_VALUE,
+    convert_bool_opr, convert_opr,
+    fixed::parse_decimal,
+    invert_comparison,
+    macros::macro_not_found,
+    types::{check_comparison, expect_number},
+    CONST_MINUS_ONE, FRAME_HEAD_LENGTH, REG_CURRENT_MEM_OFFSET, REG_PARENT_MEM_OFFSET,
+    REG_RETURNED_VALUE,
 };
 
 fn new_reg(cache_offset: &mut u32) -> CacheTag<'static> {
@@ -26,21 +36,48 @@ impl<'a> Atoi<'a> {
         insts: &mut Vec<Ir<'a>>,
         cache_offset: &mut u32,
     ) -> Result<CacheTag<'a>> {
-        let reg = new_reg(cache_offset);
-        self.read_expr(expr, insts, reg, *cache_offset)?;
+        let (reg, _) = self.read_typed_expr_at_next_reg(expr, insts, cache_offset)?;
         Ok(reg)
     }
 
+    /// 同时返回值的类型
+    pub(super) fn read_typed_expr_at_next_reg(
+        &mut self,
+        expr: &Expr<'a>,
+        insts: &mut Vec<Ir<'a>>,
+        cache_offset: &mut u32,
+    ) -> Result<(CacheTag<'a>, Type)> {
+        let reg = new_reg(cache_offset);
+        let ty = self.read_expr(expr, insts, reg, *cache_offset)?;
+        Ok((reg, ty))
+    }
+
+    /// 求值并写入`dst`，返回值的类型
     pub(super) fn read_expr(
         &mut self,
         expr: &Expr<'a>,
         insts: &mut Vec<Ir<'a>>,
         dst: CacheTag<'a>,
         mut cache_offset: u32,
-    ) -> Result<()> {
-        match expr {
+    ) -> Result<Type> {
+        let ty = match expr {
             Expr::Integer(value) => {
                 insts.push(Ir::Assign { dst, value: *value });
+                Type::Int
+            }
+
+            Expr::Decimal(num) => {
+                let value = parse_decimal(num, self.fixed_scale)?;
+                insts.push(Ir::Assign { dst, value });
+                Type::Fixed
+            }
+
+            Expr::Bool(value) => {
+                insts.push(Ir::Assign {
+                    dst,
+                    value: *value as i32,
+                });
+                Type::Bool
             }
 
             Expr::Str(_) => {
@@ -51,6 +88,11 @@ impl<'a> Atoi<'a> {
                 todo!()
             }
 
+            Expr::Loop(expr_loop) => {
+                self.read_loop_expr(expr_loop, insts, dst, cache_offset)?;
+                Type::Int
+            }
+
             Expr::MacroCall(m) => {
                 let Some(lexer) = self.call_macro(m) else {
                     return Err(macro_not_found(m.name));
@@ -61,91 +103,187 @@ impl<'a> Atoi<'a> {
                     insts,
                     dst,
                     cache_offset,
-                )?;
+                )?
             }
 
             Expr::Var(var) => {
-                let Some(tag) = self.bindings.find_newest(var) else {
+                let Some(tag) = self.find_binding(var) else {
                     return Err(variable_not_found(var));
                 };
 
                 match tag {
-                    Binding::Cache(src) => insts.push(Ir::Operation {
-                        dst,
-                        opr: Operator::Set,
-                        src: *src,
-                    }),
-                    Binding::Constant(val) => insts.push(Ir::Assign { dst, value: *val }),
+                    Binding::Cache(src, ty) => {
+                        insts.push(Ir::Operation {
+                            dst,
+                            opr: Operator::Set,
+                            src,
+                        });
+                        ty
+                    }
+                    Binding::Constant(value, ty) => {
+                        insts.push(Ir::Assign { dst, value });
+                        ty
+                    }
                     Binding::String(_) => return Err(no_string_error()),
                 }
             }
 
             Expr::Binary(ExprBinary {
-                bin_op,
+                bin_op: bin_op @ (Punct::And2 | Punct::Or2),
                 lhs: lhs_expr,
                 rhs: rhs_expr,
             }) => {
-                let ir = if let Some(opr) = convert_opr(bin_op) {
-                    self.read_expr(lhs_expr, insts, dst, cache_offset)?;
-                    let rhs = self.read_expr_at_next_reg(rhs_expr, insts, &mut cache_offset)?;
+                let is_and = *bin_op == Punct::And2;
+                // 结果先存入新的寄存器，右侧读取`dst`时得到的仍是原来的值
+                let result = new_reg(&mut cache_offset);
+                self.read_bool_operand(lhs_expr, insts, result, cache_offset)?;
+
+                // 右侧只在`&&`左侧为真、`||`左侧为假时求值
+                let mut rhs_info = self.new_label(LabelKind::Operand);
+                self.read_bool_operand(rhs_expr, &mut rhs_info.insts, result, cache_offset)?;
+                insts.push(Ir::Cond {
+                    positive: is_and,
+                    cond: result,
+                    then: rhs_info.label,
+                });
+                self.label_map.insert_label(rhs_info)?;
+                insts.push(Ir::Operation {
+                    dst,
+                    opr: Operator::Set,
+                    src: result,
+                });
+                Type::Bool
+            }
 
-                    Ir::Operation { dst, opr, src: rhs }
+            Expr::Binary(ExprBinary {
+                bin_op,
+                lhs: lhs_expr,
+                rhs: rhs_expr,
+            }) => {
+                if let Some(opr) = convert_opr(bin_op) {
+                    let lhs_ty = self.read_expr(lhs_expr, insts, dst, cache_offset)?;
+                    let (opr, rhs, rhs_ty) =
+                        self.read_operand(opr, rhs_expr, insts, &mut cache_offset)?;
+                    for (expr, ty) in [(lhs_expr, lhs_ty), (rhs_expr, rhs_ty)] {
+                        expect_number(expr_fragment(expr).unwrap_or(self.current_fn), ty)?;
+                    }
+                    self.fixed_binary(insts, (dst, lhs_ty), opr, (rhs, rhs_ty), cache_offset)?
                 } else if let Some(opr) = convert_bool_opr(bin_op) {
-                    let lhs = self.read_expr_at_next_reg(lhs_expr, insts, &mut cache_offset)?;
+                    if let Some(value) = self.constant_comparison(lhs_expr, *bin_op, rhs_expr) {
+                        let at = expr_fragment(lhs_expr)
+                            .or_else(|| expr_fragment(rhs_expr))
+                            .unwrap_or(self.current_fn);
+                        self.warn(
+                            Lint::ConstantComparisons,
+                            at,
+                            format_args!("comparison is always {value}"),
+                        );
+                    }
 
-                    if let Expr::Integer(val) = **rhs_expr {
-                        Ir::BoolOperation {
-                            dst,
-                            lhs,
-                            opr,
-                            rhs: BoolOprRhs::Constant(val),
-                        }
-                    } else {
-                        let rhs = self.read_expr_at_next_reg(rhs_expr, insts, &mut cache_offset)?;
-                        Ir::BoolOperation {
-                            dst,
-                            lhs,
-                            opr,
-                            rhs: BoolOprRhs::CacheTag(rhs),
+                    let (lhs, lhs_ty) =
+                        self.read_typed_expr_at_next_reg(lhs_expr, insts, &mut cache_offset)?;
+
+                    // 与定点数比较的整数常量按倍数放大
+                    let rhs_constant = match (&**rhs_expr, lhs_ty) {
+                        (&Expr::Integer(val), Type::Int) => Some(val),
+                        (&Expr::Integer(val), Type::Fixed) => val.checked_mul(self.fixed_scale),
+                        _ => None,
+                    };
+                    let rhs = match rhs_constant {
+                        Some(val) => BoolOprRhs::Constant(val),
+                        None => {
+                            let (rhs, rhs_ty) = self.read_typed_expr_at_next_reg(
+                                rhs_expr,
+                                insts,
+                                &mut cache_offset,
+                            )?;
+                            let at = expr_fragment(rhs_expr)
+                                .or_else(|| expr_fragment(lhs_expr))
+                                .unwrap_or(self.current_fn);
+                            check_comparison(at, opr, lhs_ty, rhs_ty)?;
+                            self.fixed_comparison(insts, (lhs, lhs_ty), (rhs, rhs_ty))?;
+                            BoolOprRhs::CacheTag(rhs)
                         }
-                    }
+                    };
+                    insts.push(Ir::BoolOperation { dst, lhs, opr, rhs });
+                    Type::Bool
                 } else {
                     return Err(anyhow!("unrecognized binary operator `{bin_op}`"));
-                };
-
-                insts.push(ir);
+                }
             }
 
-            Expr::Unary(ExprUnary { op, expr }) => {
-                let ir = match op {
-                    Punct::Bang => {
-                        let src = self.read_expr_at_next_reg(expr, insts, &mut cache_offset)?;
-                        Ir::Not { src, dst }
+            Expr::Unary(ExprUnary { op, expr }) => match op {
+                Punct::Bang => {
+                    if let Expr::Binary(ExprBinary { bin_op, lhs, rhs }) = &**expr {
+                        if let Some(bin_op) = invert_comparison(*bin_op) {
+                            let inverted = Expr::Binary(ExprBinary {
+                                bin_op,
+                                lhs: lhs.clone(),
+                                rhs: rhs.clone(),
+                            });
+                            return self.read_expr(&inverted, insts, dst, cache_offset);
+                        }
                     }
-                    Punct::Minus => Ir::Operation {
+                    let src = self.read_expr_at_next_reg(expr, insts, &mut cache_offset)?;
+                    insts.push(Ir::Not { src, dst });
+                    Type::Bool
+                }
+                Punct::Minus => {
+                    let ty = self.read_expr(expr, insts, dst, cache_offset)?;
+                    expect_number(expr_fragment(expr).unwrap_or(self.current_fn), ty)?;
+                    insts.push(Ir::Operation {
                         dst,
                         opr: Operator::Mul,
                         src: CONST_MINUS_ONE,
-                    },
-                    _ => return Err(anyhow!("unrecognized unary operator `{op}`")),
-                };
-                insts.push(ir);
-            }
+                    });
+                    ty
+                }
+                // `~a`等于`-a - 1`
+                Punct::Tilde => {
+                    let ty = self.read_expr(expr, insts, dst, cache_offset)?;
+                    expect_number(expr_fragment(expr).unwrap_or(self.current_fn), ty)?;
+                    if ty == Type::Fixed {
+                        return Err(anyhow!(
+                            "bitwise operators cannot be applied to fixed-point values"
+                        ));
+                    }
+                    insts.push(Ir::Operation {
+                        dst,
+                        opr: Operator::Mul,
+                        src: CONST_MINUS_ONE,
+                    });
+                    insts.push(Ir::Increase { dst, value: -1 });
+                    Type::Int
+                }
+                _ => return Err(anyhow!("unrecognized unary operator `{op}`")),
+            },
 
             Expr::Call(expr_fn_call @ ExprFnCall { name, args }) => {
-                let Some(def) = self.functions.find_newest(name).copied() else {
-                    if self.call_builtin_function(expr_fn_call, insts, dst, cache_offset)? {
-                        return Ok(());
-                    } else {
-                        return Err(anyhow!("function `{name}` not found"));
+                let Some(def) = self.find_function(name) else {
+                    if let Some(ty) =
+                        self.call_builtin_function(expr_fn_call, insts, dst, cache_offset)?
+                    {
+                        return Ok(ty);
+                    }
+                    if let Some(ty) =
+                        self.call_math_function(expr_fn_call, insts, dst, cache_offset)?
+                    {
+                        return Ok(ty);
                     }
+                    return Err(SpannedError::at(
+                        name,
+                        format_args!("function `{name}` not found"),
+                    ));
                 };
 
-                if args.len() as u32 != def.arg_count {
-                    return Err(anyhow!(
-                        "function `{name}` requires {} arguments, but {} was provided",
-                        def.arg_count,
-                        args.len()
+                if args.len() != def.args.len() {
+                    return Err(SpannedError::at(
+                        name,
+                        format_args!(
+                            "function `{name}` requires {} arguments, but {} was provided",
+                            def.args.len(),
+                            args.len()
+                        ),
                     ));
                 }
 
@@ -154,9 +292,11 @@ impl<'a> Atoi<'a> {
 
                 // 参数求值
                 let mut temp_cache_offset = cache_offset;
-                for arg in args {
+                for (arg, &arg_ty) in args.iter().zip(def.args.iter()) {
                     let nth_arg = new_reg(&mut temp_cache_offset);
-                    self.read_expr(arg, insts, nth_arg, temp_cache_offset)?;
+                    let ty = self.read_expr(arg, insts, nth_arg, temp_cache_offset)?;
+                    let at = expr_fragment(arg).unwrap_or(name);
+                    self.convert_ty(insts, nth_arg, ty, arg_ty, at)?;
                 }
 
                 // 把缓存换进内存
@@ -200,29 +340,55 @@ impl<'a> Atoi<'a> {
                     opr: Operator::Set,
                     src: REG_RETURNED_VALUE,
                 });
+                def.ret
             }
         };
 
-        Ok(())
+        Ok(ty)
     }
 
-    fn require_constant(&self, expr: &Expr) -> Result<i32> {
-        match self
-            .read_constant(expr)
-            .map_err(|err| anyhow!("only constant value is allowed: {err}"))?
-        {
-            ConstValue::Int(v) => Ok(v),
-            ConstValue::Str(_) => Err(anyhow!("string value is not allowed")),
+    /// 读取二元运算的右侧操作数。左移常量位数时改为乘法，不需要调用辅助函数
+    pub(super) fn read_operand(
+        &mut self,
+        opr: Operator,
+        expr: &Expr<'a>,
+        insts: &mut Vec<Ir<'a>>,
+        cache_offset: &mut u32,
+    ) -> Result<(Operator, CacheTag<'a>, Type)> {
+        if let (Operator::Shl, Some(bits)) = (opr, self.try_constant(expr)) {
+            let src = new_reg(cache_offset);
+            insts.push(Ir::Assign {
+                dst: src,
+                value: 1i32.wrapping_shl(bits as u32),
+            });
+            return Ok((Operator::Mul, src, Type::Int));
         }
+
+        let (src, ty) = self.read_typed_expr_at_next_reg(expr, insts, cache_offset)?;
+        Ok((opr, src, ty))
     }
 
+    /// 求值并转换为0或1
+    fn read_bool_operand(
+        &mut self,
+        expr: &Expr<'a>,
+        insts: &mut Vec<Ir<'a>>,
+        dst: CacheTag<'a>,
+        mut cache_offset: u32,
+    ) -> Result<()> {
+        let (value, ty) = self.read_typed_expr_at_next_reg(expr, insts, &mut cache_offset)?;
+        Self::to_bool(insts, dst, value, ty);
+        Ok(())
+    }
+
+    /// 调用内置函数并返回结果的类型，`name`不是内置函数时返回`None`
     fn call_builtin_function(
         &mut self,
         ExprFnCall { name, args }: &ExprFnCall<'a>,
         insts: &mut Vec<Ir<'a>>,
         dst: CacheTag<'a>,
         mut cache_offset: u32,
-    ) -> Result<bool> {
+    ) -> Result<Option<Type>> {
         let get_args = || match &**args {
             [lhs_expr, rhs_expr] => Ok((lhs_expr, rhs_expr)),
             _ => Err(anyhow!(
@@ -234,24 +400,69 @@ impl<'a> Atoi<'a> {
         let mut to_opr_expr = |this: &mut Self, insts: &mut _, opr| {
             let (lhs_expr, rhs_expr) = get_args()?;
             let rhs = new_reg(&mut cache_offset);
-            this.read_expr(lhs_expr, insts, dst, cache_offset)?;
-            this.read_expr(rhs_expr, insts, rhs, cache_offset)?;
-
-            insts.push(Ir::Operation { dst, opr, src: rhs });
-            Ok(true)
+            let lhs_ty = this.read_expr(lhs_expr, insts, dst, cache_offset)?;
+            let rhs_ty = this.read_expr(rhs_expr, insts, rhs, cache_offset)?;
+            for (expr, ty) in [(lhs_expr, lhs_ty), (rhs_expr, rhs_ty)] {
+                expect_number(expr_fragment(expr).unwrap_or(name), ty)?;
+            }
+            this.fixed_binary(insts, (dst, lhs_ty), opr, (rhs, rhs_ty), cache_offset)
+                .map(Some)
         };
 
         match *name {
             "min" => to_opr_expr(self, insts, Operator::Min),
             "max" => to_opr_expr(self, insts, Operator::Max),
+            "int" | "fixed" | "bool" => {
+                let [arg] = &**args else {
+                    return Err(anyhow!(
+                        "builtin function `{name}` requires 1 arguments, but {} was provided",
+                        args.len()
+                    ));
+                };
+                if *name == "bool" {
+                    let (src, ty) =
+                        self.read_typed_expr_at_next_reg(arg, insts, &mut cache_offset)?;
+                    Self::to_bool(insts, dst, src, ty);
+                    return Ok(Some(Type::Bool));
+                }
+                let ty = self.read_expr(arg, insts, dst, cache_offset)?;
+                match (*name, ty) {
+                    ("int", Type::Fixed) => self.fixed_to_int(insts, dst)?,
+                    ("fixed", _) => {
+                        let at = expr_fragment(arg).unwrap_or(name);
+                        self.convert_ty(insts, dst, ty, Type::Fixed, at)?
+                    }
+                    // `bool`的值本身就是0或1
+                    _ => {}
+                }
+                Ok(Type::from_name(name))
+            }
             "random" => {
                 let (lhs_expr, rhs_expr) = get_args()?;
-                let min = self.require_constant(lhs_expr)?;
-                let max = self.require_constant(rhs_expr)?;
-                insts.push(Ir::Random { dst, max, min });
-                Ok(true)
+                // 范围为常量时只需要一条命令
+                if let (Some(min), Some(max)) =
+                    (self.try_constant(lhs_expr), self.try_constant(rhs_expr))
+                {
+                    if min > max {
+                        return Err(anyhow!(
+                            "`random` requires min <= max, but {min} > {max} was provided"
+                        ));
+                    }
+                    insts.push(Ir::Random { dst, max, min });
+                    return Ok(Some(Type::Int));
+                }
+                let mut regs = Vec::new();
+                for arg in [lhs_expr, rhs_expr] {
+                    let (reg, ty) =
+                        self.read_typed_expr_at_next_reg(arg, insts, &mut cache_offset)?;
+                    let at = expr_fragment(arg).unwrap_or(name);
+                    self.convert_ty(insts, reg, ty, Type::Int, at)?;
+                    regs.push(reg);
+                }
+                self.call_math_helper("random", &regs, insts, dst)?;
+                Ok(Some(Type::Int))
             }
-            _ => Ok(false),
+            _ => Ok(None),
         }
     }
 }
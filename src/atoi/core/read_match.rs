@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    atoi::{
+        get_anonymous_id,
+        lint::{expr_fragment, Lint},
+        symbols::SymbolKind,
+        variable_not_found, Atoi, Binding,
+    },
+    ir::{ArmKind, ArmRange, CacheTag, Ir, Label, LabelKind, Operator},
+    parse::{span::SpannedError, MatchArm, PatValue, Pattern, StmtMatch, Type},
+};
+
+use super::{read_stmt::ReadStmtWorkflow, REG_COND_ENABLE};
+
+/// 分支的模式解析后的结果
+enum ArmPattern<'a> {
+    /// `..`，其他分支都不匹配时才会执行，与所在位置无关
+    Default,
+    /// 匹配任意值并绑定到变量
+    Bind(&'a str),
+    Ranges(Vec<ArmRange>),
+}
+
+impl ArmPattern<'_> {
+    fn contains(&self, value: i32) -> bool {
+        match self {
+            Self::Default => false,
+            Self::Bind(_) => true,
+            Self::Ranges(ranges) => ranges.iter().any(|range| range.contains(value)),
+        }
+    }
+}
+
+impl<'a> Atoi<'a> {
+    /// 除`..`外按分支的先后顺序匹配，守卫不成立时继续尝试之后的分支。
+    ///
+    /// 值的取值范围按分支区间的端点分段，每段依次尝试的分支相同。只有一个无守卫分支的段直接跳转到分支，
+    /// 否则跳转到依次调用各个分支的标签：守卫不成立时`REG_COND_ENABLE`保持为1，分支开头将其清除
+    pub(super) fn read_match(
+        &mut self,
+        StmtMatch { expr, arms }: &StmtMatch<'a>,
+        wf: &mut ReadStmtWorkflow<'a>,
+    ) -> Result<()> {
+        let patterns = arms
+            .iter()
+            .map(|arm| self.read_arm_pattern(arm))
+            .collect::<Result<Vec<_>>>()?;
+        let is_guarded = |index: usize| arms[index].guard.is_some();
+
+        if patterns
+            .iter()
+            .enumerate()
+            .filter(|&(index, pattern)| {
+                matches!(pattern, ArmPattern::Default) && !is_guarded(index)
+            })
+            .nth(1)
+            .is_some()
+        {
+            return Err(anyhow!("duplicated default match arm detected"));
+        }
+
+        // 依次尝试的分支，遇到无守卫的分支为止
+        let chain_of = |segment: Option<i32>| {
+            let positional = patterns
+                .iter()
+                .enumerate()
+                .filter(|(_, pattern)| segment.is_some_and(|value| pattern.contains(value)));
+            let defaults = patterns
+                .iter()
+                .enumerate()
+                .filter(|(_, pattern)| matches!(pattern, ArmPattern::Default));
+
+            let mut chain = Vec::new();
+            for (index, _) in positional.chain(defaults) {
+                chain.push(index);
+                if !is_guarded(index) {
+                    break;
+                }
+            }
+            chain
+        };
+
+        let mut bounds = vec![i32::MIN];
+        for pattern in &patterns {
+            if let ArmPattern::Ranges(ranges) = pattern {
+                for range in ranges {
+                    bounds.push(range.start);
+                    bounds.extend(range.end.checked_add(1));
+                }
+            }
+        }
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let default_chain = chain_of(None);
+        let segments: Vec<_> = bounds
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = bounds.get(i + 1).map_or(i32::MAX, |next| next - 1);
+                (ArmRange { start, end }, chain_of(Some(start)))
+            })
+            .collect();
+
+        let mut reachable = vec![false; arms.len()];
+        for (_, chain) in &segments {
+            chain.iter().for_each(|&index| reachable[index] = true);
+        }
+
+        if segments
+            .iter()
+            .any(|(_, chain)| chain.last().is_none_or(|&index| is_guarded(index)))
+        {
+            let at = expr_fragment(expr).unwrap_or(self.current_fn);
+            self.warn(
+                Lint::MissingDefaultArm,
+                at,
+                "`match` has no default arm `..`, unmatched values are ignored",
+            );
+        }
+
+        let cache_offset_saved = wf.cache_offset;
+        let (cond, ty) = wf.read_typed_expr(self, expr)?;
+        if ty != Type::Int {
+            let at = expr_fragment(expr).unwrap_or(self.current_fn);
+            return Err(SpannedError::at(
+                at,
+                format_args!(
+                    "`{}` value cannot be matched, use `int(...)` to convert",
+                    ty.name()
+                ),
+            ));
+        }
+        let has_bind = patterns.iter().any(|p| matches!(p, ArmPattern::Bind(_)));
+        // 绑定的变量是值的副本，分支中的修改不会影响查找
+        let bind_reg = has_bind.then(|| {
+            let reg = CacheTag::Regular(get_anonymous_id(&mut wf.cache_offset));
+            wf.insts().push(Ir::Operation {
+                dst: reg,
+                opr: Operator::Set,
+                src: cond,
+            });
+            reg
+        });
+
+        let any_guard = arms.iter().any(|arm| arm.guard.is_some());
+        let group = get_anonymous_id(&mut self.anonymous_branch_pool);
+        // 每个分支的入口，有守卫时为守卫的标签
+        let mut entries = Vec::new();
+        let mut bodies = Vec::new();
+        for (index, (arm, pattern)) in arms.iter().zip(&patterns).enumerate() {
+            if !reachable[index] {
+                self.warn(Lint::UnreachablePatterns, arm.at, "unreachable pattern");
+            }
+            let (entry, body) =
+                self.read_match_arm(arm, pattern, group, index, any_guard, bind_reg, wf)?;
+            entries.push(entry);
+            bodies.push(body);
+        }
+
+        let mut dispatches: HashMap<Vec<usize>, Label<'a>> = HashMap::new();
+        let mut target = |this: &mut Self, chain: &[usize]| -> Result<Option<Label<'a>>> {
+            match chain {
+                [] => return Ok(None),
+                &[index] if !is_guarded(index) => return Ok(Some(bodies[index])),
+                _ => {}
+            }
+            if let Some(label) = dispatches.get(chain) {
+                return Ok(Some(*label));
+            }
+
+            let mut info = this.new_label(LabelKind::Guard);
+            info.insts.push(Ir::Call {
+                label: entries[chain[0]],
+            });
+            for &index in &chain[1..] {
+                info.insts.push(Ir::Cond {
+                    positive: true,
+                    cond: REG_COND_ENABLE,
+                    then: entries[index],
+                });
+            }
+            info.insts.push(Ir::Assign {
+                dst: REG_COND_ENABLE,
+                value: 0,
+            });
+            let label = info.label;
+            this.label_map.insert_label(info)?;
+            dispatches.insert(chain.to_vec(), label);
+            Ok(Some(label))
+        };
+
+        let mut sorted_arms = Vec::new();
+        if let Some(label) = target(self, &default_chain)? {
+            sorted_arms.push((None, label));
+        }
+
+        let mut merged: Vec<(ArmRange, &[usize])> = Vec::new();
+        for (range, chain) in &segments {
+            if *chain == default_chain {
+                continue;
+            }
+            match merged.last_mut() {
+                Some((last, last_chain))
+                    if *last_chain == chain.as_slice()
+                        && last.end.checked_add(1) == Some(range.start) =>
+                {
+                    last.end = range.end;
+                }
+                _ => merged.push((*range, chain)),
+            }
+        }
+        for (range, chain) in merged {
+            if let Some(label) = target(self, chain)? {
+                sorted_arms.push((Some(range), label));
+            }
+        }
+
+        wf.insts().push(Ir::Table { cond, sorted_arms });
+        wf.cache_offset = cache_offset_saved;
+        Ok(())
+    }
+
+    fn read_arm_pattern(&mut self, arm: &MatchArm<'a>) -> Result<ArmPattern<'a>> {
+        if let [pattern] = &*arm.patterns {
+            match *pattern {
+                Pattern::Wildcard => return Ok(ArmPattern::Default),
+                Pattern::Value(PatValue::Ident(name)) if !self.is_constant(name) => {
+                    return Ok(ArmPattern::Bind(name))
+                }
+                _ => {}
+            }
+        }
+
+        let mut ranges = Vec::new();
+        for pattern in &arm.patterns {
+            let range = match *pattern {
+                Pattern::Wildcard => {
+                    return Err(SpannedError::at(
+                        arm.at,
+                        "`..` cannot be used in or-patterns",
+                    ))
+                }
+                Pattern::Value(PatValue::Ident(name)) if !self.is_constant(name) => {
+                    return Err(SpannedError::at(
+                        name,
+                        format_args!("variable `{name}` cannot be bound in or-patterns"),
+                    ))
+                }
+                Pattern::Value(value) => {
+                    let value = self.read_pat_value(value)?;
+                    ArmRange {
+                        start: value,
+                        end: value,
+                    }
+                }
+                Pattern::Range {
+                    start,
+                    end,
+                    inclusive,
+                } => {
+                    let start = match start {
+                        Some(value) => self.read_pat_value(value)?,
+                        None => i32::MIN,
+                    };
+                    let end = match end {
+                        Some(value) => self.read_pat_value(value)?,
+                        None => i32::MAX,
+                    };
+                    let end = if inclusive || end == i32::MIN {
+                        end
+                    } else {
+                        end - 1
+                    };
+                    if start > end || !inclusive && end == i32::MIN {
+                        return Err(SpannedError::at(arm.at, "range pattern is empty"));
+                    }
+                    ArmRange { start, end }
+                }
+            };
+            ranges.push(range);
+        }
+        Ok(ArmPattern::Ranges(ranges))
+    }
+
+    fn is_constant(&self, name: &'a str) -> bool {
+        matches!(
+            self.bindings.find_newest_entry(name),
+            Some((_, Binding::Constant(..)))
+        )
+    }
+
+    fn read_pat_value(&mut self, value: PatValue<'a>) -> Result<i32> {
+        match value {
+            PatValue::Int(int) => Ok(int),
+            PatValue::Ident(name) => match self.find_binding(name) {
+                Some(Binding::Constant(int, Type::Int)) => Ok(int),
+                Some(_) => Err(SpannedError::at(
+                    name,
+                    format_args!("`{name}` in range pattern must be an integer constant"),
+                )),
+                None => Err(variable_not_found(name)),
+            },
+        }
+    }
+
+    /// 读取分支的守卫与分支体，返回两者的标签。没有守卫时两者相同
+    #[allow(clippy::too_many_arguments)]
+    fn read_match_arm(
+        &mut self,
+        MatchArm {
+            at, guard, body, ..
+        }: &MatchArm<'a>,
+        pattern: &ArmPattern<'a>,
+        group: u32,
+        index: usize,
+        any_guard: bool,
+        bind_reg: Option<CacheTag<'a>>,
+        wf: &ReadStmtWorkflow<'a>,
+    ) -> Result<(Label<'a>, Label<'a>)> {
+        self.bindings.delimite();
+        if let ArmPattern::Bind(name) = pattern {
+            self.bindings
+                .push(name, Binding::Cache(bind_reg.unwrap(), Type::Int));
+            self.symbols
+                .get_mut()
+                .define(name, SymbolKind::Variable, None);
+            self.track_usage(name, SymbolKind::Variable);
+        }
+
+        let mut info = self.new_label(LabelKind::Arm {
+            group,
+            index: index as _,
+            arm: ArmKind::Match {
+                default: !matches!(pattern, ArmPattern::Ranges(_)),
+            },
+        });
+        let body_label = info.label;
+        if let Some(origin) = &mut info.origin {
+            origin.at = Some(at);
+        }
+        if any_guard {
+            info.insts.push(Ir::Assign {
+                dst: REG_COND_ENABLE,
+                value: 0,
+            });
+        }
+        let mut body_wf = ReadStmtWorkflow {
+            label: Some(info),
+            loops: wf.loops.clone(),
+            cache_offset: wf.cache_offset,
+        };
+        self.read_stmt(body, &mut body_wf)?;
+        if let Some(info) = body_wf.label.take() {
+            self.label_map.insert_label(info)?;
+        }
+
+        let entry = match guard {
+            None => body_label,
+            Some(guard) => {
+                let mut info = self.new_label(LabelKind::Guard);
+                let mut cache_offset = wf.cache_offset;
+                let cond = self.read_expr_at_next_reg(guard, &mut info.insts, &mut cache_offset)?;
+                // 守卫中的函数调用可能修改`REG_COND_ENABLE`
+                info.insts.push(Ir::Assign {
+                    dst: REG_COND_ENABLE,
+                    value: 1,
+                });
+                info.insts.push(Ir::Cond {
+                    positive: true,
+                    cond,
+                    then: body_label,
+                });
+                let label = info.label;
+                self.label_map.insert_label(info)?;
+                label
+            }
+        };
+
+        self.bindings.pop_block();
+        Ok((entry, body_label))
+    }
+}
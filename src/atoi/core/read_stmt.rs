@@ -1,63 +1,134 @@
 use anyhow::{anyhow, Result};
 
 use crate::{
-    atoi::{get_anonymous_id, variable_not_found, Atoi, Binding},
-    ir::{BoolOperator, BoolOprRhs, CacheTag, Ir, Label, LabelInfo, Operator},
+    atoi::{
+        get_anonymous_id,
+        lint::{expr_fragment, stmt_fragment, Lint},
+        symbols::SymbolKind,
+        variable_not_found, Atoi, Binding,
+    },
+    ir::{ArmKind, BoolOperator, BoolOprRhs, CacheTag, Ir, Label, LabelInfo, LabelKind, Operator},
     parse::{
         parse_file::{parse_stmt, to_anyhow_result},
-        Definition, Expr, MacroCall, Stmt, StmtAssign, StmtIf, StmtMatch, StmtReturn, StmtSwap,
-        StmtWhile,
+        span::SpannedError,
+        AssignOp, Definition, Expr, ExprLoop, ItemConstant, ItemStatic, MacroCall, Stmt,
+        StmtAssign, StmtBreak, StmtCompoundAssign, StmtFor, StmtIf, StmtReturn, StmtSwap,
+        StmtWhile, Type,
     },
 };
 
 use super::{
-    macros::macro_not_found, REG_COND_ENABLE, REG_CURRENT_MEM_OFFSET, REG_PARENT_MEM_OFFSET,
+    macros::macro_not_found,
+    types::{expect_number, mismatched_types},
+    REG_COND_ENABLE, REG_CURRENT_MEM_OFFSET, REG_LOOP_ENABLE, REG_PARENT_MEM_OFFSET,
     REG_RETURNED_VALUE,
 };
 
 pub(super) struct ReadStmtWorkflow<'a> {
     pub label: Option<LabelInfo<'a>>,
-    pub continue_break_points: Option<(Label<'a>, Label<'a>)>,
+    /// 外层的循环，最内层的在最后
+    pub loops: Vec<LoopPoints<'a>>,
     pub cache_offset: u32,
 }
 
+#[derive(Clone, Copy)]
+pub(super) struct LoopPoints<'a> {
+    pub label: Option<&'a str>,
+    pub continue_point: Label<'a>,
+    pub break_point: Label<'a>,
+    /// `loop`表达式的结果，`break`的值写入这里
+    pub value: Option<CacheTag<'a>>,
+}
+
 impl<'a> ReadStmtWorkflow<'a> {
-    fn insts(&mut self) -> &mut Vec<Ir<'a>> {
+    pub(super) fn insts(&mut self) -> &mut Vec<Ir<'a>> {
         &mut self.label.as_mut().unwrap().insts
     }
+
+    /// 查找`break`或`continue`的目标，没有标签时为最内层的循环
+    fn find_loop(&self, label: Option<&'a str>, keyword: &str) -> Result<LoopPoints<'a>> {
+        match label {
+            None => self
+                .loops
+                .last()
+                .copied()
+                .ok_or_else(|| anyhow!("keyword `{keyword}` can only be used in loop")),
+            Some(label) => self
+                .loops
+                .iter()
+                .rev()
+                .find(|points| points.label == Some(label))
+                .copied()
+                .ok_or_else(|| {
+                    SpannedError::at(label, format_args!("use of undeclared label `{label}`"))
+                }),
+        }
+    }
 }
 
 impl<'a> ReadStmtWorkflow<'a> {
-    fn read_expr(&mut self, atoi: &mut Atoi<'a>, expr: &Expr<'a>) -> Result<CacheTag<'a>> {
+    pub(super) fn read_expr(
+        &mut self,
+        atoi: &mut Atoi<'a>,
+        expr: &Expr<'a>,
+    ) -> Result<CacheTag<'a>> {
         atoi.read_expr_at_next_reg(
             expr,
             &mut self.label.as_mut().unwrap().insts,
             &mut self.cache_offset,
         )
     }
+
+    pub(super) fn read_typed_expr(
+        &mut self,
+        atoi: &mut Atoi<'a>,
+        expr: &Expr<'a>,
+    ) -> Result<(CacheTag<'a>, Type)> {
+        atoi.read_typed_expr_at_next_reg(
+            expr,
+            &mut self.label.as_mut().unwrap().insts,
+            &mut self.cache_offset,
+        )
+    }
 }
 
 impl<'a> Atoi<'a> {
     fn read_block(&mut self, stmts: &[Stmt<'a>], wf: &mut ReadStmtWorkflow<'a>) -> Result<()> {
         self.bindings.delimite();
+        self.read_stmts(stmts, wf)?;
+        self.bindings.pop_block();
+        Ok(())
+    }
+
+    /// 在当前作用域中读取语句，跳过`return`等之后不可达的语句
+    pub(super) fn read_stmts(
+        &mut self,
+        stmts: &[Stmt<'a>],
+        wf: &mut ReadStmtWorkflow<'a>,
+    ) -> Result<()> {
         for stmt in stmts {
             if wf.label.is_none() {
+                let at = stmt_fragment(stmt).unwrap_or(self.current_fn);
+                self.warn(Lint::UnreachableCode, at, "unreachable statement");
                 break;
             }
             self.read_stmt(stmt, wf)?;
         }
-        self.bindings.pop_block();
         Ok(())
     }
 
     fn read_arm(
         &mut self,
         stmts: &[Stmt<'a>],
+        (kind, at): (LabelKind, Option<&'a str>),
         branch_end: Label<'a>,
         wf: &mut ReadStmtWorkflow<'a>,
     ) -> Result<Label<'a>> {
-        let mut new_info = self.new_label();
+        let mut new_info = self.new_label(kind);
         let new_label = new_info.label;
+        if let Some(origin) = &mut new_info.origin {
+            origin.at = at;
+        }
 
         new_info.insts.push(Ir::Assign {
             dst: REG_COND_ENABLE,
@@ -66,7 +137,7 @@ impl<'a> Atoi<'a> {
 
         let mut wf2 = ReadStmtWorkflow {
             label: Some(new_info),
-            continue_break_points: wf.continue_break_points,
+            loops: wf.loops.clone(),
             cache_offset: wf.cache_offset,
         };
 
@@ -80,16 +151,122 @@ impl<'a> Atoi<'a> {
         Ok(new_label)
     }
 
-    fn find_variable(&self, name: &str) -> Result<CacheTag<'a>> {
-        let Some(bind) = self.bindings.find_newest(name) else {
+    /// 开头清除`REG_LOOP_ENABLE`的标签，见[`Self::push_loop_cond`]
+    fn new_loop_label(&mut self, kind: LabelKind) -> LabelInfo<'a> {
+        let mut info = self.new_label(kind);
+        info.insts.push(Ir::Assign {
+            dst: REG_LOOP_ENABLE,
+            value: 0,
+        });
+        info
+    }
+
+    /// 条件成立时进入循环体，否则执行循环之后的部分。
+    /// 两者开头都会清除`REG_LOOP_ENABLE`，因此只有一个会被执行
+    fn push_loop_cond(
+        insts: &mut Vec<Ir<'a>>,
+        cond: CacheTag<'a>,
+        body: Label<'a>,
+        loop_end: Label<'a>,
+    ) {
+        insts.push(Ir::Assign {
+            dst: REG_LOOP_ENABLE,
+            value: 1,
+        });
+        insts.push(Ir::Cond {
+            positive: true,
+            cond,
+            then: body,
+        });
+        insts.push(Ir::Cond {
+            positive: true,
+            cond: REG_LOOP_ENABLE,
+            then: loop_end,
+        });
+    }
+
+    /// 读取循环体，`loops`的最后一个是当前的循环。循环体结束后进入下一次迭代
+    fn read_loop_body(
+        &mut self,
+        body_info: LabelInfo<'a>,
+        body: &[Stmt<'a>],
+        loops: Vec<LoopPoints<'a>>,
+        cache_offset: u32,
+    ) -> Result<()> {
+        let continue_point = loops.last().unwrap().continue_point;
+        let mut body_wf = ReadStmtWorkflow {
+            label: Some(body_info),
+            loops,
+            cache_offset,
+        };
+        self.read_block(body, &mut body_wf)?;
+        if let Some(mut body_info) = body_wf.label.take() {
+            body_info.insts.push(Ir::Call {
+                label: continue_point,
+            });
+            self.label_map.insert_label(body_info)?;
+        }
+        Ok(())
+    }
+
+    /// `loop`表达式。表达式不能拆分到多个标签中，循环结束后回到`insts`继续执行，
+    /// 因此循环体中不能`return`，也不能跳出表达式之外的循环
+    pub(super) fn read_loop_expr(
+        &mut self,
+        ExprLoop { label, body }: &ExprLoop<'a>,
+        insts: &mut Vec<Ir<'a>>,
+        dst: CacheTag<'a>,
+        cache_offset: u32,
+    ) -> Result<()> {
+        let loop_end = self.new_label(LabelKind::Continuation);
+        let body_info = self.new_label(LabelKind::LoopBody);
+        let points = LoopPoints {
+            label: *label,
+            continue_point: body_info.label,
+            break_point: loop_end.label,
+            value: Some(dst),
+        };
+        self.label_map.insert_label(loop_end)?;
+        insts.push(Ir::Call {
+            label: body_info.label,
+        });
+        self.read_loop_body(body_info, body, vec![points], cache_offset)
+    }
+
+    /// 在当前作用域中定义变量
+    fn bind_variable(&mut self, name: &'a str, cache: CacheTag<'a>, ty: Type) -> Result<()> {
+        if self.bindings.has_sibling_namesake(name) {
+            return Err(SpannedError::at(
+                name,
+                format_args!("identifier `{name}` has been defined"),
+            ));
+        }
+        if self.bindings.find_newest_entry(name).is_some() {
+            self.warn(
+                Lint::Shadowing,
+                name,
+                format_args!("`{name}` shadows an outer definition"),
+            );
+        }
+        self.bindings.push(name, Binding::Cache(cache, ty));
+        self.symbols
+            .get_mut()
+            .define(name, SymbolKind::Variable, None);
+        self.track_usage(name, SymbolKind::Variable);
+        Ok(())
+    }
+
+    fn find_variable(&self, name: &'a str) -> Result<(CacheTag<'a>, Type)> {
+        let Some(bind) = self.find_binding(name) else {
             return Err(variable_not_found(name));
         };
 
         match bind {
-            Binding::Constant(_) | Binding::String(_) => Err(anyhow!(
-                "cannot assign value to a constant identifier `{name}`"
+            Binding::Constant(..) | Binding::String(_) => Err(SpannedError::at(
+                name,
+                format_args!("cannot assign value to a constant identifier `{name}`"),
             )),
-            Binding::Cache(cache_tag) => Ok(*cache_tag),
+            Binding::Cache(cache_tag, ty) => Ok((cache_tag, ty)),
         }
     }
 
@@ -98,22 +275,74 @@ impl<'a> Atoi<'a> {
         stmt: &Stmt<'a>,
         wf: &mut ReadStmtWorkflow<'a>,
     ) -> Result<()> {
+        if let (Some(info), Some(at)) = (&mut wf.label, stmt_fragment(stmt)) {
+            info.stmts.push(at);
+        }
+
         match stmt {
             Stmt::Assign(StmtAssign {
                 is_bind,
                 name,
+                ty,
                 expr,
             }) => {
+                let at = expr_fragment(expr).unwrap_or(name);
                 if *is_bind {
-                    let result = wf.read_expr(self, expr)?;
-                    if self.bindings.has_sibling_namesake(name) {
-                        return Err(anyhow!("identifier `{name}` has been defined"));
-                    }
-                    self.bindings.push(name, Binding::Cache(result));
+                    let (result, expr_ty) = wf.read_typed_expr(self, expr)?;
+                    // 没有类型标注时使用表达式的类型
+                    let ty = ty.unwrap_or(expr_ty);
+                    self.convert_ty(wf.insts(), result, expr_ty, ty, at)?;
+                    self.bind_variable(name, result, ty)?;
                 } else {
-                    let dst = self.find_variable(name)?;
+                    let (dst, ty) = self.find_variable(name)?;
                     let cache_offset = wf.cache_offset;
-                    self.read_expr(expr, wf.insts(), dst, cache_offset)?;
+                    let expr_ty = self.read_expr(expr, wf.insts(), dst, cache_offset)?;
+                    self.convert_ty(wf.insts(), dst, expr_ty, ty, at)?;
+                }
+            }
+
+            Stmt::CompoundAssign(StmtCompoundAssign { name, op, expr }) => {
+                let (dst, ty) = self.find_variable(name)?;
+                expect_number(name, ty)?;
+                let opr = match op {
+                    AssignOp::Add => Operator::Add,
+                    AssignOp::Sub => Operator::Sub,
+                    AssignOp::Mul => Operator::Mul,
+                    AssignOp::Div => Operator::Div,
+                    AssignOp::Rem => Operator::Rem,
+                    AssignOp::Max => Operator::Max,
+                    AssignOp::Min => Operator::Min,
+                    AssignOp::BitAnd => Operator::BitAnd,
+                    AssignOp::BitOr => Operator::BitOr,
+                    AssignOp::BitXor => Operator::BitXor,
+                    AssignOp::Shl => Operator::Shl,
+                    AssignOp::Shr => Operator::Shr,
+                };
+
+                // 加减常量时不需要额外的寄存器，定点数变量的常量需要乘以倍数
+                let increase = match (opr, self.try_constant(expr)) {
+                    (Operator::Add, Some(value)) => Some(value),
+                    (Operator::Sub, Some(value)) => value.checked_neg(),
+                    _ => None,
+                }
+                .and_then(|value| match ty {
+                    Type::Fixed => value.checked_mul(self.fixed_scale),
+                    _ => Some(value),
+                });
+
+                match increase {
+                    Some(value) => wf.insts().push(Ir::Increase { dst, value }),
+                    None => {
+                        let mut cache_offset = wf.cache_offset;
+                        let (opr, src, src_ty) =
+                            self.read_operand(opr, expr, wf.insts(), &mut cache_offset)?;
+                        let at = expr_fragment(expr).unwrap_or(name);
+                        expect_number(at, src_ty)?;
+                        if ty == Type::Int && src_ty == Type::Fixed {
+                            return Err(mismatched_types(at, ty, src_ty));
+                        }
+                        self.fixed_binary(wf.insts(), (dst, ty), opr, (src, src_ty), cache_offset)?;
+                    }
                 }
             }
 
@@ -128,28 +357,60 @@ impl<'a> Atoi<'a> {
 
             Stmt::Yield => return Err(anyhow!("yielding is not support yet")),
 
-            Stmt::Break => match wf.continue_break_points {
-                Some((_, break_point)) => {
-                    wf.insts().push(Ir::Call { label: break_point });
-                    self.label_map.insert_label(wf.label.take().unwrap())?;
+            Stmt::Break(StmtBreak { label, expr }) => {
+                let points = wf.find_loop(*label, "break")?;
+                match (expr, points.value) {
+                    (Some(expr), Some(dst)) => {
+                        let cache_offset = wf.cache_offset;
+                        let ty = self.read_expr(expr, wf.insts(), dst, cache_offset)?;
+                        let at = expr_fragment(expr).unwrap_or(self.current_fn);
+                        self.convert_ty(wf.insts(), dst, ty, Type::Int, at)?;
+                    }
+                    (None, None) => {}
+                    (Some(_), None) => {
+                        return Err(anyhow!(
+                            "`break` with value can only be used in `loop` expression"
+                        ))
+                    }
+                    (None, Some(_)) => {
+                        return Err(anyhow!("`break` in `loop` expression must have a value"))
+                    }
                 }
-                None => return Err(anyhow!("keyword `break` can only be used in loop")),
-            },
+                wf.insts().push(Ir::Call {
+                    label: points.break_point,
+                });
+                self.label_map.insert_label(wf.label.take().unwrap())?;
+            }
 
-            Stmt::Continue => match wf.continue_break_points {
-                Some((continue_point, _)) => {
-                    wf.insts().push(Ir::Call {
-                        label: continue_point,
-                    });
-                    self.label_map.insert_label(wf.label.take().unwrap())?;
-                }
-                None => return Err(anyhow!("keyword `continue` can only be used in loop")),
-            },
+            Stmt::Continue(label) => {
+                let points = wf.find_loop(*label, "continue")?;
+                wf.insts().push(Ir::Call {
+                    label: points.continue_point,
+                });
+                self.label_map.insert_label(wf.label.take().unwrap())?;
+            }
 
             Stmt::Return(StmtReturn { expr }) => {
+                if wf
+                    .loops
+                    .first()
+                    .is_some_and(|points| points.value.is_some())
+                {
+                    return Err(anyhow!("`return` cannot be used in `loop` expression"));
+                }
+
                 let mut info = wf.label.take().unwrap();
                 if let Some(expr) = expr {
-                    self.read_expr(expr, &mut info.insts, REG_RETURNED_VALUE, wf.cache_offset)?;
+                    let ty =
+                        self.read_expr(expr, &mut info.insts, REG_RETURNED_VALUE, wf.cache_offset)?;
+                    let at = expr_fragment(expr).unwrap_or(self.current_fn);
+                    self.convert_ty(
+                        &mut info.insts,
+                        REG_RETURNED_VALUE,
+                        ty,
+                        self.current_ret,
+                        at,
+                    )?;
                 }
 
                 info.insts.push(Ir::Operation {
@@ -160,9 +421,12 @@ impl<'a> Atoi<'a> {
                 self.label_map.insert_label(info)?;
             }
 
-            Stmt::Swap(StmtSwap { lhs, rhs }) => {
-                let lhs = self.find_variable(lhs)?;
-                let rhs = self.find_variable(rhs)?;
+            Stmt::Swap(StmtSwap { lhs, rhs: rhs_name }) => {
+                let (lhs, lhs_ty) = self.find_variable(lhs)?;
+                let (rhs, rhs_ty) = self.find_variable(rhs_name)?;
+                if lhs_ty != rhs_ty {
+                    return Err(mismatched_types(rhs_name, lhs_ty, rhs_ty));
+                }
                 wf.insts().push(Ir::Operation {
                     dst: lhs,
                     opr: Operator::Swp,
@@ -176,12 +440,13 @@ impl<'a> Atoi<'a> {
                     value: 1,
                 });
 
-                let branch_end = self.new_label();
+                let branch_end = self.new_label(LabelKind::Continuation);
                 let cache_offset_saved = wf.cache_offset;
+                let group = get_anonymous_id(&mut self.anonymous_branch_pool);
 
                 let mut is_first = true;
-                for (cond, stmts) in arms {
-                    let mut cond = wf.read_expr(self, cond)?;
+                for (index, (cond_expr, stmts)) in arms.iter().enumerate() {
+                    let mut cond = wf.read_expr(self, cond_expr)?;
 
                     // 如果不是第一个，则设置判决成功
                     if is_first {
@@ -197,7 +462,13 @@ impl<'a> Atoi<'a> {
                         cond = cond2;
                     }
 
-                    let arm_label = self.read_arm(stmts, branch_end.label, wf)?;
+                    let kind = LabelKind::Arm {
+                        group,
+                        index: index as _,
+                        arm: ArmKind::If,
+                    };
+                    let at = expr_fragment(cond_expr);
+                    let arm_label = self.read_arm(stmts, (kind, at), branch_end.label, wf)?;
                     wf.insts().push(Ir::Cond {
                         positive: true,
                         cond,
@@ -211,7 +482,18 @@ impl<'a> Atoi<'a> {
                     None => &[],
                 };
 
-                let default_label = self.read_arm(default_block, branch_end.label, wf)?;
+                let kind = LabelKind::Arm {
+                    group,
+                    index: arms.len() as _,
+                    arm: ArmKind::Else,
+                };
+                // 没有`else`时记在`if`的位置
+                let at = default_block
+                    .first()
+                    .and_then(stmt_fragment)
+                    .or_else(|| expr_fragment(&arms.first()?.0));
+                let default_label =
+                    self.read_arm(default_block, (kind, at), branch_end.label, wf)?;
                 wf.insts().push(Ir::Cond {
                     positive: true,
                     cond: REG_COND_ENABLE,
@@ -223,13 +505,10 @@ impl<'a> Atoi<'a> {
                     .insert_label(wf.label.replace(branch_end).unwrap())?;
             }
 
-            Stmt::While(StmtWhile { expr, body }) => {
-                let loop_end = self.new_label();
-                let loop_end_label = loop_end.label;
-                self.label_map.insert_label(loop_end)?;
-
-                let mut cond_info = self.new_label();
-                let body_info = self.new_label();
+            Stmt::While(StmtWhile { label, expr, body }) => {
+                let loop_end = self.new_loop_label(LabelKind::Continuation);
+                let mut cond_info = self.new_label(LabelKind::LoopCond);
+                let body_info = self.new_loop_label(LabelKind::LoopBody);
                 wf.insts().push(Ir::Call {
                     label: cond_info.label,
                 });
@@ -239,63 +518,191 @@ impl<'a> Atoi<'a> {
                     &mut cond_info.insts,
                     &mut wf.cache_offset.clone(),
                 )?;
-                cond_info.insts.push(Ir::Cond {
-                    positive: true,
-                    cond: expr_result,
-                    then: body_info.label,
+                Self::push_loop_cond(
+                    &mut cond_info.insts,
+                    expr_result,
+                    body_info.label,
+                    loop_end.label,
+                );
+
+                let mut loops = wf.loops.clone();
+                loops.push(LoopPoints {
+                    label: *label,
+                    continue_point: cond_info.label,
+                    break_point: loop_end.label,
+                    value: None,
                 });
-
-                let mut body_wf = ReadStmtWorkflow {
-                    label: Some(body_info),
-                    continue_break_points: Some((cond_info.label, loop_end_label)),
-                    cache_offset: wf.cache_offset,
-                };
-                self.read_block(body, &mut body_wf)?;
-                if let Some(mut body_info) = body_wf.label.take() {
-                    body_info.insts.push(Ir::Call {
-                        label: cond_info.label,
-                    });
-                    self.label_map.insert_label(body_info)?;
-                }
+                self.read_loop_body(body_info, body, loops, wf.cache_offset)?;
 
                 self.label_map.insert_label(cond_info)?;
+                // 循环之后的语句在循环结束时执行
+                self.label_map
+                    .insert_label(wf.label.replace(loop_end).unwrap())?;
             }
 
-            Stmt::Match(StmtMatch { expr, sorted_arms }) => {
-                let mut cache_offset = wf.cache_offset;
-                let cond = self.read_expr_at_next_reg(expr, wf.insts(), &mut cache_offset)?;
-                if let Some((&(arm, _), _)) = sorted_arms
-                    .windows(2)
-                    .map(|arr| (&arr[0], &arr[1]))
-                    .find(|(a, b)| a.0 >= b.0)
-                {
-                    return Err(match arm {
-                        Some(int) => anyhow!("duplicated match arm `{int}` detected"),
-                        None => anyhow!("duplicated default match arm detected"),
-                    });
+            Stmt::Loop(ExprLoop { label, body }) => {
+                let loop_end = self.new_loop_label(LabelKind::Continuation);
+                let body_info = self.new_label(LabelKind::LoopBody);
+                wf.insts().push(Ir::Call {
+                    label: body_info.label,
+                });
+
+                let mut loops = wf.loops.clone();
+                loops.push(LoopPoints {
+                    label: *label,
+                    continue_point: body_info.label,
+                    break_point: loop_end.label,
+                    value: None,
+                });
+                self.read_loop_body(body_info, body, loops, wf.cache_offset)?;
+
+                self.label_map
+                    .insert_label(wf.label.replace(loop_end).unwrap())?;
+            }
+
+            Stmt::For(StmtFor {
+                label,
+                var,
+                start,
+                end,
+                inclusive,
+                rev,
+                step,
+                body,
+            }) => {
+                let step = match step {
+                    Some(expr) => match self.try_constant(expr) {
+                        Some(step) if step > 0 => step,
+                        _ => {
+                            let at = expr_fragment(expr).unwrap_or(var);
+                            return Err(SpannedError::at(
+                                at,
+                                "the step of `for` loop must be a positive constant",
+                            ));
+                        }
+                    },
+                    None => 1,
+                };
+
+                // 循环结束后计数器等寄存器不再使用
+                let cache_offset_saved = wf.cache_offset;
+                // 倒序时从区间的末端开始，直到计数器小于起点
+                let (first, last) = if *rev { (end, start) } else { (start, end) };
+                let (counter, counter_ty) = wf.read_typed_expr(self, first)?;
+                let (bound, bound_ty) = wf.read_typed_expr(self, last)?;
+                for (expr, reg, ty) in [(first, counter, counter_ty), (last, bound, bound_ty)] {
+                    let at = expr_fragment(expr).unwrap_or(var);
+                    self.convert_ty(wf.insts(), reg, ty, Type::Int, at)?;
                 }
+                let var_reg = CacheTag::Regular(get_anonymous_id(&mut wf.cache_offset));
 
-                let mut output_arms = Vec::new();
-                for (arm, stmt) in sorted_arms {
-                    let info = self.new_label();
-                    output_arms.push((*arm, info.label));
-                    let mut wf2 = ReadStmtWorkflow {
-                        label: Some(info),
-                        continue_break_points: wf.continue_break_points,
-                        cache_offset: wf.cache_offset,
-                    };
-                    self.read_stmt(stmt, &mut wf2)?;
-                    if let Some(info) = wf2.label.take() {
-                        self.label_map.insert_label(info)?;
-                    }
+                let loop_end = self.new_loop_label(LabelKind::Continuation);
+                let mut cond_info = self.new_label(LabelKind::LoopCond);
+                let mut step_info = self.new_label(LabelKind::LoopStep);
+                let mut body_info = self.new_loop_label(LabelKind::LoopBody);
+                wf.insts().push(Ir::Call {
+                    label: cond_info.label,
+                });
+
+                // 第一次迭代前比较两端
+                let cond = CacheTag::Regular(wf.cache_offset);
+                cond_info.insts.push(Ir::BoolOperation {
+                    dst: cond,
+                    lhs: counter,
+                    opr: match (rev, inclusive) {
+                        (true, true) => BoolOperator::Ge,
+                        (true, false) => BoolOperator::Gt,
+                        (false, true) => BoolOperator::Le,
+                        (false, false) => BoolOperator::Lt,
+                    },
+                    rhs: BoolOprRhs::CacheTag(bound),
+                });
+                // 不进入循环时计数器溢出也没有影响
+                if *rev && !*inclusive {
+                    cond_info.insts.push(Ir::Increase {
+                        dst: counter,
+                        value: -1,
+                    });
                 }
+                Self::push_loop_cond(&mut cond_info.insts, cond, body_info.label, loop_end.label);
+
+                // 之后比较剩余的距离与步长，而不是先步进再比较，计数器不会越过`i32`的两端。
+                // 距离不小于2^31时相减的结果回绕为负数，此时一定大于步长
+                let (distance, negative) = (
+                    CacheTag::Regular(wf.cache_offset + 1),
+                    CacheTag::Regular(wf.cache_offset + 2),
+                );
+                let (minuend, subtrahend) = if *rev {
+                    (counter, bound)
+                } else {
+                    (bound, counter)
+                };
+                step_info.insts.extend([
+                    Ir::Operation {
+                        dst: distance,
+                        opr: Operator::Set,
+                        src: minuend,
+                    },
+                    Ir::Operation {
+                        dst: distance,
+                        opr: Operator::Sub,
+                        src: subtrahend,
+                    },
+                    Ir::BoolOperation {
+                        dst: cond,
+                        lhs: distance,
+                        opr: if *rev || *inclusive {
+                            BoolOperator::Ge
+                        } else {
+                            BoolOperator::Gt
+                        },
+                        rhs: BoolOprRhs::Constant(step),
+                    },
+                    Ir::BoolOperation {
+                        dst: negative,
+                        lhs: distance,
+                        opr: BoolOperator::Lt,
+                        rhs: BoolOprRhs::Constant(0),
+                    },
+                    Ir::Operation {
+                        dst: cond,
+                        opr: Operator::Max,
+                        src: negative,
+                    },
+                    Ir::Increase {
+                        dst: counter,
+                        value: if *rev { -step } else { step },
+                    },
+                ]);
+                Self::push_loop_cond(&mut step_info.insts, cond, body_info.label, loop_end.label);
+
+                // 每次迭代复制计数器，修改循环变量不影响迭代
+                body_info.insts.push(Ir::Operation {
+                    dst: var_reg,
+                    opr: Operator::Set,
+                    src: counter,
+                });
+                let mut loops = wf.loops.clone();
+                loops.push(LoopPoints {
+                    label: *label,
+                    continue_point: step_info.label,
+                    break_point: loop_end.label,
+                    value: None,
+                });
+                self.bindings.delimite();
+                self.bind_variable(var, var_reg, Type::Int)?;
+                self.read_loop_body(body_info, body, loops, wf.cache_offset)?;
+                self.bindings.pop_block();
 
-                wf.insts().push(Ir::Table {
-                    cond,
-                    sorted_arms: output_arms,
-                })
+                self.label_map.insert_label(step_info)?;
+                self.label_map.insert_label(cond_info)?;
+                self.label_map
+                    .insert_label(wf.label.replace(loop_end).unwrap())?;
+                wf.cache_offset = cache_offset_saved;
             }
 
+            Stmt::Match(stmt_match) => self.read_match(stmt_match, wf)?,
+
             Stmt::Debugger => {
                 wf.insts().push(Ir::SimulationAbort);
             }
@@ -305,14 +712,15 @@ impl<'a> Atoi<'a> {
                     return self.read_stmt(&to_anyhow_result(parse_stmt(lexer))?, wf);
                 }
 
+                let cache_offset = wf.cache_offset;
                 let insts = wf.insts();
                 let lexer = tokens.clone();
 
                 match *name {
                     "run" => Self::macro_run(insts, lexer),
                     "run_concat" => self.macro_run_concat(insts, lexer),
-                    "print" => self.macro_print(insts, lexer),
-                    "title" => self.macro_title(insts, lexer),
+                    "print" => self.macro_print(insts, lexer, cache_offset),
+                    "title" => self.macro_title(insts, lexer, cache_offset),
                     _ => return Err(macro_not_found(name)),
                 }?;
             }
@@ -321,7 +729,10 @@ impl<'a> Atoi<'a> {
                 Definition::Function(_) => {
                     return Err(anyhow!("functions are not allowed in statement blocks"));
                 }
-                _ => self.read_def(def)?,
+                Definition::Constant(ItemConstant { attrs, .. })
+                | Definition::Static(ItemStatic { attrs, .. }) => {
+                    self.with_attrs(attrs, |this| this.read_def(def))?
+                }
             },
         }
 
@@ -0,0 +1,116 @@
+use anyhow::Result;
+
+use crate::{
+    atoi::Atoi,
+    ir::{BoolOperator, BoolOprRhs, CacheTag, Ir, Operator},
+    parse::{span::SpannedError, Type},
+};
+
+use super::fixed::const_promote;
+
+/// 两个操作数运算后的类型，有一个是定点数时结果为定点数
+pub(super) fn join_ty(lhs: Type, rhs: Type) -> Type {
+    if lhs == Type::Fixed || rhs == Type::Fixed {
+        Type::Fixed
+    } else {
+        Type::Int
+    }
+}
+
+pub(super) fn mismatched_types(at: &str, expected: Type, found: Type) -> anyhow::Error {
+    let hint = match (expected, found) {
+        (Type::Int, _) => ", use `int(...)` to convert",
+        (Type::Bool, _) => ", use `bool(...)` to convert",
+        (Type::Fixed, Type::Bool) => ", use `fixed(int(...))` to convert",
+        (Type::Fixed, _) => "",
+    };
+    SpannedError::at(
+        at,
+        format_args!(
+            "mismatched types: expected `{}`, found `{}`{hint}",
+            expected.name(),
+            found.name()
+        ),
+    )
+}
+
+/// 算术运算的操作数不能是`bool`
+pub(super) fn expect_number(at: &str, ty: Type) -> Result<()> {
+    match ty {
+        Type::Bool => Err(mismatched_types(at, Type::Int, ty)),
+        _ => Ok(()),
+    }
+}
+
+/// 比较两侧的类型必须相容，`bool`只能与`bool`判断是否相等
+pub(super) fn check_comparison(at: &str, opr: BoolOperator, lhs: Type, rhs: Type) -> Result<()> {
+    match (lhs, rhs) {
+        (Type::Bool, Type::Bool) => match opr {
+            BoolOperator::Equal | BoolOperator::NotEqual => Ok(()),
+            _ => Err(SpannedError::at(
+                at,
+                "`bool` values can only be compared with `==` or `!=`",
+            )),
+        },
+        (Type::Bool, _) | (_, Type::Bool) => Err(mismatched_types(at, lhs, rhs)),
+        _ => Ok(()),
+    }
+}
+
+impl<'a> Atoi<'a> {
+    /// 把`reg`中`from`类型的值转换为`to`类型。整数可以隐式转换为定点数，其他转换需要`int(...)`等函数
+    pub(super) fn convert_ty(
+        &mut self,
+        insts: &mut Vec<Ir<'a>>,
+        reg: CacheTag<'a>,
+        from: Type,
+        to: Type,
+        at: &'a str,
+    ) -> Result<()> {
+        match (from, to) {
+            (Type::Int, Type::Fixed) => {
+                let src = self.fixed_scale_reg()?;
+                insts.push(Ir::Operation {
+                    dst: reg,
+                    opr: Operator::Mul,
+                    src,
+                });
+                Ok(())
+            }
+            _ if from == to => Ok(()),
+            _ => Err(mismatched_types(at, to, from)),
+        }
+    }
+
+    /// 编译期常量的类型转换
+    pub(super) fn convert_const_ty(
+        &self,
+        value: i32,
+        from: Type,
+        to: Type,
+        at: &'a str,
+    ) -> Result<i32> {
+        match (from, to) {
+            (Type::Int, Type::Fixed) => const_promote(value, self.fixed_scale),
+            _ if from == to => Ok(value),
+            _ => Err(mismatched_types(at, to, from)),
+        }
+    }
+
+    /// 把`src`转换为0或1写入`dst`，`src`已经是`bool`时直接复制
+    pub(super) fn to_bool(insts: &mut Vec<Ir<'a>>, dst: CacheTag<'a>, src: CacheTag<'a>, ty: Type) {
+        insts.push(match ty {
+            Type::Bool => Ir::Operation {
+                dst,
+                opr: Operator::Set,
+                src,
+            },
+            _ => Ir::BoolOperation {
+                dst,
+                lhs: src,
+                opr: BoolOperator::NotEqual,
+                rhs: BoolOprRhs::Constant(0),
+            },
+        });
+    }
+}
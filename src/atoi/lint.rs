@@ -0,0 +1,247 @@
+use std::{collections::HashSet, fmt::Display};
+
+use anyhow::Result;
+
+use crate::parse::{
+    lexer::Punct, span::SpannedError, Attribute, Expr, ExprBinary, ExprBlock, ExprFnCall, ExprLoop,
+    ExprUnary, MacroCall, Stmt, StmtAssign, StmtBreak, StmtCompoundAssign, StmtFor, StmtIf,
+    StmtMatch, StmtReturn, StmtSwap, StmtWhile,
+};
+
+use super::{core::ConstValue, def_name, symbols::SymbolKind, Atoi};
+
+macro_rules! lints {
+    {$($Lint:ident $name:literal,)*} => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Lint {
+            $($Lint,)*
+        }
+
+        impl Lint {
+            pub const ALL: &'static [Self] = &[$(Self::$Lint,)*];
+
+            /// `#[allow(...)]`中使用的名称
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$Lint => $name,)*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some(Self::$Lint),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+lints! {
+    UnusedVariables "unused_variables",
+    DeadCode "dead_code",
+    UnreachableCode "unreachable_code",
+    MissingDefaultArm "missing_default_arm",
+    UnreachablePatterns "unreachable_patterns",
+    ConstantComparisons "constant_comparisons",
+    Shadowing "shadowing",
+}
+
+#[derive(Clone, Debug)]
+pub struct Warning<'a> {
+    pub lint: Lint,
+    pub message: String,
+    /// 警告所在的源码片段
+    pub at: &'a str,
+}
+
+impl<'a> Atoi<'a> {
+    /// 在`attrs`允许的警告下执行`f`
+    pub(super) fn with_attrs<R>(
+        &mut self,
+        attrs: &[Attribute<'a>],
+        f: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        let mut allowed = Vec::new();
+        for attr in attrs {
+            if attr.name != "allow" {
+                return Err(SpannedError::at(
+                    attr.name,
+                    format_args!("unknown attribute `{}`", attr.name),
+                ));
+            }
+
+            for name in &attr.args {
+                match (*name, Lint::from_name(name)) {
+                    ("warnings", _) => allowed.extend(Lint::ALL),
+                    (_, Some(lint)) => allowed.push(lint),
+                    (_, None) => {
+                        return Err(SpannedError::at(
+                            name,
+                            format_args!("unknown lint `{name}`"),
+                        ))
+                    }
+                }
+            }
+        }
+
+        let saved = self.allowed_lints.len();
+        self.allowed_lints.extend(allowed);
+        let r = f(self);
+        self.allowed_lints.truncate(saved);
+        r
+    }
+
+    pub(super) fn is_allowed(&self, lint: Lint) -> bool {
+        self.allowed_lints.contains(&lint)
+    }
+
+    pub(super) fn warn(&mut self, lint: Lint, at: &'a str, message: impl Display) {
+        if !self.is_allowed(lint) {
+            self.warnings.push(Warning {
+                lint,
+                message: message.to_string(),
+                at,
+            });
+        }
+    }
+
+    /// 记录需要在读取结束后检查是否被使用的定义
+    pub(super) fn track_usage(&mut self, name: &'a str, kind: SymbolKind) {
+        let lint = match kind {
+            SymbolKind::Variable => Lint::UnusedVariables,
+            SymbolKind::Function | SymbolKind::Static => Lint::DeadCode,
+            SymbolKind::Constant | SymbolKind::Argument => return,
+        };
+
+        // 以下划线开头的名称表示有意不使用
+        if !name.starts_with('_') && !self.is_allowed(lint) {
+            self.unused_candidates.push((name, kind));
+        }
+    }
+
+    pub(super) fn check_unused(&mut self) {
+        let used: HashSet<_> = self
+            .symbols
+            .get_mut()
+            .references
+            .iter()
+            .map(|r| r.definition.as_ptr())
+            .collect();
+
+        for (name, kind) in std::mem::take(&mut self.unused_candidates) {
+            if used.contains(&name.as_ptr()) {
+                continue;
+            }
+
+            let (lint, what) = match kind {
+                SymbolKind::Variable => (Lint::UnusedVariables, "variable"),
+                SymbolKind::Function => (Lint::DeadCode, "function"),
+                _ => (Lint::DeadCode, "static"),
+            };
+            self.warnings.push(Warning {
+                lint,
+                message: format!("{what} `{name}` is never used"),
+                at: name,
+            });
+        }
+
+        self.warnings.sort_by_key(|w| w.at.as_ptr());
+    }
+
+    /// 与常量比较时结果恒定的比较表达式的值
+    pub(super) fn constant_comparison(
+        &self,
+        lhs: &Expr<'a>,
+        opr: Punct,
+        rhs: &Expr<'a>,
+    ) -> Option<bool> {
+        let rhs = self.try_constant(rhs)?;
+        if let Some(lhs) = self.try_constant(lhs) {
+            let r = match opr {
+                Punct::GreaterThan => lhs > rhs,
+                Punct::LessThan => lhs < rhs,
+                Punct::GreaterEq => lhs >= rhs,
+                Punct::LessEq => lhs <= rhs,
+                Punct::Equal2 => lhs == rhs,
+                Punct::NotEq => lhs != rhs,
+                _ => return None,
+            };
+            return Some(r);
+        }
+
+        match (opr, rhs) {
+            (Punct::GreaterThan, i32::MAX) | (Punct::LessThan, i32::MIN) => Some(false),
+            (Punct::GreaterEq, i32::MIN) | (Punct::LessEq, i32::MAX) => Some(true),
+            _ => None,
+        }
+    }
+
+    pub(super) fn try_constant(&self, expr: &Expr<'a>) -> Option<i32> {
+        match expr {
+            Expr::Integer(int) => Some(*int),
+            Expr::Var(_) | Expr::Unary(_) | Expr::Binary(_) => {
+                match self.read_constant(expr).ok()? {
+                    ConstValue::Int(int) => Some(int),
+                    ConstValue::Fixed(_) | ConstValue::Bool(_) | ConstValue::Str(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 语句中可以用来标记位置的源码片段
+pub(super) fn stmt_fragment<'a>(stmt: &Stmt<'a>) -> Option<&'a str> {
+    match stmt {
+        Stmt::Block(stmts) => stmts.first().and_then(stmt_fragment),
+        Stmt::Assign(StmtAssign { name, .. })
+        | Stmt::CompoundAssign(StmtCompoundAssign { name, .. })
+        | Stmt::Swap(StmtSwap { lhs: name, .. })
+        | Stmt::For(StmtFor {
+            label: None,
+            var: name,
+            ..
+        }) => Some(name),
+        Stmt::While(StmtWhile {
+            label: Some(label), ..
+        })
+        | Stmt::For(StmtFor {
+            label: Some(label), ..
+        }) => Some(label),
+        Stmt::While(StmtWhile {
+            label: None, expr, ..
+        })
+        | Stmt::Match(StmtMatch { expr, .. })
+        | Stmt::Return(StmtReturn { expr: Some(expr) })
+        | Stmt::Expr(expr) => expr_fragment(expr),
+        Stmt::If(StmtIf { arms, .. }) => arms.first().and_then(|(cond, _)| expr_fragment(cond)),
+        Stmt::MacroCall(MacroCall { name, .. }) => Some(name),
+        Stmt::Def(def) => Some(def_name(def)),
+        Stmt::Loop(expr_loop) => loop_fragment(expr_loop),
+        Stmt::Break(StmtBreak { label, expr }) => label.or_else(|| expr_fragment(expr.as_ref()?)),
+        Stmt::Continue(label) => *label,
+        Stmt::Return(StmtReturn { expr: None }) | Stmt::Yield | Stmt::Debugger => None,
+    }
+}
+
+pub(super) fn expr_fragment<'a>(expr: &Expr<'a>) -> Option<&'a str> {
+    match expr {
+        Expr::Var(s) | Expr::Str(s) | Expr::Decimal(s) => Some(s),
+        Expr::Call(ExprFnCall { name, .. }) | Expr::MacroCall(MacroCall { name, .. }) => Some(name),
+        Expr::Binary(ExprBinary { lhs, rhs, .. }) => {
+            expr_fragment(lhs).or_else(|| expr_fragment(rhs))
+        }
+        Expr::Unary(ExprUnary { expr, .. }) => expr_fragment(expr),
+        Expr::Block(ExprBlock { stmts, ret }) => stmts
+            .first()
+            .and_then(stmt_fragment)
+            .or_else(|| expr_fragment(ret)),
+        Expr::Loop(expr_loop) => loop_fragment(expr_loop),
+        Expr::Integer(_) | Expr::Bool(_) => None,
+    }
+}
+
+fn loop_fragment<'a>(ExprLoop { label, body }: &ExprLoop<'a>) -> Option<&'a str> {
+    label.or_else(|| body.first().and_then(stmt_fragment))
+}
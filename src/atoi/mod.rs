@@ -1,29 +1,48 @@
-use anyhow::{anyhow, Ok, Result};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use anyhow::{anyhow, Result};
 
 use crate::{
-    ir::{BoolOperator, CacheTag, Label, LabelInfo, LabelMap, Operator},
-    parse::{Definition, ItemFn},
+    ir::{
+        BoolOperator, CacheTag, Label, LabelInfo, LabelKind, LabelMap, LabelOrigin, Operator,
+        DEFAULT_MEM_SIZE, DEFAULT_WORD_WIDTH,
+    },
+    parse::{
+        span::{ErrorList, SpannedError},
+        Attribute, Definition, ItemConstant, ItemFn, ItemStatic, Type,
+    },
+    CompileOptions,
 };
 
 use self::{
-    core::{CONST_MINUS_ONE, REG_COND_ENABLE, REG_CURRENT_MEM_OFFSET, REG_RETURNED_VALUE},
+    core::{
+        CONST_MINUS_ONE, REG_COND_ENABLE, REG_CURRENT_MEM_OFFSET, REG_LOOP_ENABLE,
+        REG_RETURNED_VALUE,
+    },
+    lint::{Lint, Warning},
     stack::UnsizedStack,
+    symbols::{SymbolKind, Symbols},
 };
 
+pub use self::core::DEFAULT_FIXED_SCALE;
+
 mod core;
+pub mod lint;
 mod stack;
+pub mod symbols;
 
 #[derive(Clone, Copy, Debug)]
 enum Binding<'a> {
-    Constant(i32),
+    Constant(i32, Type),
     String(&'a str),
-    Cache(CacheTag<'a>),
+    Cache(CacheTag<'a>, Type),
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 struct FuncDef<'a> {
     label: Label<'a>,
-    arg_count: u32,
+    args: Rc<[Type]>,
+    ret: Type,
 }
 
 pub struct Atoi<'a> {
@@ -32,13 +51,39 @@ pub struct Atoi<'a> {
     label_map: LabelMap<'a>,
     anonymous_label_pool: u32,
     anonymous_static_pool: u32,
+    anonymous_branch_pool: u32,
+    /// 正在读取的函数名称
+    current_fn: &'a str,
+    /// 正在读取的函数的返回值类型
+    current_ret: Type,
+    /// 定点数的倍数
+    fixed_scale: i32,
+    symbols: RefCell<Symbols<'a>>,
+    warnings: Vec<Warning<'a>>,
+    /// 当前被`#[allow(...)]`允许的警告
+    allowed_lints: Vec<Lint>,
+    /// 读取结束后检查是否被使用的定义
+    unused_candidates: Vec<(&'a str, SymbolKind)>,
+    /// 已生成的数学函数辅助标签
+    math_helpers: HashMap<&'static str, Label<'a>>,
+}
+
+impl Default for Atoi<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> Atoi<'a> {
     pub fn new() -> Self {
-        let mut label_map = LabelMap::new(64, 4);
+        Self::with_memory(DEFAULT_MEM_SIZE, DEFAULT_WORD_WIDTH)
+    }
+
+    pub fn with_memory(mem_size: u32, word_width: u32) -> Self {
+        let mut label_map = LabelMap::new(mem_size, word_width);
         for (key, val) in [
             (REG_COND_ENABLE, 0),
+            (REG_LOOP_ENABLE, 0),
             (REG_CURRENT_MEM_OFFSET, 0),
             (CONST_MINUS_ONE, -1),
             (REG_RETURNED_VALUE, 0),
@@ -52,18 +97,43 @@ impl<'a> Atoi<'a> {
             label_map,
             anonymous_label_pool: 0,
             anonymous_static_pool: 0,
+            anonymous_branch_pool: 0,
+            current_fn: "",
+            current_ret: Type::Int,
+            fixed_scale: DEFAULT_FIXED_SCALE,
+            symbols: Default::default(),
+            warnings: Vec::new(),
+            allowed_lints: Vec::new(),
+            unused_candidates: Vec::new(),
+            math_helpers: HashMap::new(),
         }
     }
 
-    fn new_label(&mut self) -> LabelInfo<'a> {
-        LabelInfo::new(Label::Anonymous(get_anonymous_id(
-            &mut self.anonymous_label_pool,
-        )))
+    pub fn with_options(options: &CompileOptions) -> Self {
+        let mut atoi = Self::with_memory(options.mem_size, options.word_width);
+        atoi.fixed_scale = options.fixed_scale;
+        atoi
+    }
+
+    fn new_label(&mut self, kind: LabelKind) -> LabelInfo<'a> {
+        LabelInfo::with_origin(
+            Label::Anonymous(get_anonymous_id(&mut self.anonymous_label_pool)),
+            LabelOrigin {
+                function: self.current_fn,
+                kind,
+                at: None,
+            },
+        )
     }
 
+    /// 读取所有定义。某个定义出错时继续读取其他定义，所有错误会一起返回
     pub fn parse(&mut self, defs: &[Definition<'a>]) -> Result<()> {
+        let mut errors = Vec::new();
+
         for def in defs {
-            self.read_def(def)?;
+            if let Err(err) = self.with_attrs(def_attrs(def), |this| this.read_def(def)) {
+                errors.push(SpannedError::attach(err, def_name(def)));
+            }
         }
 
         for def in defs {
@@ -71,15 +141,60 @@ impl<'a> Atoi<'a> {
                 continue;
             };
 
-            self.insert_fn(item_fn)?;
+            let depth = self.bindings.depth();
+            if let Err(err) = self.insert_fn(item_fn) {
+                self.bindings.pop_to(depth);
+                errors.push(SpannedError::attach(err, item_fn.name));
+            }
         }
 
-        Ok(())
+        self.check_unused();
+        ErrorList::into_result(errors)
+    }
+
+    /// 读取过程中产生的警告，按在源码中的位置排序
+    pub fn warnings(&self) -> &[Warning<'a>] {
+        &self.warnings
+    }
+
+    /// 到目前为止收集到的标识符信息
+    pub fn symbols(&self) -> Symbols<'a> {
+        self.symbols.borrow().clone()
     }
 
     pub fn finish(self) -> LabelMap<'a> {
         self.label_map
     }
+
+    /// 查找绑定并记录标识符的使用
+    fn find_binding(&self, name: &'a str) -> Option<Binding<'a>> {
+        let (definition, binding) = self.bindings.find_newest_entry(name)?;
+        self.symbols.borrow_mut().reference(name, definition);
+        Some(*binding)
+    }
+
+    /// 查找函数并记录标识符的使用
+    fn find_function(&self, name: &'a str) -> Option<FuncDef<'a>> {
+        let (definition, def) = self.functions.find_newest_entry(name)?;
+        self.symbols.borrow_mut().reference(name, definition);
+        Some(def.clone())
+    }
+}
+
+fn def_name<'a>(def: &Definition<'a>) -> &'a str {
+    match def {
+        Definition::Function(ItemFn { name, .. })
+        | Definition::Constant(ItemConstant { name, .. })
+        | Definition::Static(ItemStatic { name, .. }) => name,
+    }
+}
+
+fn def_attrs<'a, 'b>(def: &'b Definition<'a>) -> &'b [Attribute<'a>] {
+    match def {
+        Definition::Function(ItemFn { attrs, .. })
+        | Definition::Constant(ItemConstant { attrs, .. })
+        | Definition::Static(ItemStatic { attrs, .. }) => attrs,
+    }
 }
 
 fn get_fn_label<'a>(ast: &ItemFn<'a>) -> Label<'a> {
@@ -96,23 +211,53 @@ fn get_anonymous_id(pool: &mut u32) -> u32 {
 }
 
 fn variable_not_found(var: &str) -> anyhow::Error {
-    anyhow!("identifier `{var}` is not defined")
+    SpannedError::at(var, format_args!("identifier `{var}` is not defined"))
 }
 
 fn no_string_error() -> anyhow::Error {
     anyhow!("string can only be used in constant and macro definition")
 }
 
-pub fn calculate_arithmetical_bin_expr(lhs: i32, rhs: i32, opr: Operator) -> i32 {
-    match opr {
-        Operator::Add => lhs + rhs,
-        Operator::Sub => lhs - rhs,
-        Operator::Mul => lhs * rhs,
-        Operator::Div => lhs / rhs,
-        Operator::Rem => lhs % rhs,
+pub fn calculate_arithmetical_bin_expr(lhs: i32, rhs: i32, opr: Operator) -> Result<i32> {
+    if matches!(opr, Operator::Div | Operator::Rem) && rhs == 0 {
+        return Err(anyhow!("attempt to divide by zero"));
+    }
+
+    Ok(match opr {
+        // 与记分板相同，溢出时回绕，除法向下取整
+        Operator::Add => lhs.wrapping_add(rhs),
+        Operator::Sub => lhs.wrapping_sub(rhs),
+        Operator::Mul => lhs.wrapping_mul(rhs),
+        Operator::Div => floor_div(lhs as i64, rhs as i64) as i32,
+        Operator::Rem => floor_rem(lhs as i64, rhs as i64) as i32,
         Operator::Max => lhs.max(rhs),
         Operator::Min => lhs.min(rhs),
+        Operator::BitAnd => lhs & rhs,
+        Operator::BitOr => lhs | rhs,
+        Operator::BitXor => lhs ^ rhs,
+        Operator::Shl => lhs.wrapping_shl(rhs as u32),
+        Operator::Shr => lhs.wrapping_shr(rhs as u32),
         Operator::Set | Operator::Swp => panic!("set or swap operation is invalid"),
+    })
+}
+
+/// 向负无穷取整的除法，与记分板的`/=`相同
+pub(crate) fn floor_div(lhs: i64, rhs: i64) -> i64 {
+    let q = lhs / rhs;
+    if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+/// 与[`floor_div`]对应的余数，符号与除数相同
+pub(crate) fn floor_rem(lhs: i64, rhs: i64) -> i64 {
+    let r = lhs % rhs;
+    if r != 0 && (r < 0) != (rhs < 0) {
+        r + rhs
+    } else {
+        r
     }
 }
 
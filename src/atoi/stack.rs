@@ -23,12 +23,13 @@ impl<'a, T> UnsizedStack<'a, T> {
         self.block_indexes.push(self.items.len());
     }
 
-    pub fn find_newest(&self, tag: &str) -> Option<&T> {
+    /// 同时返回定义时使用的名称
+    pub fn find_newest_entry(&self, tag: &str) -> Option<(&'a str, &T)> {
         self.items
             .iter()
             .rev()
             .find(|(t, _)| *t == tag)
-            .map(|(_, item)| item)
+            .map(|(t, item)| (*t, item))
     }
 
     pub fn has_sibling_namesake(&self, tag: &str) -> bool {
@@ -41,4 +42,15 @@ impl<'a, T> UnsizedStack<'a, T> {
             self.items.truncate(size);
         }
     }
+
+    pub fn depth(&self) -> usize {
+        self.block_indexes.len()
+    }
+
+    /// 出错时弹出多余的块，恢复到`depth`层
+    pub fn pop_to(&mut self, depth: usize) {
+        while self.block_indexes.len() > depth {
+            self.pop_block();
+        }
+    }
 }
@@ -0,0 +1,47 @@
+use crate::parse::span::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Static,
+    Constant,
+    Variable,
+    Argument,
+}
+
+/// 一个标识符的定义
+#[derive(Clone, Debug)]
+pub struct SymbolDef<'a> {
+    /// 定义处的名称，指向源码
+    pub name: &'a str,
+    pub kind: SymbolKind,
+    /// 常量折叠后的值或静态变量的初始值
+    pub value: Option<String>,
+}
+
+/// 标识符的一次使用及其解析到的定义
+#[derive(Clone, Copy, Debug)]
+pub struct SymbolRef<'a> {
+    pub usage: &'a str,
+    pub definition: &'a str,
+}
+
+/// 读取过程中收集的标识符信息，供语言服务器等工具使用
+#[derive(Clone, Debug, Default)]
+pub struct Symbols<'a> {
+    pub definitions: Vec<SymbolDef<'a>>,
+    pub references: Vec<SymbolRef<'a>>,
+}
+
+impl<'a> Symbols<'a> {
+    pub(super) fn define(&mut self, name: &'a str, kind: SymbolKind, value: Option<String>) {
+        self.definitions.push(SymbolDef { name, kind, value });
+    }
+
+    pub(super) fn reference(&mut self, usage: &'a str, definition: &'a str) {
+        // 定义本身也会被查找，不记录为使用
+        if Span::of(usage) != Span::of(definition) {
+            self.references.push(SymbolRef { usage, definition });
+        }
+    }
+}
@@ -0,0 +1,731 @@
+use std::{
+    env::{self, current_dir},
+    fs,
+    io::{self, IsTerminal, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
+
+use crate::{
+    diagnostics::{Diagnostic, MessageFormat, Reporter, Severity},
+    format, formatter,
+    ir::{
+        compile::Files,
+        simulate::{
+            world::{ChatMessage, World},
+            SimulateOptions, SimulateResult,
+        },
+    },
+    lsp,
+    manifest::McManifest,
+    parse::span::Span,
+    project::{Project, PROJECT_FILE},
+    watch::{self, SyncStats},
+    Compilation, CompileOptions,
+};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "MCSH",
+    author = "FancyFlame<fancyflame@163.com>",
+    about = "MCSH编译器：将mcsh源代码文件编译为mcfunction文件"
+)]
+struct Cli {
+    #[arg(help = "输入源文件。在项目中可省略，默认为项目的`src/main.mcsh`")]
+    input: Option<PathBuf>,
+
+    #[arg(long, global = true, help = "有警告时视为编译失败")]
+    deny_warnings: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        help = "错误与警告的输出格式，`json`时每行输出一个JSON对象"
+    )]
+    message_format: MessageFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = "在指定函数上运行指令仿真")]
+    Simulate(SimulateArgs),
+
+    #[command(about = "在仿真器中运行所有以`test_`开头的导出函数")]
+    Test(TestArgs),
+
+    #[command(about = "编译文件并安装到游戏的开发行为包目录，用于调试")]
+    Dev(DevArgs),
+
+    #[command(alias = "b", about = "编译文件")]
+    Build(BuildArgs),
+
+    #[command(alias = "c", about = "只检查源文件中的错误与警告，不写入任何文件")]
+    Check,
+
+    #[command(about = "以标准输入输出运行语言服务器（LSP）")]
+    Lsp,
+
+    #[command(about = "格式化源文件")]
+    Fmt(FmtArgs),
+
+    #[command(about = "创建新项目")]
+    New(NewArgs),
+}
+
+#[derive(Args, Debug)]
+struct NewArgs {
+    #[arg(help = "项目目录，目录名即行为包名称")]
+    path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct DevArgs {
+    #[arg(
+        long,
+        value_name = "FOLDER",
+        help = "在`minecraftWorlds`中该文件夹的存档里启用行为包，优先于项目文件中的设置"
+    )]
+    world: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct FmtArgs {
+    #[arg(long, help = "不写入文件，文件需要格式化时返回错误")]
+    check: bool,
+}
+
+#[derive(Args, Debug)]
+struct SimulateArgs {
+    #[arg(
+        required = true,
+        help = "依次运行的导出函数，共享同一个世界状态。\
+            `函数名:次数`可重复运行，`mcsh_init`将重置环境"
+    )]
+    functions: Vec<String>,
+
+    #[arg(long, help = "随机数种子，相同种子的仿真结果可复现")]
+    seed: Option<u64>,
+
+    #[arg(
+        long = "set",
+        value_name = "NAME=VALUE",
+        value_parser = parse_static_assignment,
+        help = "设置导出静态变量的初始值，可多次使用"
+    )]
+    statics: Vec<(String, i32)>,
+
+    #[arg(long, help = "以JSON格式输出运行结果与导出静态变量")]
+    json: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "将每条指令的执行记录以JSON Lines格式写入文件，`-`表示标准输出"
+    )]
+    trace: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "从JSON文件读取仿真世界中的实体，默认只有一名玩家Steve"
+    )]
+    world: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "不以ANSI颜色显示消息中的样式。输出不是终端或设置了`NO_COLOR`时也不会显示颜色"
+    )]
+    no_color: bool,
+
+    #[arg(long, value_name = "FILE", help = "将覆盖率以lcov格式写入文件")]
+    coverage: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct TestArgs {
+    #[arg(help = "只运行名称包含该字符串的测试")]
+    filter: Option<String>,
+
+    #[arg(long, help = "随机数种子，相同种子的仿真结果可复现")]
+    seed: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "从JSON文件读取仿真世界中的实体，每个测试开始时都会恢复"
+    )]
+    world: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = "将覆盖率以lcov格式写入文件")]
+    coverage: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+struct BuildArgs {
+    #[arg(long, short, help = "编译文件保存路径")]
+    out: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        help = "在编译结果附加manifest.json文件。\
+            详细信息将启动命令行进行交互式信息输入。"
+    )]
+    manifest: bool,
+
+    #[arg(long, help = "编译结果打包为mcpack", requires = "manifest")]
+    mcpack: bool,
+
+    #[arg(long, help = "在编译结果附加图标文件")]
+    pack_icon: Option<PathBuf>,
+
+    #[arg(
+        long,
+        short,
+        help = "监视源文件，修改后自动重新编译。只会写入内容有变化的文件",
+        conflicts_with = "mcpack"
+    )]
+    watch: bool,
+}
+
+/// 解析命令行参数并执行命令
+pub fn run() -> Result<()> {
+    let cli = Cli::parse();
+    if let Command::Lsp = cli.command {
+        return lsp::run();
+    }
+
+    if let Command::New(NewArgs { path }) = &cli.command {
+        return new_project(path);
+    }
+
+    // 没有指定输入文件时编译所在项目的源文件
+    let (input, project) = match cli.input {
+        Some(input) => {
+            let project = Project::find(&absolute_path(&input)?)?;
+            (input, project)
+        }
+        None => match Project::find_in(&current_dir()?)? {
+            Some(project) => (project.main_file(), Some(project)),
+            None => {
+                return Err(anyhow!(
+                    "an input file is required outside of a project, \
+                    or create a project with `mcsh new`"
+                ))
+            }
+        },
+    };
+    let options = project
+        .as_ref()
+        .map_or_else(CompileOptions::default, |project| project.options.clone());
+
+    if let Command::Build(args @ BuildArgs { watch: true, .. }) = cli.command {
+        return watch_build(
+            &input,
+            args,
+            project.as_ref(),
+            &options,
+            cli.message_format,
+            cli.deny_warnings,
+        );
+    }
+
+    let file = fs::read_to_string(absolute_path(&input)?)?;
+    if let Command::Fmt(args) = &cli.command {
+        return format_file(&input, &file, args);
+    }
+
+    let reporter = Reporter::new(&input, &file, cli.message_format);
+    let compilation = analyze(&file, &options, &reporter, cli.deny_warnings)?;
+
+    match cli.command {
+        Command::Simulate(args) => start_simulation(&compilation, args, &input, &file),
+        Command::Test(args) => run_tests(&compilation, args, &reporter),
+        Command::Build(args) => build(&compilation, &input, args, project.as_ref()).map(drop),
+        Command::Dev(args) => install_dev(&compilation, project.as_ref(), args),
+        Command::Check => Ok(()),
+        Command::Lsp | Command::Fmt(_) | Command::New(_) => unreachable!(),
+    }
+}
+
+fn new_project(path: &Path) -> Result<()> {
+    let project = Project::create(&absolute_path(path)?)?;
+    println!(
+        "已创建项目`{}`，在项目目录中运行`mcsh build`即可编译",
+        project.manifest.name
+    );
+    Ok(())
+}
+
+/// 编译为中间代码，输出所有诊断信息
+fn analyze<'a>(
+    file: &'a str,
+    options: &CompileOptions,
+    reporter: &Reporter,
+    deny_warnings: bool,
+) -> Result<Compilation<'a>> {
+    let compilation =
+        crate::compile_with(file, options).map_err(|err| reporter.report_failure(&err))?;
+    reporter.report_warnings(&compilation.warnings, deny_warnings)?;
+    Ok(compilation)
+}
+
+fn watch_build(
+    input: &Path,
+    args: BuildArgs,
+    project: Option<&Project>,
+    options: &CompileOptions,
+    message_format: MessageFormat,
+    deny_warnings: bool,
+) -> Result<()> {
+    let path = absolute_path(input)?;
+    let mut first_build = true;
+
+    watch::watch(&path, || {
+        let file = fs::read_to_string(&path)?;
+        let reporter = Reporter::new(input, &file, message_format);
+        let compilation = analyze(&file, options, &reporter, deny_warnings)?;
+
+        // manifest与图标只在第一次编译时生成
+        let mut args = args.clone();
+        if !first_build {
+            args.manifest = false;
+            args.pack_icon = None;
+        }
+        first_build = false;
+
+        let stats = build(&compilation, input, args, project)?;
+        eprintln!(
+            "[{}] 编译完成：写入{}个文件，删除{}个文件",
+            chrono::Local::now().format("%H:%M:%S"),
+            stats.written,
+            stats.removed
+        );
+        Ok(())
+    })
+}
+
+fn format_file(input: &Path, file: &str, args: &FmtArgs) -> Result<()> {
+    let formatted = formatter::format_source(file)?;
+    if formatted == file {
+        return Ok(());
+    }
+
+    if args.check {
+        return Err(anyhow!("`{}` is not formatted", input.display()));
+    }
+    fs::write(absolute_path(input)?, formatted)?;
+    println!("已格式化`{}`", input.display());
+    Ok(())
+}
+
+fn absolute_path(p: &Path) -> Result<PathBuf> {
+    match current_dir() {
+        Ok(mut dir) => {
+            dir.push(p);
+            Ok(dir)
+        }
+        Err(err) => {
+            if p.is_absolute() {
+                Ok(p.to_path_buf())
+            } else {
+                Err(anyhow!(
+                    "cannot infer the absolute path, \
+                    because the working directory is unable to\
+                    access: {err}"
+                ))
+            }
+        }
+    }
+}
+
+fn build(
+    compilation: &Compilation,
+    file_path: &Path,
+    BuildArgs {
+        out,
+        manifest,
+        mcpack,
+        pack_icon,
+        watch: _,
+    }: BuildArgs,
+    project: Option<&Project>,
+) -> Result<SyncStats> {
+    let out_dir = absolute_path(out.as_deref().unwrap_or(Path::new("mcsh_out")))?;
+
+    if !out_dir.exists() {
+        fs::create_dir_all(&out_dir)?;
+    }
+
+    let work_dir = if mcpack {
+        dirs::cache_dir()
+            .as_ref()
+            .unwrap_or(&out_dir)
+            .join("mcsh_compile_cache")
+    } else {
+        out_dir.clone()
+    };
+
+    if !work_dir.exists() {
+        fs::create_dir_all(&work_dir)?;
+    }
+
+    if manifest {
+        let manifest_file = work_dir.join("manifest.json");
+        // 项目文件中的信息不会变化，可以直接覆盖
+        let manifest = match project {
+            Some(project) => project.manifest.clone(),
+            None if manifest_file.exists() && !mcpack => {
+                return Err(anyhow!(
+                    "已存在manifest.json文件，请妥善处理后重试或\
+                    关闭生成manifest.json选项"
+                ));
+            }
+            None => McManifest::interact()?,
+        };
+        fs::write(manifest_file, manifest.to_json())?;
+    }
+
+    if let Some(pack_icon) = pack_icon.or_else(|| project.and_then(Project::pack_icon)) {
+        let ext = pack_icon.extension();
+        let mut pack_icon_dst = work_dir.join("pack_icon");
+        if let Some(ext) = ext {
+            pack_icon_dst.set_extension(ext);
+        }
+        fs::copy(pack_icon, pack_icon_dst)?;
+    }
+
+    // 只写入有变化的文件，以便游戏中`/reload`时只需重新加载少量文件
+    let stats = watch::sync_files(&compilation.files()?, &work_dir.join("functions"))?;
+
+    if mcpack {
+        let mut out_file = out_dir.join(file_path.file_name().unwrap());
+        out_file.set_extension("mcpack");
+        zip_extensions::zip_create_from_directory(&out_file, &work_dir)?;
+        fs::remove_dir_all(&work_dir)?;
+    }
+
+    Ok(stats)
+}
+
+fn install_dev(
+    compilation: &Compilation,
+    project: Option<&Project>,
+    DevArgs { world }: DevArgs,
+) -> Result<()> {
+    let Some(project) = project else {
+        return Err(anyhow!(
+            "`dev` requires a project file `{PROJECT_FILE}` next to the source file or in a parent directory"
+        ));
+    };
+
+    let name = &project.manifest.name;
+    check_dir_name(name)?;
+    let world = world.or_else(|| project.dev.world.clone());
+    if let Some(world) = &world {
+        check_dir_name(world)?;
+    }
+
+    let minecraft_dir = project.minecraft_dir()?;
+    let pack_dir = minecraft_dir.join("development_behavior_packs").join(name);
+
+    // 同步整个行为包目录，上次安装留下的多余文件会被删除
+    let mut files: Files = compilation
+        .files()?
+        .into_iter()
+        .map(|(path, content)| (Path::new("functions").join(path), content))
+        .collect();
+    files.insert("manifest.json".into(), project.manifest.to_json().into());
+    if let Some(pack_icon) = project.pack_icon() {
+        files.insert("pack_icon.png".into(), fs::read(pack_icon)?);
+    }
+    let stats = watch::sync_files(&files, &pack_dir)?;
+    println!(
+        "已安装到`{}`：写入{}个文件，删除{}个文件",
+        pack_dir.display(),
+        stats.written,
+        stats.removed
+    );
+
+    if let Some(world) = world {
+        project.enable_in_world(&minecraft_dir.join("minecraftWorlds").join(&world))?;
+        println!("已在存档`{world}`中启用行为包");
+    }
+
+    Ok(())
+}
+
+/// 名称只能是单个普通的路径组成部分，`.`、`..`与多级路径都会指向其他目录
+fn check_dir_name(name: &str) -> Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(anyhow!("`{name}` cannot be used as a directory name")),
+    }
+}
+
+fn parse_static_assignment(s: &str) -> Result<(String, i32)> {
+    let Some((name, value)) = s.split_once('=') else {
+        return Err(anyhow!("expected `NAME=VALUE`, found `{s}`"));
+    };
+    Ok((name.trim().to_string(), value.trim().parse()?))
+}
+
+fn parse_call(s: &str) -> Result<(&str, u32)> {
+    match s.rsplit_once(':') {
+        Some((name, count)) => Ok((name, count.parse()?)),
+        None => Ok((s, 1)),
+    }
+}
+
+fn start_simulation(
+    compilation: &Compilation,
+    SimulateArgs {
+        functions,
+        seed,
+        statics,
+        json,
+        trace,
+        world,
+        no_color,
+        coverage,
+    }: SimulateArgs,
+    input: &Path,
+    source: &str,
+) -> Result<()> {
+    let color = !no_color && env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal();
+    let options = SimulateOptions {
+        seed,
+        statics,
+        trace: trace.is_some(),
+        world: load_world(world.as_deref())?,
+    };
+    let calls = functions
+        .iter()
+        .map(|call| parse_call(call))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut trace_output: Option<Box<dyn Write>> = match &trace {
+        Some(path) if path == Path::new("-") => Some(Box::new(io::stdout())),
+        Some(path) => Some(Box::new(io::BufWriter::new(fs::File::create(
+            absolute_path(path)?,
+        )?))),
+        None => None,
+    };
+
+    let mut records = Vec::new();
+    let summary = compilation.simulate_calls(&calls, &options, |fn_name, call| {
+        let SimulateResult {
+            result,
+            log,
+            events,
+            messages,
+        } = call;
+        if !json {
+            println!("[{fn_name}] 日志：\n{log}");
+            for ChatMessage {
+                recipient,
+                kind,
+                text,
+            } in &messages
+            {
+                let text = if color {
+                    format::render_ansi(text)
+                } else {
+                    format::strip_styles(text)
+                };
+                println!("[{fn_name}] {recipient} 收到{kind:?}：{text}");
+            }
+            println!("[{fn_name}] 运行结果：{result:?}");
+        }
+
+        if let Some(output) = &mut trace_output {
+            writeln!(
+                output,
+                "{}",
+                json!({ "event": "call", "function": fn_name })
+            )?;
+            for event in &events {
+                writeln!(output, "{}", event.to_json())?;
+            }
+            let finish = match &result {
+                Ok(value) => json!({ "event": "finish", "function": fn_name, "result": value }),
+                Err(err) => json!({
+                    "event": "finish",
+                    "function": fn_name,
+                    "error": err.to_string(),
+                }),
+            };
+            writeln!(output, "{finish}")?;
+        }
+
+        let messages: Vec<_> = messages
+            .iter()
+            .map(|m| {
+                json!({
+                    "recipient": m.recipient,
+                    "kind": format!("{:?}", m.kind).to_lowercase(),
+                    "text": m.text,
+                })
+            })
+            .collect();
+
+        records.push(match result {
+            Ok(value) => json!({ "function": fn_name, "result": value, "messages": messages }),
+            Err(err) => json!({
+                "function": fn_name,
+                "error": err.to_string(),
+                "messages": messages,
+            }),
+        });
+        Ok(())
+    })?;
+
+    if let Some(output) = &mut trace_output {
+        output.flush()?;
+    }
+
+    if let Some(path) = &coverage {
+        fs::write(
+            absolute_path(path)?,
+            summary
+                .coverage
+                .to_lcov(&input.display().to_string(), source),
+        )?;
+    }
+
+    if json {
+        let statics: serde_json::Map<_, _> = summary
+            .statics
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), json!(value)))
+            .collect();
+        let entities: Vec<_> = summary
+            .world
+            .entities
+            .iter()
+            .map(|e| json!({ "name": e.name, "tags": e.tags, "scores": e.scores }))
+            .collect();
+        println!(
+            "{:#}",
+            json!({
+                "seed": seed,
+                "calls": records,
+                "statics": statics,
+                "entities": entities,
+            })
+        );
+    } else {
+        println!("导出静态变量：");
+        for (name, value) in summary.statics {
+            match value {
+                Some(value) => println!("  {name} = {value}"),
+                None => println!("  {name} = none"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_tests(
+    compilation: &Compilation,
+    TestArgs {
+        filter,
+        seed,
+        world,
+        coverage,
+    }: TestArgs,
+    reporter: &Reporter,
+) -> Result<()> {
+    // JSON格式时标准输出只留给诊断信息
+    let mut out: Box<dyn Write> = match reporter.format() {
+        MessageFormat::Human => Box::new(io::stdout()),
+        MessageFormat::Json => Box::new(io::stderr()),
+    };
+
+    let report = compilation.run_tests(
+        filter.as_deref(),
+        &SimulateOptions {
+            seed,
+            world: load_world(world.as_deref())?,
+            ..Default::default()
+        },
+    )?;
+
+    writeln!(out, "运行{}个测试", report.tests.len())?;
+    for test in &report.tests {
+        let status = if test.failure.is_some() {
+            "FAILED"
+        } else {
+            "ok"
+        };
+        writeln!(out, "test {} ... {status}", test.name)?;
+    }
+
+    for test in report.failures() {
+        let (name, reason) = (test.name, test.failure.as_deref().unwrap_or_default());
+        writeln!(out, "\n---- {name} ----\n{reason}\n日志：\n{}", test.log)?;
+        if reporter.format() == MessageFormat::Json {
+            reporter.emit(&Diagnostic {
+                severity: Severity::Error,
+                message: format!("test `{name}` failed"),
+                code: None,
+                range: Span::of(name).range_in(reporter.source()),
+                notes: vec![reason.to_string()],
+            });
+        }
+    }
+
+    let coverage_report = &report.coverage;
+    let arms: Vec<_> = coverage_report.arms().collect();
+    writeln!(
+        out,
+        "\n覆盖率：函数 {}/{}，分支 {}/{}，指令 {}/{}",
+        coverage_report.functions_hit(),
+        coverage_report.functions.len(),
+        arms.iter().filter(|(_, arm)| arm.hits > 0).count(),
+        arms.len(),
+        coverage_report.insts_hit,
+        coverage_report.insts_total,
+    )?;
+    for (function, arm) in arms.iter().filter(|(_, arm)| arm.hits == 0) {
+        writeln!(
+            out,
+            "  未执行：函数`{function}`中第{}个分支语句的第{}个分支（`{}`）",
+            arm.group + 1,
+            arm.index + 1,
+            arm.arm
+        )?;
+    }
+
+    if let Some(path) = &coverage {
+        fs::write(
+            absolute_path(path)?,
+            coverage_report.to_lcov(&reporter.path().display().to_string(), reporter.source()),
+        )?;
+    }
+
+    match report.failures().count() {
+        0 => Ok(()),
+        failed => Err(anyhow!("{failed} test(s) failed")),
+    }
+}
+
+fn load_world(path: Option<&Path>) -> Result<World> {
+    match path {
+        Some(path) => {
+            let value = serde_json::from_str(&fs::read_to_string(absolute_path(path)?)?)?;
+            World::from_json(&value)
+        }
+        None => Ok(World::default()),
+    }
+}
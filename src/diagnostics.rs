@@ -0,0 +1,216 @@
+use std::{fmt, ops::Range, path::Path};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::{
+    atoi::lint::Warning,
+    parse::span::{ErrorList, Span, SpannedError},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+    /// 供人阅读的文本，输出到标准错误
+    #[default]
+    Human,
+    /// 每行一个JSON对象，输出到标准输出
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// 警告的名称
+    pub code: Option<&'static str>,
+    /// 在源码中的字节范围
+    pub range: Option<Range<usize>>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn from_warning(source: &str, warning: &Warning) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: warning.message.clone(),
+            code: Some(warning.lint.name()),
+            range: Span::of(warning.at).range_in(source),
+            notes: Vec::new(),
+        }
+    }
+
+    /// 展开`ErrorList`，每个错误对应一条诊断信息
+    pub fn from_error(source: &str, err: &anyhow::Error) -> Vec<Self> {
+        ErrorList::flatten(err)
+            .into_iter()
+            .map(|err| {
+                let (message, range) = match SpannedError::find(err) {
+                    Some(e) => (e.message.clone(), e.span.range_in(source)),
+                    None => (format!("{err:#}"), None),
+                };
+                Self {
+                    severity: Severity::Error,
+                    message,
+                    code: None,
+                    range,
+                    notes: Vec::new(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 编译失败时的所有诊断信息，包括警告
+#[derive(Debug)]
+pub struct CompileError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CompileError {
+    pub fn error_count(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count()
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "compilation failed with {} error(s)", self.error_count())
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// 以指定格式输出一个源文件的诊断信息
+pub struct Reporter<'a> {
+    path: &'a Path,
+    source: &'a str,
+    format: MessageFormat,
+}
+
+impl<'a> Reporter<'a> {
+    pub fn new(path: &'a Path, source: &'a str, format: MessageFormat) -> Self {
+        Self {
+            path,
+            source,
+            format,
+        }
+    }
+
+    pub fn path(&self) -> &'a Path {
+        self.path
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    pub fn format(&self) -> MessageFormat {
+        self.format
+    }
+
+    pub fn emit(&self, diagnostic: &Diagnostic) {
+        let range = diagnostic.range.clone();
+        let path = self.path.display();
+
+        match self.format {
+            MessageFormat::Human => {
+                let code = match diagnostic.code {
+                    Some(code) => format!(" [{code}]"),
+                    None => String::new(),
+                };
+                eprintln!(
+                    "{}: {}{code}",
+                    diagnostic.severity.name(),
+                    diagnostic.message
+                );
+                if let Some(range) = range {
+                    let (line, column) = line_col(self.source, range.start);
+                    eprintln!("  --> {path}:{line}:{column}");
+                }
+                for note in &diagnostic.notes {
+                    eprintln!("  = note: {note}");
+                }
+            }
+            MessageFormat::Json => {
+                let span = range.map(|range| {
+                    let (line, column) = line_col(self.source, range.start);
+                    let (end_line, end_column) = line_col(self.source, range.end);
+                    json!({
+                        "start": range.start,
+                        "end": range.end,
+                        "line": line,
+                        "column": column,
+                        "end_line": end_line,
+                        "end_column": end_column,
+                    })
+                });
+                println!(
+                    "{}",
+                    json!({
+                        "severity": diagnostic.severity.name(),
+                        "message": diagnostic.message,
+                        "code": diagnostic.code,
+                        "file": path.to_string(),
+                        "span": span,
+                        "notes": diagnostic.notes,
+                    })
+                );
+            }
+        }
+    }
+
+    /// 输出警告，`deny`时有警告即返回错误
+    pub fn report_warnings(&self, warnings: &[Diagnostic], deny: bool) -> Result<()> {
+        for warning in warnings {
+            self.emit(warning);
+        }
+        if deny && !warnings.is_empty() {
+            return Err(anyhow!(
+                "{} warning(s) denied by `--deny-warnings`",
+                warnings.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// 输出编译失败时的所有诊断信息，返回汇总的错误
+    pub fn report_failure(&self, err: &CompileError) -> anyhow::Error {
+        for diagnostic in &err.diagnostics {
+            self.emit(diagnostic);
+        }
+        anyhow!(
+            "could not compile `{}` due to {} error(s)",
+            self.path.display(),
+            err.error_count()
+        )
+    }
+}
+
+/// 从1开始的行号与列号
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
@@ -6,13 +6,20 @@ macro_rules! format_style {
         }
 
         impl FormatStyle {
+            pub const ALL: &'static [Self] = &[$(Self::$Name,)*];
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$Name => $name,)*
+                }
+            }
+
             pub fn code(&self) -> &'static str {
                 match self {
                     $(Self::$Name => $code,)*
                 }
             }
 
-            #[allow(dead_code)]
             pub fn from_code(s: &str) -> Option<Self> {
                 match s {
                     $($code => Some(Self::$Name),)*
@@ -33,24 +40,27 @@ macro_rules! format_style {
 }
 
 impl FormatStyle {
+    pub const NICK_NAMES: &'static [(&'static str, Self)] = &[
+        ("magenta", Self::LightPurple),
+        ("dark_yellow", Self::MinecoinGold),
+        ("quartz", Self::MaterialQuartz),
+        ("iron", Self::MaterialIron),
+        ("netherite", Self::MaterialNetherite),
+        ("rand_char", Self::Obfuscated),
+        ("redstone", Self::MaterialRedstone),
+        ("copper", Self::MaterialCopper),
+        ("dark_gold", Self::MaterialGold),
+        ("emerald", Self::MaterialEmerald),
+        ("diamond", Self::MaterialDiamond),
+        ("lapis", Self::MaterialLapis),
+        ("amethyst", Self::MaterialAmethyst),
+    ];
+
     pub fn from_nick_name(s: &str) -> Option<Self> {
-        let r = match s {
-            "magenta" => Self::LightPurple,
-            "dark_yellow" => Self::MinecoinGold,
-            "quartz" => Self::MaterialQuartz,
-            "iron" => Self::MaterialIron,
-            "netherite" => Self::MaterialNetherite,
-            "rand_char" => Self::Obfuscated,
-            "redstone" => Self::MaterialRedstone,
-            "copper" => Self::MaterialCopper,
-            "dark_gold" => Self::MaterialGold,
-            "emerald" => Self::MaterialEmerald,
-            "diamond" => Self::MaterialDiamond,
-            "lapis" => Self::MaterialLapis,
-            "amethyst" => Self::MaterialAmethyst,
-            _ => return None,
-        };
-        Some(r)
+        Self::NICK_NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, style)| *style)
     }
 }
 
@@ -87,3 +97,118 @@ format_style! {
     MaterialLapis     "material_lapis"     "t",
     MaterialAmethyst  "material_amethyst"  "u",
 }
+
+impl FormatStyle {
+    /// 颜色样式在终端中近似的RGB值，非颜色样式返回`None`
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        let rgb = match self {
+            Self::Black => (0x00, 0x00, 0x00),
+            Self::DarkBlue => (0x00, 0x00, 0xAA),
+            Self::DarkGreen => (0x00, 0xAA, 0x00),
+            Self::DarkAqua => (0x00, 0xAA, 0xAA),
+            Self::DarkRed => (0xAA, 0x00, 0x00),
+            Self::DarkPurple => (0xAA, 0x00, 0xAA),
+            Self::Gold => (0xFF, 0xAA, 0x00),
+            Self::Gray => (0xAA, 0xAA, 0xAA),
+            Self::DarkGray => (0x55, 0x55, 0x55),
+            Self::Blue => (0x55, 0x55, 0xFF),
+            Self::Green => (0x55, 0xFF, 0x55),
+            Self::Aqua => (0x55, 0xFF, 0xFF),
+            Self::Red => (0xFF, 0x55, 0x55),
+            Self::LightPurple => (0xFF, 0x55, 0xFF),
+            Self::Yellow => (0xFF, 0xFF, 0x55),
+            Self::White => (0xFF, 0xFF, 0xFF),
+            Self::MinecoinGold => (0xDD, 0xD6, 0x05),
+            Self::MaterialQuartz => (0xE3, 0xD4, 0xD1),
+            Self::MaterialIron => (0xCE, 0xCA, 0xCA),
+            Self::MaterialNetherite => (0x44, 0x3A, 0x3B),
+            Self::MaterialRedstone => (0x97, 0x16, 0x07),
+            Self::MaterialCopper => (0xB4, 0x68, 0x4D),
+            Self::MaterialGold => (0xDE, 0xB1, 0x2D),
+            Self::MaterialEmerald => (0x47, 0xA0, 0x36),
+            Self::MaterialDiamond => (0x2C, 0xBA, 0xA8),
+            Self::MaterialLapis => (0x21, 0x49, 0x7B),
+            Self::MaterialAmethyst => (0x9A, 0x5C, 0xC6),
+            Self::Obfuscated | Self::Bold | Self::Italic | Self::Reset => return None,
+        };
+        Some(rgb)
+    }
+
+    /// 对应的ANSI转义序列
+    pub fn ansi(&self) -> String {
+        match self {
+            Self::Bold => "\x1b[1m".into(),
+            Self::Italic => "\x1b[3m".into(),
+            Self::Reset => "\x1b[0m".into(),
+            // 终端中没有对应的效果
+            Self::Obfuscated => String::new(),
+            _ => {
+                let (r, g, b) = self.rgb().unwrap();
+                format!("\x1b[38;2;{r};{g};{b}m")
+            }
+        }
+    }
+}
+
+/// 把乘以`scale`后的定点数显示为小数，如`12.345`。`scale`必须是10的幂
+pub fn format_fixed(value: i32, scale: i32) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let value = value.unsigned_abs();
+    let scale = scale as u32;
+    let digits = scale.ilog10() as usize;
+    format!("{sign}{}.{:0digits$}", value / scale, value % scale)
+}
+
+/// 将带有`§`格式代码的文本渲染为终端中的ANSI样式
+pub fn render_ansi(text: &str) -> String {
+    let mut output = String::new();
+    let mut styled = false;
+    map_styles(text, |part| match part {
+        Ok(s) => output.push_str(s),
+        Err(style) => {
+            output += &style.ansi();
+            styled = !matches!(style, FormatStyle::Reset);
+        }
+    });
+
+    if styled {
+        output += &FormatStyle::Reset.ansi();
+    }
+    output
+}
+
+/// 去掉文本中的`§`格式代码
+pub fn strip_styles(text: &str) -> String {
+    let mut output = String::new();
+    map_styles(text, |part| {
+        if let Ok(s) = part {
+            output.push_str(s)
+        }
+    });
+    output
+}
+
+fn map_styles<'a>(text: &'a str, mut f: impl FnMut(Result<&'a str, FormatStyle>)) {
+    let mut parts = text.split('§');
+    if let Some(first) = parts.next() {
+        f(Ok(first));
+    }
+
+    for part in parts {
+        let mut chars = part.chars();
+        match chars
+            .next()
+            .and_then(|c| FormatStyle::from_code(c.encode_utf8(&mut [0; 4])))
+        {
+            Some(style) => {
+                f(Err(style));
+                f(Ok(chars.as_str()));
+            }
+            // 不是有效的格式代码时原样保留
+            None => {
+                f(Ok("§"));
+                f(Ok(part));
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::{anyhow, Result};
+
+use crate::parse::{
+    lexer::{parse_tokens, Delimiter, Punct, Token, TokenTree},
+    parse_file,
+    span::Span,
+};
+
+const INDENT: &str = "    ";
+
+/// 其后的`-`、`!`和`~`是一元运算符，其后的圆括号不是函数调用
+const KEYWORDS: &[&str] = &[
+    "let", "const", "static", "fn", "export", "if", "else", "while", "for", "in", "loop", "match",
+    "return",
+];
+
+/// 格式化源码。
+///
+/// 源码必须能通过语法分析。格式化只调整词元之间的空白，注释从词元之间的间隙中找回，
+/// 因此不会改变程序的含义。
+pub fn format_source(source: &str) -> Result<String> {
+    parse_file(source)?;
+    let (_, tokens) = parse_tokens(source).map_err(|err| anyhow!("{err}"))?;
+
+    let mut printer = Printer {
+        source,
+        output: String::new(),
+        indent: 0,
+        pending: None,
+    };
+    printer.sequence(&tokens, 0, source.len(), Mode::TopLevel);
+
+    let mut output = printer.output;
+    let trimmed = output.trim_end().len();
+    output.truncate(trimmed);
+    output.push('\n');
+    Ok(output)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    TopLevel,
+    /// 花括号中的语句
+    Block,
+    /// `match`的分支，以逗号分隔
+    Arms,
+    /// 圆括号或宏参数中的内容，不主动换行
+    Inline,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Break {
+    Newline,
+    BlankLine,
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    output: String,
+    indent: usize,
+    /// 写入下一个词元前需要的换行
+    pending: Option<Break>,
+}
+
+struct Comment<'a> {
+    text: &'a str,
+    newlines_before: usize,
+    newlines_after: usize,
+}
+
+impl<'a> Printer<'a> {
+    /// 输出`start..end`之间的词元，`start`与`end`是源码中的字节偏移
+    fn sequence(&mut self, tokens: &[TokenTree<'a>], start: usize, end: usize, mode: Mode) {
+        let mut cursor = start;
+
+        for (index, tt) in tokens.iter().enumerate() {
+            let range = self.range(tt);
+            let prev = index.checked_sub(1).map(|i| &tokens[i].token);
+            let before_prev = index.checked_sub(2).map(|i| &tokens[i].token);
+
+            self.gap(cursor, range.start);
+            cursor = range.end;
+
+            if self.pending.is_some() {
+                self.flush_break();
+            } else if let Some(prev) = prev {
+                if needs_space(before_prev, prev, &tt.token) {
+                    self.output.push(' ');
+                }
+            }
+
+            self.token(tt, prev);
+
+            let next = tokens.get(index + 1).map(|tt| &tt.token);
+            self.break_after(prev, &tt.token, next, mode);
+        }
+
+        self.gap(cursor, end);
+    }
+
+    fn token(&mut self, tt: &TokenTree<'a>, prev: Option<&Token>) {
+        let Token::Group(group) = &tt.token else {
+            self.output.push_str(tt.source);
+            return;
+        };
+
+        let range = self.range(tt);
+        let (inner_start, inner_end) = (range.start + 1, range.end - 1);
+        let is_macro = matches!(prev, Some(Token::Punct(Punct::Bang)));
+
+        match group.delimiter {
+            // 选择器与属性的写法多样，保持原样
+            Delimiter::Bracket => self.output.push_str(tt.source),
+            Delimiter::Paren => {
+                self.output.push('(');
+                self.sequence(&group.content, inner_start, inner_end, Mode::Inline);
+                self.output.push(')');
+            }
+            Delimiter::Brace if is_macro => {
+                self.output.push('{');
+                self.sequence(&group.content, inner_start, inner_end, Mode::Inline);
+                self.output.push('}');
+            }
+            Delimiter::Brace => {
+                let inner = &self.source[inner_start..inner_end];
+                if group.content.is_empty() && inner.trim().is_empty() {
+                    self.output.push_str("{}");
+                    return;
+                }
+
+                let is_arms = group
+                    .content
+                    .iter()
+                    .any(|tt| matches!(tt.token, Token::Punct(Punct::FatArrow)));
+
+                self.output.push('{');
+                self.indent += 1;
+                self.pending = Some(Break::Newline);
+                let mode = if is_arms { Mode::Arms } else { Mode::Block };
+                self.sequence(&group.content, inner_start, inner_end, mode);
+                self.indent -= 1;
+                self.pending = Some(Break::Newline);
+                self.flush_break();
+                self.output.push('}');
+            }
+        }
+    }
+
+    fn break_after(
+        &mut self,
+        prev: Option<&Token>,
+        token: &Token,
+        next: Option<&Token>,
+        mode: Mode,
+    ) {
+        if mode == Mode::Inline || next.is_none() && mode != Mode::TopLevel {
+            return;
+        }
+
+        let is_block = matches!(token, Token::Group(g) if g.delimiter == Delimiter::Brace)
+            && !matches!(prev, Some(Token::Punct(Punct::Bang)));
+        let is_attribute = matches!(token, Token::Group(g) if g.delimiter == Delimiter::Bracket)
+            && matches!(prev, Some(Token::Punct(Punct::Pound)));
+
+        let continues = matches!(
+            next,
+            Some(Token::Punct(Punct::Comma | Punct::Semi)) | Some(Token::Ident("else"))
+        );
+
+        let brk = match token {
+            Token::Punct(Punct::Semi) => Some(Break::Newline),
+            Token::Punct(Punct::Comma) if mode == Mode::Arms => Some(Break::Newline),
+            _ if is_attribute => Some(Break::Newline),
+            _ if is_block && !continues => {
+                if mode == Mode::TopLevel {
+                    Some(Break::BlankLine)
+                } else {
+                    Some(Break::Newline)
+                }
+            }
+            _ => None,
+        };
+
+        if brk.is_some() {
+            self.pending = brk;
+        }
+    }
+
+    /// 处理两个词元之间的注释与空行
+    fn gap(&mut self, start: usize, end: usize) {
+        let comments = comments(&self.source[start..end]);
+        let newlines = self.source[start..end].matches('\n').count();
+
+        for comment in &comments {
+            // 与前一个词元在同一行的注释保持在行尾
+            if comment.newlines_before == 0 && !self.output.is_empty() {
+                self.output.push(' ');
+            } else {
+                let brk = if comment.newlines_before >= 2 && !self.at_block_start() {
+                    Break::BlankLine
+                } else {
+                    Break::Newline
+                };
+                self.pending = Some(self.pending.map_or(brk, |p| p.max(brk)));
+                self.flush_break();
+            }
+
+            self.output.push_str(comment.text);
+            if comment.text.starts_with("//") || comment.newlines_after > 0 {
+                self.pending = Some(self.pending.unwrap_or(Break::Newline));
+            } else if self.pending.is_none() {
+                self.output.push(' ');
+            }
+        }
+
+        // 保留语句之间的一个空行
+        let trailing = comments.last().map_or(newlines, |c| c.newlines_after);
+        if trailing >= 2 && self.pending.is_some() && !self.at_block_start() {
+            self.pending = Some(Break::BlankLine);
+        }
+    }
+
+    fn flush_break(&mut self) {
+        let Some(brk) = self.pending.take() else {
+            return;
+        };
+
+        let trimmed = self.output.trim_end_matches(' ').len();
+        self.output.truncate(trimmed);
+        if !self.output.is_empty() {
+            self.output.push('\n');
+            if brk == Break::BlankLine {
+                self.output.push('\n');
+            }
+        }
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+    }
+
+    fn at_block_start(&self) -> bool {
+        self.output.trim_end().ends_with('{') || self.output.trim().is_empty()
+    }
+
+    fn range(&self, tt: &TokenTree) -> std::ops::Range<usize> {
+        Span::of(tt.source)
+            .range_in(self.source)
+            .expect("token must be a slice of the source")
+    }
+}
+
+impl PartialOrd for Break {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Break {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (*self == Break::BlankLine).cmp(&(*other == Break::BlankLine))
+    }
+}
+
+fn is_keyword(token: &Token) -> bool {
+    matches!(token, Token::Ident(id) if KEYWORDS.contains(id))
+}
+
+/// `prev`之后的`-`、`!`或`~`是否为一元运算符
+fn is_unary(prev: Option<&Token>) -> bool {
+    match prev {
+        None => true,
+        Some(Token::Punct(_)) => true,
+        Some(token) => is_keyword(token),
+    }
+}
+
+fn needs_space(before_prev: Option<&Token>, prev: &Token, next: &Token) -> bool {
+    use Punct::*;
+
+    let next_is_group = |d| matches!(next, Token::Group(g) if g.delimiter == d);
+    let prev_is_operand =
+        matches!(prev, Token::Ident(_) | Token::Literal(_) | Token::Group(_)) && !is_keyword(prev);
+
+    // 宏调用的感叹号
+    if let (Token::Punct(Bang), Token::Ident(_)) = (prev, before_prev.unwrap_or(&Token::Eof)) {
+        return false;
+    }
+    if matches!(next, Token::Punct(Bang)) && matches!(prev, Token::Ident(_)) && !is_keyword(prev) {
+        return false;
+    }
+
+    // `a max= b;`
+    if let (Token::Ident("max" | "min"), Token::Punct(Equal), Some(before_prev @ Token::Ident(_))) =
+        (prev, next, before_prev)
+    {
+        return is_keyword(before_prev);
+    }
+
+    match (prev, next) {
+        (_, Token::Punct(Comma | Semi | Colon | Dot)) => false,
+        (Token::Punct(At | Pound | Dot), _) => false,
+        (Token::Punct(Minus | Bang), _) if is_unary(before_prev) => false,
+        (Token::Punct(Tilde), _) => false,
+        (_, Token::Punct(Dot2 | Dot2Eq)) => !prev_is_operand,
+        (Token::Punct(Dot2 | Dot2Eq), _) => {
+            !matches!(
+                next,
+                Token::Ident(_) | Token::Literal(_) | Token::Punct(Minus)
+            ) || next_is_group(Delimiter::Paren)
+        }
+        _ if next_is_group(Delimiter::Paren) => !prev_is_operand,
+        _ if next_is_group(Delimiter::Bracket) => {
+            !matches!(prev, Token::Ident(_)) || is_keyword(prev)
+        }
+        _ => true,
+    }
+}
+
+fn comments(gap: &str) -> Vec<Comment<'_>> {
+    let mut comments: Vec<Comment> = Vec::new();
+    let mut rest = gap;
+    let mut newlines = 0;
+
+    while !rest.is_empty() {
+        let len = if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            rest.find("*/").map_or(rest.len(), |i| i + 2)
+        } else {
+            let c = rest.chars().next().unwrap();
+            if c == '\n' {
+                newlines += 1;
+            }
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+
+        if let Some(last) = comments.last_mut() {
+            last.newlines_after = newlines;
+        }
+        comments.push(Comment {
+            text: rest[..len].trim_end(),
+            newlines_before: newlines,
+            newlines_after: 0,
+        });
+        newlines = 0;
+        rest = &rest[len..];
+    }
+
+    if let Some(last) = comments.last_mut() {
+        last.newlines_after = newlines;
+    }
+    comments
+}
@@ -1,38 +1,37 @@
 use std::{
+    cell::RefCell,
     fmt::Display,
-    fs::{self, File},
     io::{self, Write as _},
     path::{Path, PathBuf},
 };
 
-use crate::ir::{to_display, REG_MATCH_ENABLED};
+use super::Files;
+use crate::ir::{to_display, ArmRange, REG_MATCH_ENABLED};
 
+/// 生成在`arms`中查找`pointer_reg`所在区间的函数，`file_content`以区间的下标生成对应的分支，
+/// 不在任何区间内时下标为`None`
 pub fn bin_search<F>(
-    functions_dir: &Path,
-    arms: &[i32],
+    files: &mut Files,
+    arms: &[ArmRange],
     namespace: &str,
     pointer_reg: &str,
     is_simple: bool,
     file_content: F,
 ) -> io::Result<()>
 where
-    F: Fn(Option<i32>, &mut File) -> io::Result<()>,
+    F: Fn(Option<usize>, &mut Vec<u8>) -> io::Result<()>,
 {
-    BinSearch::new(
-        functions_dir,
-        arms,
-        namespace,
-        pointer_reg,
-        file_content,
-        is_simple,
-    )
-    .entry_file()
+    let search = BinSearch::new(arms, namespace, pointer_reg, file_content, is_simple);
+    search.entry_file()?;
+    files.append(&mut search.files.into_inner());
+    Ok(())
 }
 
 struct BinSearch<'a, F1> {
     path_prefix: PathBuf,
+    files: RefCell<Files>,
     file_content: F1,
-    arms: &'a [i32],
+    arms: &'a [ArmRange],
     namespace: &'a str,
     pointer_reg: &'a str,
     is_simple: bool,
@@ -40,22 +39,20 @@ struct BinSearch<'a, F1> {
 
 impl<'a, F1> BinSearch<'a, F1>
 where
-    F1: Fn(Option<i32>, &mut File) -> io::Result<()>,
+    F1: Fn(Option<usize>, &mut Vec<u8>) -> io::Result<()>,
 {
     fn new(
-        functions_dir: &Path,
-        arms: &'a [i32],
+        arms: &'a [ArmRange],
         namespace: &'a str,
         pointer_reg: &'a str,
         file_content: F1,
         is_simple: bool,
     ) -> Self {
-        let mut path_prefix = functions_dir.to_path_buf();
-        path_prefix.push("MCSH");
-        path_prefix.push(namespace);
+        let path_prefix = Path::new("MCSH").join(namespace);
 
         Self {
             path_prefix,
+            files: RefCell::default(),
             file_content,
             arms,
             pointer_reg,
@@ -76,17 +73,21 @@ where
 
         let mut file_path = path_prefix.clone();
         file_path.set_extension("mcfunction");
-        let mut entry_file = File::create(file_path)?;
-        fs::create_dir(path_prefix)?;
+        let mut entry_file = Vec::new();
         let default_file = self.default_file()?;
 
         // 确定取值范围
         let (Some(first), Some(last)) = (arms.first(), arms.last()) else {
             writeln!(entry_file, "function MCSH/{namespace}/{default_file}")?;
+            self.add_file(file_path, entry_file);
             return Ok(());
         };
 
-        let start_search_func = self.branch_file(arms)?;
+        let start_search_func = self.branch_file(0, arms)?;
+        let range = ArmRange {
+            start: first.start,
+            end: last.end,
+        };
 
         if !is_simple {
             writeln!(
@@ -97,13 +98,13 @@ where
 
         writeln!(
             entry_file,
-            "execute if score MCSH {pointer_reg} matches {first}..{last} run function MCSH/{namespace}/{start_search_func}"
+            "execute if score MCSH {pointer_reg} matches {range} run function MCSH/{namespace}/{start_search_func}"
         )?;
 
         if *is_simple {
             writeln!(
                 entry_file,
-                "execute unless score MCSH {pointer_reg} matches {first}..{last} run \
+                "execute unless score MCSH {pointer_reg} matches {range} run \
                 function MCSH/{namespace}/{default_file}"
             )?;
         } else {
@@ -115,9 +116,14 @@ where
             )?;
         }
 
+        self.add_file(file_path, entry_file);
         Ok(())
     }
 
+    fn add_file(&self, path: PathBuf, content: Vec<u8>) {
+        self.files.borrow_mut().insert(path, content);
+    }
+
     fn check_match_enabled(&self) -> impl Display {
         let is_simple = self.is_simple;
         to_display(move |f| {
@@ -129,7 +135,7 @@ where
         })
     }
 
-    fn stop_match(&self, file: &mut File) -> io::Result<()> {
+    fn stop_match(&self, file: &mut Vec<u8>) -> io::Result<()> {
         if !self.is_simple {
             writeln!(file, "scoreboard players set MCSH {REG_MATCH_ENABLED} 0")
         } else {
@@ -139,54 +145,67 @@ where
 
     fn default_file(&self) -> io::Result<&'static str> {
         let mcfn = "Default";
-        let mut file = File::create(self.path_prefix.join("Default.mcfunction"))?;
+        let mut file = Vec::new();
         self.stop_match(&mut file)?;
         (self.file_content)(None, &mut file)?;
+        self.add_file(self.path_prefix.join("Default.mcfunction"), file);
         Ok(mcfn)
     }
 
-    fn branch_file(&self, arms: &[i32]) -> io::Result<String> {
+    /// `offset`为`arms`的第一个区间在所有区间中的下标
+    fn branch_file(&self, offset: usize, arms: &[ArmRange]) -> io::Result<String> {
         let Self {
             namespace,
             pointer_reg,
             ..
         } = self;
 
-        let create_file = |mcfn| {
-            File::create({
-                let mut file_path = self.path_prefix.join(&mcfn);
-                file_path.set_extension("mcfunction");
-                file_path
-            })
+        let add_file = |mcfn: &str, file| {
+            let mut file_path = self.path_prefix.join(mcfn);
+            file_path.set_extension("mcfunction");
+            self.add_file(file_path, file);
         };
 
         match arms {
             [] => unreachable!(),
             [one] => {
-                let mcfn = format!("Leaf{}", one);
-                let mut file = create_file(&mcfn)?;
+                let mcfn = match (one.start, one.end) {
+                    (start, end) if start == end => format!("Leaf{start}"),
+                    (start, end) => format!("Leaf{start}_{end}"),
+                };
+                let mut file = Vec::new();
                 self.stop_match(&mut file)?;
-                (self.file_content)(Some(*one), &mut file)?;
+                (self.file_content)(Some(offset), &mut file)?;
+                add_file(&mcfn, file);
                 Ok(mcfn)
             }
             [first_el, .., last_el] => {
-                let mcfn = format!("Branch{first_el}_{last_el}");
-                let mut file = create_file(&mcfn)?;
-                let (arms1, arms2) = arms.split_at(arms.len() / 2);
+                let mcfn = format!("Branch{}_{}", first_el.start, last_el.end);
+                let mut file = Vec::new();
+                let mid = arms.len() / 2;
+                let (arms1, arms2) = arms.split_at(mid);
 
-                let file_name1 = self.branch_file(arms1)?;
-                let file_name2 = self.branch_file(arms2)?;
+                let file_name1 = self.branch_file(offset, arms1)?;
+                let file_name2 = self.branch_file(offset + mid, arms2)?;
                 let check_match_enabled = self.check_match_enabled();
+                let range1 = ArmRange {
+                    start: first_el.start,
+                    end: arms1.last().unwrap().end,
+                };
+                let range2 = ArmRange {
+                    start: arms2.first().unwrap().start,
+                    end: last_el.end,
+                };
 
                 writeln!(
                     file,
-                    "execute {check_match_enabled}if score MCSH {pointer_reg} matches {first_el}..{0} run \
+                    "execute {check_match_enabled}if score MCSH {pointer_reg} matches {range1} run \
                         function MCSH/{namespace}/{file_name1}\n\
-                    execute {check_match_enabled}if score MCSH {pointer_reg} matches {1}..{last_el} run \
+                    execute {check_match_enabled}if score MCSH {pointer_reg} matches {range2} run \
                         function MCSH/{namespace}/{file_name2}",
-                    arms1.last().unwrap(), arms2.first().unwrap()
                 )?;
 
+                add_file(&mcfn, file);
                 Ok(mcfn)
             }
         }
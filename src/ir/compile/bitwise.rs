@@ -0,0 +1,147 @@
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use const_format::formatcp;
+
+use super::{Files, PREFIX};
+use crate::ir::{to_display, Operator};
+
+pub(crate) const REG_BIT_LHS: &str = formatcp!("{PREFIX}_Bit_Lhs");
+pub(crate) const REG_BIT_RHS: &str = formatcp!("{PREFIX}_Bit_Rhs");
+const REG_BIT_RESULT: &str = formatcp!("{PREFIX}_Bit_Result");
+const REG_BIT_WEIGHT: &str = formatcp!("{PREFIX}_Bit_Weight");
+const REG_BIT_A: &str = formatcp!("{PREFIX}_Bit_A");
+const REG_BIT_B: &str = formatcp!("{PREFIX}_Bit_B");
+const REG_BIT_TWO: &str = formatcp!("{PREFIX}_Bit_Two");
+
+/// 辅助函数使用的记分板，需要在初始化时创建
+pub(super) const BITWISE_REGS: &[&str] = &[
+    REG_BIT_LHS,
+    REG_BIT_RHS,
+    REG_BIT_RESULT,
+    REG_BIT_WEIGHT,
+    REG_BIT_A,
+    REG_BIT_B,
+    REG_BIT_TWO,
+];
+
+pub(crate) fn is_bitwise(opr: Operator) -> bool {
+    matches!(
+        opr,
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor | Operator::Shl | Operator::Shr
+    )
+}
+
+/// 计算`Bit_Lhs opr Bit_Rhs`并写回`Bit_Lhs`的辅助函数
+pub(super) fn bitwise_func(opr: Operator) -> impl Display {
+    let name = match opr {
+        Operator::BitAnd => "And",
+        Operator::BitOr => "Or",
+        Operator::BitXor => "Xor",
+        Operator::Shl => "Shl",
+        Operator::Shr => "Shr",
+        _ => unreachable!("`{opr:?}` is not a bitwise operator"),
+    };
+    to_display(move |f| write!(f, "{PREFIX}_Bitwise_{name}"))
+}
+
+/// 辅助函数中的一条命令
+#[derive(Clone, Copy)]
+pub(crate) enum HelperStep {
+    Set(&'static str, i32),
+    Operation(&'static str, Operator, &'static str),
+    /// `Bit_Rhs`不小于给定值时才执行的运算
+    IfRhsAtLeast(i32, &'static str, Operator, &'static str),
+}
+
+/// 辅助函数的命令序列，编译与仿真使用同一份。
+///
+/// 记分板的`/=`与`%=`向下取整，因此对负数也有`x /= 2`等于`x >> 1`、`x %= 2`等于`x & 1`
+pub(crate) fn helper_steps(opr: Operator) -> Vec<HelperStep> {
+    use HelperStep::*;
+
+    let mut steps = vec![Set(REG_BIT_TWO, 2)];
+    match opr {
+        Operator::Shl | Operator::Shr => {
+            // 与`i32::wrapping_shl`相同，只使用低5位
+            steps.push(Set(REG_BIT_A, 32));
+            steps.push(Operation(REG_BIT_RHS, Operator::Rem, REG_BIT_A));
+            let (step_opr, step_src) = if matches!(opr, Operator::Shl) {
+                (Operator::Add, REG_BIT_LHS)
+            } else {
+                (Operator::Div, REG_BIT_TWO)
+            };
+            for n in 1..32 {
+                steps.push(IfRhsAtLeast(n, REG_BIT_LHS, step_opr, step_src));
+            }
+        }
+        _ => {
+            steps.push(Set(REG_BIT_RESULT, 0));
+            steps.push(Set(REG_BIT_WEIGHT, 1));
+            for _ in 0..32 {
+                steps.extend([
+                    Operation(REG_BIT_A, Operator::Set, REG_BIT_LHS),
+                    Operation(REG_BIT_A, Operator::Rem, REG_BIT_TWO),
+                    Operation(REG_BIT_B, Operator::Set, REG_BIT_RHS),
+                    Operation(REG_BIT_B, Operator::Rem, REG_BIT_TWO),
+                    Operation(REG_BIT_LHS, Operator::Div, REG_BIT_TWO),
+                    Operation(REG_BIT_RHS, Operator::Div, REG_BIT_TWO),
+                ]);
+                match opr {
+                    Operator::BitAnd => steps.push(Operation(REG_BIT_A, Operator::Mul, REG_BIT_B)),
+                    Operator::BitOr => steps.push(Operation(REG_BIT_A, Operator::Max, REG_BIT_B)),
+                    _ => steps.extend([
+                        Operation(REG_BIT_A, Operator::Add, REG_BIT_B),
+                        Operation(REG_BIT_A, Operator::Rem, REG_BIT_TWO),
+                    ]),
+                }
+                // 第32位的权重溢出为`i32::MIN`，正好是符号位
+                steps.extend([
+                    Operation(REG_BIT_A, Operator::Mul, REG_BIT_WEIGHT),
+                    Operation(REG_BIT_RESULT, Operator::Add, REG_BIT_A),
+                    Operation(REG_BIT_WEIGHT, Operator::Add, REG_BIT_WEIGHT),
+                ]);
+            }
+            steps.push(Operation(REG_BIT_LHS, Operator::Set, REG_BIT_RESULT));
+        }
+    }
+    steps
+}
+
+/// 生成辅助函数
+pub(super) fn bitwise_helper(files: &mut Files, opr: Operator) -> io::Result<()> {
+    let mut file = Vec::new();
+    let op = |dst: &str, opr: Operator, src: &str| {
+        let opr = match opr {
+            Operator::Set => "=",
+            Operator::Add => "+=",
+            Operator::Mul => "*=",
+            Operator::Div => "/=",
+            Operator::Rem => "%=",
+            Operator::Max => ">",
+            _ => unreachable!("`{opr:?}` is not used in bitwise helpers"),
+        };
+        format!("scoreboard players operation MCSH {dst} {opr} MCSH {src}")
+    };
+
+    for step in helper_steps(opr) {
+        match step {
+            HelperStep::Set(reg, value) => {
+                writeln!(file, "scoreboard players set MCSH {reg} {value}")?
+            }
+            HelperStep::Operation(dst, opr, src) => writeln!(file, "{}", op(dst, opr, src))?,
+            HelperStep::IfRhsAtLeast(n, dst, opr, src) => writeln!(
+                file,
+                "execute if score MCSH {REG_BIT_RHS} matches {n}.. run {}",
+                op(dst, opr, src)
+            )?,
+        }
+    }
+
+    let path = PathBuf::from("MCSH").join(format!("{}.mcfunction", bitwise_func(opr)));
+    files.insert(path, file);
+    Ok(())
+}
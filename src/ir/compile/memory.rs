@@ -1,22 +1,20 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
-    fs::File,
     io::{self, Write as _},
-    path::Path,
+    path::PathBuf,
 };
 
 use const_format::formatcp;
 
-use super::{compile_cache_tag, PREFIX};
-use crate::ir::{to_display, CacheTag, MCSH_INIT_FUNC, REG_MATCH_ENABLED};
+use super::{bitwise::BITWISE_REGS, compile_cache_tag, Files, PREFIX};
+use crate::ir::{to_display, ArmRange, CacheTag, MCSH_INIT_FUNC, REG_MATCH_ENABLED};
 
 use super::binary_search::bin_search;
 
 pub(super) const REG_MEM_PTR: &str = formatcp!("{PREFIX}_MemoryPointer");
 
 pub struct MemoryMaker<'a> {
-    pub functions_dir: &'a Path,
     pub mem_size: u32,
     pub cache_size: u32,
     pub used_cache_regs: &'a HashSet<u32>,
@@ -33,12 +31,12 @@ pub fn compile_load_func(chunks: u32) -> impl Display {
 }
 
 impl MemoryMaker<'_> {
-    pub fn mem_swap_func(&self) -> io::Result<()> {
+    pub fn mem_swap_func(&self, files: &mut Files) -> io::Result<()> {
         let mem_chunk_count = self.mem_size.div_ceil(self.word_width);
         let cache_chunk_count = self.cache_size.div_ceil(self.word_width);
 
         for chunk_count in 1..=cache_chunk_count {
-            let init = |is_store| {
+            let mut init = |is_store| {
                 let namespace = if is_store {
                     compile_store_func(chunk_count).to_string()
                 } else {
@@ -46,14 +44,16 @@ impl MemoryMaker<'_> {
                 };
 
                 bin_search(
-                    self.functions_dir,
-                    &Vec::from_iter(0..mem_chunk_count as i32),
+                    files,
+                    &Vec::from_iter(
+                        (0..mem_chunk_count as i32).map(|i| ArmRange { start: i, end: i }),
+                    ),
                     &namespace,
                     REG_MEM_PTR,
                     true,
                     |index, file| {
                         let index = match index {
-                            Some(idx) => u32::try_from(idx).unwrap(),
+                            Some(idx) => idx as u32,
                             None => return writeln!(file, "say MCSH ERROR: Memory overflow"),
                         };
 
@@ -83,26 +83,29 @@ impl MemoryMaker<'_> {
         Ok(())
     }
 
-    pub fn mem_bootstrap(&self) -> io::Result<()> {
-        let mut file = File::create(
-            self.functions_dir
-                .join(formatcp!("{MCSH_INIT_FUNC}.mcfunction")),
-        )?;
+    pub fn mem_bootstrap(&self, files: &mut Files) -> io::Result<()> {
+        let mut file = Vec::new();
 
         writeln!(file, "scoreboard players reset MCSH")?;
         writeln!(file, "{}", register_object(REG_MATCH_ENABLED))?;
         writeln!(file, "{}", register_object(REG_MEM_PTR))?;
+        for reg in BITWISE_REGS {
+            writeln!(file, "{}", register_object(reg))?;
+        }
 
         for x in 0..self.mem_size {
             writeln!(file, "{}", register_object(compile_mem_unit(x)))?;
         }
 
-        for x in (0..self.cache_size).chain(
-            self.used_cache_regs
-                .iter()
-                .copied()
-                .filter(|x| *x >= self.cache_size),
-        ) {
+        let mut extra_regs: Vec<_> = self
+            .used_cache_regs
+            .iter()
+            .copied()
+            .filter(|x| *x >= self.cache_size)
+            .collect();
+        extra_regs.sort_unstable();
+
+        for x in (0..self.cache_size).chain(extra_regs) {
             writeln!(
                 file,
                 "{}",
@@ -110,8 +113,14 @@ impl MemoryMaker<'_> {
             )?;
         }
 
-        for (key, value) in self.static_caches.iter() {
-            let key = compile_cache_tag(*key);
+        let mut statics: Vec<_> = self
+            .static_caches
+            .iter()
+            .map(|(key, value)| (compile_cache_tag(*key).to_string(), value))
+            .collect();
+        statics.sort_unstable();
+
+        for (key, value) in statics {
             let reg = register_object(&key);
 
             writeln!(
@@ -121,6 +130,10 @@ impl MemoryMaker<'_> {
             )?;
         }
 
+        files.insert(
+            PathBuf::from(formatcp!("{MCSH_INIT_FUNC}.mcfunction")),
+            file,
+        );
         Ok(())
     }
 }
@@ -1,6 +1,9 @@
 use crate::ir::{
-    compile::{compile_load_func, compile_store_func, REG_MEM_PTR},
-    to_display, BoolOperator, BoolOprRhs, FormatArgument, Operator,
+    compile::{
+        bitwise::{bitwise_func, is_bitwise, REG_BIT_LHS, REG_BIT_RHS},
+        compile_load_func, compile_store_func, REG_MEM_PTR,
+    },
+    to_display, BoolOperator, BoolOprRhs, FmtCommand, FormatArgument, Operator,
 };
 
 use super::{CacheTag, Ir, Label, PREFIX};
@@ -142,13 +145,29 @@ pub(super) fn compile_ir<'a>(ir: &'a Ir) -> impl Display + 'a {
 
         Ir::Increase { dst, value } => {
             let dst = compile_cache_tag(*dst);
-            writeln!(output, "scoreboard players add MCSH {dst} {value}")
+            if *value < 0 {
+                let value = value.unsigned_abs();
+                writeln!(output, "scoreboard players remove MCSH {dst} {value}")
+            } else {
+                writeln!(output, "scoreboard players add MCSH {dst} {value}")
+            }
         }
 
         Ir::Operation { dst, opr, src } => {
             let dst = compile_cache_tag(*dst);
             let src = compile_cache_tag(*src);
 
+            if is_bitwise(*opr) {
+                let func = bitwise_func(*opr);
+                return writeln!(
+                    output,
+                    "scoreboard players operation MCSH {REG_BIT_LHS} = MCSH {dst}\n\
+                    scoreboard players operation MCSH {REG_BIT_RHS} = MCSH {src}\n\
+                    function MCSH/{func}\n\
+                    scoreboard players operation MCSH {dst} = MCSH {REG_BIT_LHS}",
+                );
+            }
+
             let opr = match opr {
                 Operator::Set => "=",
                 Operator::Add => "+=",
@@ -159,6 +178,11 @@ pub(super) fn compile_ir<'a>(ir: &'a Ir) -> impl Display + 'a {
                 Operator::Max => ">",
                 Operator::Min => "<",
                 Operator::Swp => "><",
+                Operator::BitAnd
+                | Operator::BitOr
+                | Operator::BitXor
+                | Operator::Shl
+                | Operator::Shr => unreachable!(),
             };
 
             writeln!(
@@ -211,7 +235,15 @@ pub(super) fn compile_ir<'a>(ir: &'a Ir) -> impl Display + 'a {
             unreachable!("table ir should manually generate");
         }
 
-        Ir::CmdFmt { prefix, args } => {
+        Ir::CmdFmt {
+            cmd,
+            selector,
+            args,
+        } => {
+            let prefix = match cmd {
+                FmtCommand::Tellraw => format!("tellraw {selector}"),
+                FmtCommand::Titleraw(position) => format!("titleraw {selector} {position}"),
+            };
             let mut printer = Printer::new(output, &prefix)?;
 
             for arg in args {
@@ -252,7 +284,7 @@ pub(super) fn compile_ir<'a>(ir: &'a Ir) -> impl Display + 'a {
     })
 }
 
-pub(super) fn compile_cache_tag(ct: CacheTag) -> impl Display + '_ {
+pub(crate) fn compile_cache_tag(ct: CacheTag<'_>) -> impl Display + '_ {
     to_display(move |f| match ct {
         CacheTag::Regular(id) => write!(f, "{PREFIX}_CacheTag_{id}"),
         CacheTag::Static(id) => write!(f, "{PREFIX}_StaticCacheTag_{id}"),
@@ -261,7 +293,7 @@ pub(super) fn compile_cache_tag(ct: CacheTag) -> impl Display + '_ {
     })
 }
 
-pub(super) fn compile_label<'a>(label: &'a Label, with_dir: bool) -> impl Display + 'a {
+pub(crate) fn compile_label<'a>(label: &'a Label, with_dir: bool) -> impl Display + 'a {
     let dir = if with_dir { "MCSH/" } else { "" };
 
     to_display(move |f| match label {
@@ -1,15 +1,16 @@
 use std::{
-    collections::HashSet,
-    fs::{self, File},
+    collections::{BTreeMap, BTreeSet, HashSet},
     io::{self, Write},
-    path::Path,
+    path::PathBuf,
 };
 
-use super::{CacheTag, Ir, Label, LabelMap, PREFIX};
-use anyhow::{anyhow, Result};
+use super::{ArmRange, CacheTag, Ir, Label, LabelMap, Operator, PREFIX};
+use anyhow::Result;
 use memory::*;
 use miscellaneous::*;
 
+pub(crate) use miscellaneous::{compile_cache_tag, compile_label};
+
 macro_rules! display_write {
     ($($tt:tt)*) => {
         $crate::ir::to_display(|formatter| write!(formatter, $($tt)*))
@@ -17,53 +18,55 @@ macro_rules! display_write {
 }
 
 mod binary_search;
+pub(crate) mod bitwise;
 mod memory;
 mod miscellaneous;
 
+/// 编译生成的文件内容，路径相对于行为包的`functions`目录
+pub type Files = BTreeMap<PathBuf, Vec<u8>>;
+
 impl LabelMap<'_> {
-    pub fn compile(&self, functions_dir: &Path) -> Result<()> {
+    /// 编译为内存中的文件
+    pub fn compile_files(&self) -> Result<Files> {
         let mut anonymous_table_id = 0;
-        let mcsh_dir = functions_dir.join("MCSH");
-        if !mcsh_dir.exists() {
-            fs::create_dir_all(mcsh_dir)?;
-        }
+        let mut files = Files::new();
 
-        if self.mem_size % self.word_width != 0 {
-            return Err(anyhow!(
-                "the memory size ({}) is not a multiple of the word width ({})",
-                self.mem_size,
-                self.word_width
-            ));
-        }
+        self.verify()?;
 
         let mut cache_set = HashSet::new();
         let mut cache_size = 0;
+        let mut bitwise_oprs = BTreeSet::new();
 
         //let optimized_label_map = dead_label_delete(&self.label_map)?;
 
-        for (label, info) in &self.label_map {
+        // 固定顺序，使匹配表的编号在重新编译时保持不变
+        for info in self.labels() {
             compile_one_label(
-                functions_dir,
+                &mut files,
                 &mut cache_size,
                 &mut cache_set,
-                *label,
+                info.label,
                 &info.insts,
                 &mut anonymous_table_id,
+                &mut bitwise_oprs,
             )?;
         }
 
+        for opr in bitwise_oprs {
+            bitwise::bitwise_helper(&mut files, opr)?;
+        }
+
         let mem_maker = MemoryMaker {
-            functions_dir,
             used_cache_regs: &cache_set,
             mem_size: self.mem_size,
             cache_size,
             static_caches: &self.static_map,
             word_width: self.word_width,
         };
-        mem_maker.mem_swap_func()?;
-        mem_maker.mem_bootstrap()?;
+        mem_maker.mem_swap_func(&mut files)?;
+        mem_maker.mem_bootstrap(&mut files)?;
 
-        Ok(())
+        Ok(files)
     }
 }
 
@@ -98,20 +101,21 @@ impl LabelMap<'_> {
 }*/
 
 fn compile_one_label(
-    functions_dir: &Path,
+    files: &mut Files,
     cache_size: &mut u32,
     cache_set: &mut HashSet<u32>,
     label: Label,
     insts: &Vec<Ir>,
     anonymous_table_id: &mut u32,
+    bitwise_oprs: &mut BTreeSet<Operator>,
 ) -> io::Result<()> {
-    let mut file_path = functions_dir.to_path_buf();
+    let mut file_path = PathBuf::new();
     if let Label::Named { export: false, .. } | Label::Anonymous(_) = label {
         file_path.push("MCSH");
     }
     file_path.push(compile_label(&label, false).to_string());
     file_path.set_extension("mcfunction");
-    let mut file = File::create(file_path)?;
+    let mut file = Vec::new();
 
     for inst in insts {
         match inst {
@@ -122,6 +126,11 @@ fn compile_one_label(
                 if let CacheTag::Regular(id) = dst {
                     cache_set.insert(*id);
                 }
+                if let Ir::Operation { opr, .. } = inst {
+                    if bitwise::is_bitwise(*opr) {
+                        bitwise_oprs.insert(*opr);
+                    }
+                }
             }
             Ir::Table {
                 cond,
@@ -129,26 +138,27 @@ fn compile_one_label(
             } => {
                 let tid = *anonymous_table_id;
                 *anonymous_table_id += 1;
-                let mut arm_vec: Vec<i32> = arms.iter().filter_map(|&(a, _)| a).collect();
-                arm_vec.sort();
+                let (default_arm, range_arms) = match arms.split_first() {
+                    Some(((None, label), rest)) => (Some(label), rest),
+                    _ => (None, &arms[..]),
+                };
+                let ranges: Vec<ArmRange> =
+                    range_arms.iter().map(|(arm, _)| arm.unwrap()).collect();
 
                 let mcfn = format!("{PREFIX}_Table_{tid}");
                 binary_search::bin_search(
-                    functions_dir,
-                    &arm_vec,
+                    files,
+                    &ranges,
                     &mcfn,
                     &compile_cache_tag(*cond).to_string(),
                     false,
-                    |arm, file| {
-                        let target_label = match arms.binary_search_by(|&(x, _)| x.cmp(&arm)) {
-                            Ok(idx) => &arms[idx].1,
-                            Err(_) => {
-                                if arm.is_none() {
-                                    return Ok(());
-                                } else {
-                                    panic!("table arms is not sorted");
-                                }
-                            }
+                    |index, file| {
+                        let target_label = match index {
+                            Some(index) => &range_arms[index].1,
+                            None => match default_arm {
+                                Some(label) => label,
+                                None => return Ok(()),
+                            },
                         };
                         writeln!(file, "function {}", compile_label(target_label, true))
                     },
@@ -162,5 +172,6 @@ fn compile_one_label(
         write!(file, "{}", compile_ir(inst))?;
     }
 
+    files.insert(file_path, file);
     Ok(())
 }
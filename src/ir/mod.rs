@@ -7,14 +7,18 @@ use std::{
 use anyhow::{anyhow, Result};
 use const_format::formatcp;
 
-use crate::format::FormatStyle;
+pub use crate::format::FormatStyle;
 
 const PREFIX: &str = "__MCSH_Private";
 const REG_MATCH_ENABLED: &str = formatcp!("{PREFIX}_Flag_MatchEnabled");
 const MCSH_INIT_FUNC: &str = "mcsh_init";
 
-pub mod compile;
-pub mod simulate;
+pub const DEFAULT_MEM_SIZE: u32 = 64;
+pub const DEFAULT_WORD_WIDTH: u32 = 4;
+
+pub(crate) mod compile;
+pub(crate) mod simulate;
+mod verify;
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub enum CacheTag<'a> {
@@ -41,6 +45,87 @@ pub struct LabelMap<'a> {
 pub struct LabelInfo<'a> {
     pub label: Label<'a>,
     pub insts: Vec<Ir<'a>>,
+    /// 标签由源码中的哪个部分生成，用于覆盖率报告
+    pub origin: Option<LabelOrigin<'a>>,
+    /// 从这个标签开始执行的语句的源码片段，用于覆盖率报告的行号
+    pub stmts: Vec<&'a str>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LabelOrigin<'a> {
+    /// 所在函数的名称，指向源码
+    pub function: &'a str,
+    pub kind: LabelKind,
+    /// 分支开头的源码片段，用于覆盖率报告的行号
+    pub at: Option<&'a str>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LabelKind {
+    /// 函数体的开头
+    Function,
+    /// `if`或`match`语句的一个分支，`group`区分同一函数中不同的语句
+    Arm {
+        group: u32,
+        index: u32,
+        arm: ArmKind,
+    },
+    LoopCond,
+    LoopBody,
+    /// `for`循环更新计数器的部分，也是`continue`的目标
+    LoopStep,
+    /// `match`分支的守卫判断
+    Guard,
+    /// `&&`或`||`的右侧操作数，只在需要时求值
+    Operand,
+    /// 分支或循环结束后继续执行的部分
+    Continuation,
+    /// 按定点数的符号选择的`print!`或`title!`
+    Print,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ArmKind {
+    If,
+    Else,
+    /// `default`为不限定取值的分支，例如`..`
+    Match {
+        default: bool,
+    },
+}
+
+/// `match`表中的一个闭区间
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ArmRange {
+    pub start: i32,
+    pub end: i32,
+}
+
+impl ArmRange {
+    pub fn contains(&self, value: i32) -> bool {
+        (self.start..=self.end).contains(&value)
+    }
+}
+
+/// `Ir::Table`的分支是否满足要求：区间非空且互不重叠、按顺序排列，默认分支最多一个且排在最前
+pub(crate) fn is_table_sorted(arms: &[(Option<ArmRange>, Label)]) -> bool {
+    arms.iter()
+        .all(|(arm, _)| arm.is_none_or(|range| range.start <= range.end))
+        && arms.windows(2).all(|pair| match (pair[0].0, pair[1].0) {
+            (None, Some(_)) => true,
+            (Some(a), Some(b)) => a.end < b.start,
+            _ => false,
+        })
+}
+
+impl<'a> Label<'a> {
+    /// 用于排序，使编译结果与报告的顺序固定
+    pub(crate) fn order_key(&self) -> (u32, &'a str) {
+        match self {
+            Label::Named { name, .. } => (0, name),
+            Label::Anonymous(id) => (id + 1, ""),
+        }
+    }
 }
 
 impl<'a> LabelInfo<'a> {
@@ -48,6 +133,15 @@ impl<'a> LabelInfo<'a> {
         LabelInfo {
             label,
             insts: Vec::new(),
+            origin: None,
+            stmts: Vec::new(),
+        }
+    }
+
+    pub fn with_origin(label: Label<'a>, origin: LabelOrigin<'a>) -> Self {
+        LabelInfo {
+            origin: Some(origin),
+            ..Self::new(label)
         }
     }
 }
@@ -105,6 +199,40 @@ impl<'a> LabelMap<'a> {
     pub fn word_width(&self) -> u32 {
         self.word_width
     }
+
+    /// 所有导出函数的名称，按名称排序
+    pub fn exported_functions(&self) -> Vec<&'a str> {
+        let mut names: Vec<_> = self
+            .label_map
+            .keys()
+            .filter_map(|label| match label {
+                Label::Named { name, export: true } => Some(*name),
+                _ => None,
+            })
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn label(&self, label: &Label<'a>) -> Option<&LabelInfo<'a>> {
+        self.label_map.get(label)
+    }
+
+    /// 所有标签，顺序固定
+    pub fn labels(&self) -> Vec<&LabelInfo<'a>> {
+        let mut labels: Vec<_> = self.label_map.values().collect();
+        labels.sort_unstable_by_key(|info| info.label.order_key());
+        labels
+    }
+
+    /// 用于自定义的中间代码处理，修改后可用[`LabelMap::verify`]检查
+    pub fn labels_mut(&mut self) -> impl Iterator<Item = &mut LabelInfo<'a>> {
+        self.label_map.values_mut()
+    }
+
+    pub fn statics(&self) -> impl Iterator<Item = (&CacheTag<'a>, &i32)> {
+        self.static_map.iter()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -141,9 +269,10 @@ pub enum Ir<'a> {
         cond: CacheTag<'a>,
         then: Label<'a>,
     },
+    /// 跳转到`cond`所在区间的分支。区间互不重叠并按顺序排列，默认分支`None`排在最前
     Table {
         cond: CacheTag<'a>,
-        sorted_arms: Vec<(Option<i32>, Label<'a>)>,
+        sorted_arms: Vec<(Option<ArmRange>, Label<'a>)>,
     },
     Load {
         mem_offset: CacheTag<'a>,
@@ -159,12 +288,20 @@ pub enum Ir<'a> {
         min: i32,
     },
     CmdFmt {
-        prefix: String,
+        cmd: FmtCommand<'a>,
+        selector: String,
         args: Vec<FormatArgument<'a>>,
     },
     SimulationAbort,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum FmtCommand<'a> {
+    Tellraw,
+    /// `title`、`subtitle`或`actionbar`
+    Titleraw(&'a str),
+}
+
 #[derive(Clone, Debug)]
 pub enum FormatArgument<'a> {
     Text(&'a str),
@@ -180,7 +317,7 @@ pub enum BoolOprRhs<'a> {
     Constant(i32),
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Operator {
     Set,
     Add,
@@ -191,6 +328,12 @@ pub enum Operator {
     Max,
     Min,
     Swp,
+    /// 记分板没有位运算，编译为调用生成的辅助函数
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -222,6 +365,35 @@ impl Operator {
             Self::Swp => OperatorAsDisplay::Function("swap"),
             Self::Max => OperatorAsDisplay::Function("max"),
             Self::Min => OperatorAsDisplay::Function("min"),
+            Self::BitAnd => OperatorAsDisplay::BinaryOp("&="),
+            Self::BitOr => OperatorAsDisplay::BinaryOp("|="),
+            Self::BitXor => OperatorAsDisplay::BinaryOp("^="),
+            Self::Shl => OperatorAsDisplay::BinaryOp("<<="),
+            Self::Shr => OperatorAsDisplay::BinaryOp(">>="),
+        }
+    }
+}
+
+impl Display for ArmKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::If => write!(f, "if"),
+            Self::Else => write!(f, "else"),
+            Self::Match { default: false } => write!(f, "match"),
+            Self::Match { default: true } => write!(f, "match .."),
+        }
+    }
+}
+
+/// 与记分板的`matches`参数格式相同
+impl Display for ArmRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Self { start, end } = *self;
+        match (start, end) {
+            _ if start == end => write!(f, "{start}"),
+            (i32::MIN, _) => write!(f, "..{end}"),
+            (_, i32::MAX) => write!(f, "{start}.."),
+            _ => write!(f, "{start}..{end}"),
         }
     }
 }
@@ -0,0 +1,206 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use crate::ir::{ArmKind, Label, LabelKind, LabelMap};
+
+/// 仿真过程中各标签与指令的执行次数
+#[derive(Clone, Debug, Default)]
+pub struct Coverage<'a> {
+    hits: HashMap<Label<'a>, LabelHits>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct LabelHits {
+    calls: u64,
+    insts: Vec<u64>,
+}
+
+pub struct CoverageReport<'a> {
+    /// 按在源码中的位置排序
+    pub functions: Vec<FunctionCoverage<'a>>,
+    pub insts_hit: usize,
+    pub insts_total: usize,
+}
+
+pub struct FunctionCoverage<'a> {
+    pub name: &'a str,
+    pub calls: u64,
+    /// 按语句和分支顺序排序
+    pub arms: Vec<ArmCoverage<'a>>,
+    /// 每条语句开头的源码片段与执行次数
+    pub stmts: Vec<(&'a str, u64)>,
+}
+
+pub struct ArmCoverage<'a> {
+    pub group: u32,
+    pub index: u32,
+    pub arm: ArmKind,
+    pub hits: u64,
+    /// 分支开头的源码片段，`else`分支为空时没有
+    pub at: Option<&'a str>,
+}
+
+impl<'a> Coverage<'a> {
+    pub(super) fn enter(&mut self, label: Label<'a>) {
+        self.hits.entry(label).or_default().calls += 1;
+    }
+
+    pub(super) fn execute(&mut self, label: Label<'a>, index: usize) {
+        let insts = &mut self.hits.entry(label).or_default().insts;
+        if insts.len() <= index {
+            insts.resize(index + 1, 0);
+        }
+        insts[index] += 1;
+    }
+
+    /// 只统计由用户代码生成的标签
+    pub fn report(&self, label_map: &LabelMap<'a>) -> CoverageReport<'a> {
+        let mut functions: HashMap<&'a str, FunctionCoverage<'a>> = HashMap::new();
+        let (mut insts_hit, mut insts_total) = (0, 0);
+
+        for (label, info) in &label_map.label_map {
+            let Some(origin) = info.origin else {
+                continue;
+            };
+
+            let hits = self.hits.get(label);
+            let calls = hits.map_or(0, |h| h.calls);
+            insts_total += info.insts.len();
+            insts_hit += hits.map_or(0, |h| h.insts.iter().filter(|n| **n > 0).count());
+
+            let function = functions
+                .entry(origin.function)
+                .or_insert_with(|| FunctionCoverage {
+                    name: origin.function,
+                    calls: 0,
+                    arms: Vec::new(),
+                    stmts: Vec::new(),
+                });
+            // 同一个标签中的指令总是依次执行，语句的执行次数即标签的执行次数
+            function
+                .stmts
+                .extend(info.stmts.iter().map(|at| (*at, calls)));
+
+            match origin.kind {
+                LabelKind::Function => function.calls = calls,
+                LabelKind::Arm { group, index, arm } => function.arms.push(ArmCoverage {
+                    group,
+                    index,
+                    arm,
+                    hits: calls,
+                    at: origin.at,
+                }),
+                LabelKind::LoopCond
+                | LabelKind::LoopBody
+                | LabelKind::LoopStep
+                | LabelKind::Guard
+                | LabelKind::Operand
+                | LabelKind::Continuation
+                | LabelKind::Print => {}
+            }
+        }
+
+        let mut functions: Vec<_> = functions.into_values().collect();
+        // 函数名称指向源码，地址顺序即源码顺序
+        functions.sort_unstable_by_key(|f| f.name.as_ptr());
+        for function in &mut functions {
+            function
+                .arms
+                .sort_unstable_by_key(|arm| (arm.group, arm.index));
+        }
+
+        CoverageReport {
+            functions,
+            insts_hit,
+            insts_total,
+        }
+    }
+}
+
+impl CoverageReport<'_> {
+    pub fn functions_hit(&self) -> usize {
+        self.functions.iter().filter(|f| f.calls > 0).count()
+    }
+
+    pub fn arms(&self) -> impl Iterator<Item = (&str, &ArmCoverage<'_>)> {
+        self.functions
+            .iter()
+            .flat_map(|f| f.arms.iter().map(move |arm| (f.name, arm)))
+    }
+
+    /// 生成lcov格式的报告。分支记在分支开头的行上，找不到时记在函数的声明行上；
+    /// 一行有多条语句时取执行次数最多的一条
+    pub fn to_lcov(&self, path: &str, source: &str) -> String {
+        let mut output = String::new();
+        let lines: Vec<usize> = self
+            .functions
+            .iter()
+            .map(|f| line_of(source, f.name).unwrap_or(0))
+            .collect();
+
+        writeln!(output, "TN:\nSF:{path}").unwrap();
+        for (function, line) in self.functions.iter().zip(&lines) {
+            writeln!(output, "FN:{line},{}", function.name).unwrap();
+        }
+        for function in &self.functions {
+            writeln!(output, "FNDA:{},{}", function.calls, function.name).unwrap();
+        }
+        writeln!(
+            output,
+            "FNF:{}\nFNH:{}",
+            self.functions.len(),
+            self.functions_hit()
+        )
+        .unwrap();
+
+        let (mut arms_total, mut arms_hit) = (0, 0);
+        for (function, line) in self.functions.iter().zip(&lines) {
+            for arm in &function.arms {
+                let taken = if function.calls == 0 {
+                    "-".to_string()
+                } else {
+                    arm.hits.to_string()
+                };
+                let line = arm.at.and_then(|at| line_of(source, at)).unwrap_or(*line);
+                writeln!(output, "BRDA:{line},{},{},{taken}", arm.group, arm.index).unwrap();
+                arms_total += 1;
+                arms_hit += (arm.hits > 0) as usize;
+            }
+        }
+        writeln!(output, "BRF:{arms_total}\nBRH:{arms_hit}").unwrap();
+
+        let mut line_hits = BTreeMap::new();
+        for (function, line) in self.functions.iter().zip(&lines) {
+            let stmts = function
+                .stmts
+                .iter()
+                .filter_map(|(at, hits)| Some((line_of(source, at)?, *hits)));
+            for (line, hits) in [(*line, function.calls)].into_iter().chain(stmts) {
+                let entry = line_hits.entry(line).or_insert(0);
+                *entry = (*entry).max(hits);
+            }
+        }
+        for (line, hits) in &line_hits {
+            writeln!(output, "DA:{line},{hits}").unwrap();
+        }
+        writeln!(
+            output,
+            "LF:{}\nLH:{}\nend_of_record",
+            line_hits.len(),
+            line_hits.values().filter(|hits| **hits > 0).count()
+        )
+        .unwrap();
+
+        output
+    }
+}
+
+/// `fragment`必须是`source`的切片
+fn line_of(source: &str, fragment: &str) -> Option<usize> {
+    let offset = (fragment.as_ptr() as usize).checked_sub(source.as_ptr() as usize)?;
+    source
+        .get(..offset)
+        .map(|before| before.matches('\n').count() + 1)
+}
@@ -5,56 +5,201 @@ use std::{
 };
 
 use anyhow::{anyhow, Result};
-use rand::{rngs::ThreadRng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
     atoi::{calculate_arithmetical_bin_expr, calculate_bool_bin_expr},
-    ir::{FormatArgument, OperatorAsDisplay},
+    ir::{is_table_sorted, FormatArgument, OperatorAsDisplay, MCSH_INIT_FUNC},
 };
 
-use super::{to_display, BoolOprRhs, CacheTag, Ir, Label, LabelMap, Operator};
+use self::{
+    coverage::{Coverage, CoverageReport},
+    trace::{MemoryAccess, MemoryAccessKind, RegisterWrite, TraceEvent},
+    world::{split_command, ChatMessage, MessageKind, World},
+};
+
+use super::{
+    compile::{
+        bitwise::{helper_steps, is_bitwise, HelperStep, REG_BIT_LHS, REG_BIT_RHS},
+        compile_cache_tag,
+    },
+    to_display, BoolOprRhs, CacheTag, FmtCommand, Ir, Label, LabelMap, Operator,
+};
+
+pub mod coverage;
+pub mod trace;
+pub mod world;
+
+const REG_RETURNED_VALUE: CacheTag = CacheTag::StaticBuiltin("ReturnedValue");
 
 #[must_use]
-pub struct SimulateResult {
+pub struct SimulateResult<'a> {
     pub result: Result<i32>,
     pub log: String,
+    /// 逐条指令的执行记录，仅在开启`SimulateOptions::trace`时记录
+    pub events: Vec<TraceEvent<'a>>,
+    /// 玩家收到的消息
+    pub messages: Vec<ChatMessage>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SimulateOptions {
+    /// 随机数种子，为空时使用系统熵源
+    pub seed: Option<u64>,
+    /// 覆盖导出静态变量的初始值
+    pub statics: Vec<(String, i32)>,
+    /// 是否记录结构化的执行事件
+    pub trace: bool,
+    /// 初始的世界，选择器将在其中解析
+    pub world: World,
 }
 
-struct SimulateMachine<'a> {
+pub struct SimulateMachine<'a> {
     label_map: &'a LabelMap<'a>,
     memory: Vec<Option<i32>>,
     registers: HashMap<CacheTag<'a>, i32>,
-    rest_ir: Vec<&'a Ir<'a>>,
+    static_overrides: Vec<(CacheTag<'a>, i32)>,
+    rest_ir: Vec<(Label<'a>, usize, &'a Ir<'a>)>,
     log: String,
-    rng: ThreadRng,
+    rng: StdRng,
+    trace: bool,
+    current_event: Option<TraceEvent<'a>>,
+    events: Vec<TraceEvent<'a>>,
+    world: World,
+    initial_world: World,
+    messages: Vec<ChatMessage>,
+    coverage: Coverage<'a>,
 }
 
 impl<'a> SimulateMachine<'a> {
-    fn initialize(label_map: &'a LabelMap<'a>) -> Self {
-        SimulateMachine {
+    fn initialize(label_map: &'a LabelMap<'a>, options: &SimulateOptions) -> Result<Self> {
+        let static_overrides = options
+            .statics
+            .iter()
+            .map(|(name, value)| {
+                label_map
+                    .static_map
+                    .keys()
+                    .find(|ct| matches!(ct, CacheTag::StaticExport(n) if n == name))
+                    .map(|ct| (*ct, *value))
+                    .ok_or_else(|| anyhow!("exported static `{name}` is not defined"))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut machine = SimulateMachine {
             label_map,
             memory: vec![None; (label_map.mem_size * label_map.word_width) as _],
             registers: HashMap::new(),
+            static_overrides,
             rest_ir: Vec::new(),
             log: String::new(),
-            rng: rand::thread_rng(),
-        }
+            rng: match options.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            trace: options.trace,
+            current_event: None,
+            events: Vec::new(),
+            world: options.world.clone(),
+            initial_world: options.world.clone(),
+            messages: Vec::new(),
+            coverage: Coverage::default(),
+        };
+        machine.reset();
+        Ok(machine)
     }
 
-    fn run(&mut self, label: &Label<'a>) -> Result<()> {
+    /// 重置内存与寄存器，相当于在游戏中运行`mcsh_init`
+    pub fn reset(&mut self) {
         self.memory.fill(None);
         self.registers.clear();
-        self.rest_ir.clear();
-        self.log.clear();
 
         for (cache_tag, value) in self.label_map.static_map.iter() {
             self.registers.insert(*cache_tag, *value);
         }
+        self.registers.extend(self.static_overrides.iter().copied());
+    }
+
+    /// 重置环境并恢复初始的世界，覆盖率记录会保留
+    pub fn restart(&mut self) {
+        self.world = self.initial_world.clone();
+        self.reset();
+    }
+
+    /// 在当前状态下运行导出函数，静态变量与内存会保留到下一次调用
+    pub fn call_pub(&mut self, fn_name: &str) -> SimulateResult<'a> {
+        if fn_name == MCSH_INIT_FUNC {
+            self.reset();
+            return SimulateResult {
+                result: Ok(0),
+                log: "environment reset\n".into(),
+                events: Vec::new(),
+                messages: Vec::new(),
+            };
+        }
+
+        let label =
+            self.label_map.label_map.keys().find(
+                |label| matches!(label, Label::Named { name, export: true } if *name == fn_name),
+            );
+
+        let r = match label {
+            Some(label) => self
+                .run(label)
+                .map(|()| self.read_value(&REG_RETURNED_VALUE).unwrap()),
+            None => Err(anyhow!("exported function `{fn_name}` is not defined")),
+        };
+
+        SimulateResult {
+            result: r,
+            log: std::mem::take(&mut self.log),
+            events: std::mem::take(&mut self.events),
+            messages: std::mem::take(&mut self.messages),
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// 到目前为止所有调用的覆盖率
+    pub fn coverage_report(&self) -> CoverageReport<'a> {
+        self.coverage.report(self.label_map)
+    }
+
+    /// 所有导出静态变量的当前值，按名称排序
+    pub fn exported_statics(&self) -> Vec<(&'a str, Option<i32>)> {
+        let mut statics: Vec<_> = self
+            .label_map
+            .static_map
+            .keys()
+            .filter_map(|ct| match ct {
+                CacheTag::StaticExport(name) => Some((*name, self.registers.get(ct).copied())),
+                _ => None,
+            })
+            .collect();
+        statics.sort_unstable_by_key(|(name, _)| *name);
+        statics
+    }
+
+    fn run(&mut self, label: &Label<'a>) -> Result<()> {
+        self.rest_ir.clear();
+        self.log.clear();
+        self.events.clear();
+        self.messages.clear();
 
         self.call(label)?;
 
-        while let Some(inst) = self.rest_ir.pop() {
+        while let Some((label, index, inst)) = self.rest_ir.pop() {
+            self.coverage.execute(label, index);
+            if self.trace {
+                self.current_event = Some(TraceEvent::new(label, index, inst));
+            }
             let r = self.eval(inst);
+            if let Some(event) = self.current_event.take() {
+                self.events.push(event);
+            }
+
             if let Err(err) = &r {
                 write!(
                     self.log,
@@ -86,24 +231,68 @@ impl<'a> SimulateMachine<'a> {
         }
     }
 
-    fn get_value_mut(&mut self, ct: &CacheTag<'a>) -> Result<&mut i32> {
-        match self.registers.get_mut(ct) {
-            Some(v) => Ok(v),
-            None => Err(anyhow!("trying to operate `{ct:?}` before initialize")),
+    /// 读取寄存器并记录到当前事件
+    fn read(&mut self, ct: &CacheTag<'a>) -> Result<i32> {
+        let value = self.read_value(ct)?;
+        if let Some(event) = &mut self.current_event {
+            event.reads.push((*ct, value));
         }
+        Ok(value)
+    }
+
+    /// 写入寄存器（`None`表示清除）并记录到当前事件
+    fn write(&mut self, ct: &CacheTag<'a>, value: Option<i32>) {
+        let old = match value {
+            Some(value) => self.registers.insert(*ct, value),
+            None => self.registers.remove(ct),
+        };
+
+        if let Some(event) = &mut self.current_event {
+            event.writes.push(RegisterWrite {
+                cache_tag: *ct,
+                old,
+                new: value,
+            });
+        }
+    }
+
+    /// 修改一个已初始化的寄存器
+    fn update(&mut self, ct: &CacheTag<'a>, f: impl FnOnce(i32) -> Result<i32>) -> Result<i32> {
+        let Some(&old) = self.registers.get(ct) else {
+            return Err(anyhow!("trying to operate `{ct:?}` before initialize"));
+        };
+        let new = f(old)?;
+        self.write(ct, Some(new));
+        Ok(old)
     }
 
     fn call(&mut self, label: &Label<'a>) -> Result<()> {
-        let Some(info) = self.label_map.label_map.get(label) else {
+        let Some((label, info)) = self.label_map.label_map.get_key_value(label) else {
             return Err(anyhow!("cannot call `{label:?}` as it is not defined"));
         };
-
-        self.rest_ir.extend(info.insts.iter().rev());
+        self.coverage.enter(*label);
+
+        self.rest_ir.extend(
+            info.insts
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(index, ir)| (*label, index, ir)),
+        );
         Ok(())
     }
 
-    fn get_mem_slice(&self, mem_offset: CacheTag, size: u32) -> Result<Range<usize>> {
-        let pointer = self.read_value(&mem_offset)?;
+    fn access_memory(&mut self, kind: MemoryAccessKind, range: &Range<usize>) {
+        if let Some(event) = &mut self.current_event {
+            event.memory = Some(MemoryAccess {
+                kind,
+                range: range.clone(),
+            });
+        }
+    }
+
+    fn get_mem_slice(&mut self, mem_offset: CacheTag<'a>, size: u32) -> Result<Range<usize>> {
+        let pointer = self.read(&mem_offset)?;
         if pointer < 0 {
             return Err(anyhow!(
                 "attempt to read an invalid pointer with the value 0, \
@@ -112,7 +301,7 @@ impl<'a> SimulateMachine<'a> {
         }
 
         let word_width = self.label_map.word_width as usize;
-        let start = self.read_value(&mem_offset)? as usize * word_width;
+        let start = pointer as usize * word_width;
         let end = start + size as usize * word_width;
 
         if self.memory.get(start..end).is_none() {
@@ -126,7 +315,85 @@ impl<'a> SimulateMachine<'a> {
         Ok(start..end)
     }
 
-    fn eval(&mut self, ir: &Ir<'a>) -> Result<()> {
+    /// 执行原始命令，返回命令是否被仿真支持
+    fn run_raw(&mut self, cmd: &str) -> Result<bool> {
+        let parts = split_command(cmd.trim_start_matches('/'));
+
+        match &*parts {
+            ["tag", selector, action @ ("add" | "remove"), tag] => {
+                for index in self.world.select(selector, &mut self.rng)? {
+                    let tags = &mut self.world.entities[index].tags;
+                    if *action == "add" {
+                        tags.insert(tag.to_string());
+                    } else {
+                        tags.remove(*tag);
+                    }
+                }
+            }
+
+            ["scoreboard", "players", action @ ("set" | "add" | "remove"), target, objective, value] =>
+            {
+                let value: i32 = value.parse()?;
+                let apply = |old: i32| match *action {
+                    "set" => value,
+                    "add" => old.wrapping_add(value),
+                    _ => old.wrapping_sub(value),
+                };
+
+                if *target == "MCSH" {
+                    let ct = self
+                        .registers
+                        .keys()
+                        .find(|ct| compile_cache_tag(**ct).to_string() == *objective)
+                        .copied();
+                    match ct {
+                        Some(ct) => {
+                            self.update(&ct, |old| Ok(apply(old)))?;
+                        }
+                        None => return Ok(false),
+                    }
+                } else {
+                    for index in self.world.select(target, &mut self.rng)? {
+                        let scores = &mut self.world.entities[index].scores;
+                        let score = scores.entry(objective.to_string()).or_default();
+                        *score = apply(*score);
+                    }
+                }
+            }
+
+            ["say", words @ ..] => {
+                let speaker = match self.world.executor {
+                    Some(index) => self.world.entities[index].name.clone(),
+                    None => "Server".into(),
+                };
+                // 与游戏一致，消息中的选择器显示为实体名称
+                let words = words
+                    .iter()
+                    .map(|word| {
+                        if word.starts_with('@') {
+                            self.world.display_names(word, &mut self.rng)
+                        } else {
+                            Ok(word.to_string())
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let text = format!("[{speaker}] {}", words.join(" "));
+                for entity in self.world.entities.iter().filter(|e| e.is_player()) {
+                    self.messages.push(ChatMessage {
+                        recipient: entity.name.clone(),
+                        kind: MessageKind::Chat,
+                        text: text.clone(),
+                    });
+                }
+            }
+
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn eval(&mut self, ir: &'a Ir<'a>) -> Result<()> {
         macro_rules! log {
             ($($tt:tt)*) => {
                 writeln!(self.log, $($tt)*).unwrap()
@@ -136,19 +403,18 @@ impl<'a> SimulateMachine<'a> {
         match ir {
             Ir::Assign { dst, value } => {
                 let lhs_old = self.display_value(dst);
-                self.registers.insert(*dst, *value);
+                self.write(dst, Some(*value));
                 log!("{dst:?} = {value} ({lhs_old} -> {value})");
             }
 
             Ir::BoolOperation { dst, lhs, opr, rhs } => {
                 let rhs_val = match rhs {
-                    BoolOprRhs::CacheTag(ct) => self.read_value(ct)?,
+                    BoolOprRhs::CacheTag(ct) => self.read(ct)?,
                     BoolOprRhs::Constant(val) => *val,
                 };
-                let lhs_val = self.read_value(lhs)?;
+                let lhs_val = self.read(lhs)?;
 
-                self.registers
-                    .insert(*dst, calculate_bool_bin_expr(lhs_val, rhs_val, *opr));
+                self.write(dst, Some(calculate_bool_bin_expr(lhs_val, rhs_val, *opr)));
 
                 log!("{dst:?} = {lhs:?} {opr} {rhs:?} (lhs = {lhs_val}, rhs = {rhs_val})");
             }
@@ -158,8 +424,12 @@ impl<'a> SimulateMachine<'a> {
                 log!("call {label:?}");
             }
 
-            Ir::CmdRaw(name) => {
-                log!("raw function `{name}`");
+            Ir::CmdRaw(cmd) => {
+                if self.run_raw(cmd)? {
+                    log!("raw command `{cmd}`");
+                } else {
+                    log!("raw command `{cmd}` (ignored)");
+                }
             }
 
             Ir::Cond {
@@ -167,7 +437,7 @@ impl<'a> SimulateMachine<'a> {
                 cond,
                 then,
             } => {
-                let mut cond_val = self.read_value(cond)? != 0;
+                let mut cond_val = self.read(cond)? != 0;
 
                 if !positive {
                     cond_val = !cond_val;
@@ -184,20 +454,17 @@ impl<'a> SimulateMachine<'a> {
             }
 
             Ir::Increase { dst, value } => {
-                *self.get_value_mut(dst)? += value;
+                self.update(dst, |old| Ok(old.wrapping_add(*value)))?;
                 log!("{dst:?} += {value}");
             }
 
             Ir::Load { mem_offset, size } => {
                 let range = self.get_mem_slice(*mem_offset, *size)?;
-                let mem = &self.memory[range.clone()];
+                self.access_memory(MemoryAccessKind::Load, &range);
 
-                for (index, src) in mem.iter().enumerate() {
-                    let ct = CacheTag::Regular(index as _);
-                    match *src {
-                        Some(val) => self.registers.insert(ct, val),
-                        None => self.registers.remove(&ct),
-                    };
+                for index in 0..range.len() {
+                    let src = self.memory[range.start + index];
+                    self.write(&CacheTag::Regular(index as _), src);
                 }
 
                 log!(
@@ -208,12 +475,11 @@ impl<'a> SimulateMachine<'a> {
             }
 
             Ir::Not { src, dst } => {
-                let val = self.read_value(src)?;
-                let dst_value = self.get_value_mut(dst)?;
-                let old = *dst_value;
-                *dst_value = if val == 0 { 1 } else { 0 };
+                let val = self.read(src)?;
+                let new = if val == 0 { 1 } else { 0 };
+                let old = self.update(dst, |_| Ok(new))?;
 
-                log!("not {dst:?} ({old} -> {val})");
+                log!("not {dst:?} ({old} -> {new})");
             }
 
             Ir::Operation {
@@ -221,24 +487,26 @@ impl<'a> SimulateMachine<'a> {
                 opr: Operator::Set,
                 src,
             } => {
-                let rhs = self.read_value(src)?;
+                let rhs = self.read(src)?;
                 let lhs_old = self.display_value(dst);
                 log!("{dst:?} = {src:?} ({lhs_old} -> {rhs})");
-                self.registers.insert(*dst, rhs);
+                self.write(dst, Some(rhs));
             }
 
             Ir::Operation { dst, opr, src } => {
-                let rhs = self.read_value(src)?;
-                let lhs = self.get_value_mut(dst)?;
-                let lhs_value = *lhs;
-
-                match opr {
+                let rhs = self.read(src)?;
+                let lhs_value = match opr {
                     Operator::Swp => {
-                        *lhs = rhs;
-                        *self.get_value_mut(src).unwrap() = lhs_value;
+                        let lhs_value = self.update(dst, |_| Ok(rhs))?;
+                        self.write(src, Some(lhs_value));
+                        lhs_value
                     }
-                    _ => *lhs = calculate_arithmetical_bin_expr(*lhs, rhs, *opr),
-                }
+                    // 位运算按编译出的辅助函数逐条执行
+                    _ if is_bitwise(*opr) => {
+                        self.update(dst, |lhs| run_bitwise_helper(*opr, lhs, rhs))?
+                    }
+                    _ => self.update(dst, |lhs| calculate_arithmetical_bin_expr(lhs, rhs, *opr))?,
+                };
 
                 match opr.as_display() {
                     OperatorAsDisplay::BinaryOp(binop) => {
@@ -254,17 +522,21 @@ impl<'a> SimulateMachine<'a> {
                 let (max, min) = (*max, *min);
                 let value = self.rng.gen_range(min..=max);
                 let lhs_old = self.display_value(dst);
-                self.registers.insert(*dst, value);
+                self.write(dst, Some(value));
                 log!("{dst:?} = random {min}..{max} ({lhs_old} -> {value})");
             }
 
             Ir::Store { mem_offset, size } => {
                 let range = self.get_mem_slice(*mem_offset, *size)?;
-                let mem = &mut self.memory[range.clone()];
+                self.access_memory(MemoryAccessKind::Store, &range);
 
-                for (index, dst) in mem.iter_mut().enumerate() {
+                for index in 0..range.len() {
                     let ct = CacheTag::Regular(index as _);
-                    *dst = self.registers.get(&ct).copied();
+                    let value = self.registers.get(&ct).copied();
+                    if let (Some(event), Some(value)) = (&mut self.current_event, value) {
+                        event.reads.push((ct, value));
+                    }
+                    self.memory[range.start + index] = value;
                 }
 
                 log!(
@@ -280,30 +552,24 @@ impl<'a> SimulateMachine<'a> {
             }
 
             Ir::Table { cond, sorted_arms } => {
-                let cond_val = self.read_value(cond)?;
+                let cond_val = self.read(cond)?;
 
-                if sorted_arms.windows(2).any(|arr| arr[0].0 >= arr[1].0) {
+                if !is_table_sorted(sorted_arms) {
                     return Err(anyhow!(
                         "table arms are not sorted or duplicated arms exist"
                     ));
                 }
 
-                let mut default_arm = None;
-                for (arm, label) in sorted_arms.iter().rev() {
-                    if let None = arm {
-                        if default_arm.replace(label).is_some() {
-                            return Err(anyhow!("found duplicated definition of default arm"));
-                        }
-                    } else {
-                        break;
-                    }
-                }
+                let (default_arm, range_arms) = match sorted_arms.split_first() {
+                    Some(((None, label), rest)) => (Some(label), rest),
+                    _ => (None, &sorted_arms[..]),
+                };
 
-                let int_arms = &sorted_arms[if default_arm.is_some() { 1 } else { 0 }..];
-                match int_arms
-                    .binary_search_by_key(&cond_val, |(x, _)| (*x).unwrap())
-                    .ok()
-                    .map(|index| &int_arms[index].1)
+                let index = range_arms.partition_point(|(arm, _)| arm.unwrap().end < cond_val);
+                match range_arms
+                    .get(index)
+                    .filter(|(arm, _)| arm.unwrap().contains(cond_val))
+                    .map(|(_, label)| label)
                     .or(default_arm)
                 {
                     Some(label) => {
@@ -316,47 +582,88 @@ impl<'a> SimulateMachine<'a> {
                 };
             }
 
-            Ir::CmdFmt { args, prefix } => {
+            Ir::CmdFmt {
+                cmd,
+                selector,
+                args,
+            } => {
                 let mut string = String::new();
                 for arg in args {
                     match arg {
                         FormatArgument::CacheTag(ct) => {
-                            write!(string, "{}", self.read_value(ct)?).unwrap()
+                            write!(string, "{}", self.read(ct)?).unwrap()
                         }
                         FormatArgument::ConstInt(int) => write!(string, "{int}").unwrap(),
-                        FormatArgument::Selector(sel) => write!(string, "(SEL: {sel})").unwrap(),
-                        FormatArgument::Style(_) => {}
+                        FormatArgument::Selector(sel) => {
+                            string += &self.world.display_names(sel, &mut self.rng)?
+                        }
+                        FormatArgument::Style(style) => {
+                            write!(string, "§{}", style.code()).unwrap()
+                        }
                         FormatArgument::Text(t) => string.push_str(t),
                     }
                 }
-                log!("run formatted `{prefix}` `{string}`")
+
+                let kind = match cmd {
+                    FmtCommand::Tellraw => MessageKind::Chat,
+                    FmtCommand::Titleraw("title") => MessageKind::Title,
+                    FmtCommand::Titleraw("subtitle") => MessageKind::Subtitle,
+                    FmtCommand::Titleraw(_) => MessageKind::Actionbar,
+                };
+
+                let recipients = self.world.select(selector, &mut self.rng)?;
+                let mut names = Vec::new();
+                for index in recipients {
+                    let entity = &self.world.entities[index];
+                    // 只有玩家能收到消息
+                    if entity.is_player() {
+                        names.push(entity.name.clone());
+                        self.messages.push(ChatMessage {
+                            recipient: entity.name.clone(),
+                            kind,
+                            text: string.clone(),
+                        });
+                    }
+                }
+                log!(
+                    "run formatted {kind:?} `{selector}` `{string}` (to [{}])",
+                    names.join(", ")
+                )
             }
         }
         Ok(())
     }
 }
 
-impl<'a> LabelMap<'a> {
-    pub fn simulate_pub(&self, fn_name: &str) -> SimulateResult {
-        self.simulate(&Label::Named {
-            name: fn_name,
-            export: true,
-        })
-    }
+/// 在独立的记分板上执行位运算辅助函数的命令，返回`Bit_Lhs`的结果
+fn run_bitwise_helper(opr: Operator, lhs: i32, rhs: i32) -> Result<i32> {
+    let mut regs = HashMap::from([(REG_BIT_LHS, lhs), (REG_BIT_RHS, rhs)]);
+    for step in helper_steps(opr) {
+        let (dst, opr, src) = match step {
+            HelperStep::Set(reg, value) => {
+                regs.insert(reg, value);
+                continue;
+            }
+            HelperStep::Operation(dst, opr, src) => (dst, opr, src),
+            HelperStep::IfRhsAtLeast(n, dst, opr, src) => {
+                if regs[REG_BIT_RHS] < n {
+                    continue;
+                }
+                (dst, opr, src)
+            }
+        };
 
-    pub fn simulate(&self, entry_fn: &Label) -> SimulateResult {
-        let mut machine = SimulateMachine::initialize(self);
-        let r = machine.run(entry_fn).map(|()| {
-            machine
-                .registers
-                .get(&CacheTag::StaticBuiltin("ReturnedValue"))
-                .copied()
-                .unwrap()
-        });
+        let value = match opr {
+            Operator::Set => regs[src],
+            _ => calculate_arithmetical_bin_expr(regs[dst], regs[src], opr)?,
+        };
+        regs.insert(dst, value);
+    }
+    Ok(regs[REG_BIT_LHS])
+}
 
-        SimulateResult {
-            result: r,
-            log: machine.log,
-        }
+impl LabelMap<'_> {
+    pub fn simulator(&self, options: &SimulateOptions) -> Result<SimulateMachine<'_>> {
+        SimulateMachine::initialize(self, options)
     }
 }
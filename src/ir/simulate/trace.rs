@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+use serde_json::{json, Value};
+
+use crate::ir::{
+    compile::{compile_cache_tag, compile_label},
+    BoolOprRhs, CacheTag, FmtCommand, FormatArgument, Ir, Label,
+};
+
+/// 一条IR指令的执行记录
+#[derive(Clone, Debug)]
+pub struct TraceEvent<'a> {
+    pub label: Label<'a>,
+    /// 指令在标签中的序号
+    pub index: usize,
+    pub ir: &'a Ir<'a>,
+    /// 读取的寄存器及其值
+    pub reads: Vec<(CacheTag<'a>, i32)>,
+    pub writes: Vec<RegisterWrite<'a>>,
+    pub memory: Option<MemoryAccess>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RegisterWrite<'a> {
+    pub cache_tag: CacheTag<'a>,
+    /// `None`表示该寄存器未初始化
+    pub old: Option<i32>,
+    pub new: Option<i32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryAccess {
+    pub kind: MemoryAccessKind,
+    /// 访问的内存单元范围
+    pub range: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MemoryAccessKind {
+    Load,
+    Store,
+}
+
+impl<'a> TraceEvent<'a> {
+    pub(super) fn new(label: Label<'a>, index: usize, ir: &'a Ir<'a>) -> Self {
+        TraceEvent {
+            label,
+            index,
+            ir,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            memory: None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let reads: Vec<_> = self
+            .reads
+            .iter()
+            .map(|(ct, value)| json!({ "reg": cache_tag_json(ct), "value": value }))
+            .collect();
+
+        let writes: Vec<_> = self
+            .writes
+            .iter()
+            .map(|w| {
+                json!({
+                    "reg": cache_tag_json(&w.cache_tag),
+                    "old": w.old,
+                    "new": w.new,
+                })
+            })
+            .collect();
+
+        let memory = self.memory.as_ref().map(|m| {
+            json!({
+                "kind": match m.kind {
+                    MemoryAccessKind::Load => "load",
+                    MemoryAccessKind::Store => "store",
+                },
+                "start": m.range.start,
+                "end": m.range.end,
+            })
+        });
+
+        json!({
+            "event": "ir",
+            "label": label_json(&self.label),
+            "index": self.index,
+            "inst": ir_json(self.ir),
+            "reads": reads,
+            "writes": writes,
+            "memory": memory,
+        })
+    }
+}
+
+pub fn label_json(label: &Label) -> Value {
+    compile_label(label, false).to_string().into()
+}
+
+fn cache_tag_json(ct: &CacheTag) -> Value {
+    compile_cache_tag(*ct).to_string().into()
+}
+
+pub fn ir_json(ir: &Ir) -> Value {
+    match ir {
+        Ir::Assign { dst, value } => json!({
+            "op": "assign",
+            "dst": cache_tag_json(dst),
+            "value": value,
+        }),
+        Ir::Call { label } => json!({ "op": "call", "label": label_json(label) }),
+        Ir::CmdRaw(cmd) => json!({ "op": "cmd_raw", "cmd": cmd }),
+        Ir::Increase { dst, value } => json!({
+            "op": "increase",
+            "dst": cache_tag_json(dst),
+            "value": value,
+        }),
+        Ir::Operation { dst, opr, src } => json!({
+            "op": "operation",
+            "dst": cache_tag_json(dst),
+            "opr": format!("{opr:?}"),
+            "src": cache_tag_json(src),
+        }),
+        Ir::BoolOperation { dst, lhs, opr, rhs } => json!({
+            "op": "bool_operation",
+            "dst": cache_tag_json(dst),
+            "lhs": cache_tag_json(lhs),
+            "opr": opr.to_string(),
+            "rhs": match rhs {
+                BoolOprRhs::CacheTag(ct) => cache_tag_json(ct),
+                BoolOprRhs::Constant(c) => json!(c),
+            },
+        }),
+        Ir::Not { src, dst } => json!({
+            "op": "not",
+            "dst": cache_tag_json(dst),
+            "src": cache_tag_json(src),
+        }),
+        Ir::Cond {
+            positive,
+            cond,
+            then,
+        } => json!({
+            "op": "cond",
+            "positive": positive,
+            "cond": cache_tag_json(cond),
+            "then": label_json(then),
+        }),
+        Ir::Table { cond, sorted_arms } => json!({
+            "op": "table",
+            "cond": cache_tag_json(cond),
+            "arms": sorted_arms
+                .iter()
+                .map(|(arm, label)| {
+                    json!({
+                        "range": arm.map(|range| [range.start, range.end]),
+                        "label": label_json(label),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        }),
+        Ir::Load { mem_offset, size } => json!({
+            "op": "load",
+            "mem_offset": cache_tag_json(mem_offset),
+            "size": size,
+        }),
+        Ir::Store { mem_offset, size } => json!({
+            "op": "store",
+            "mem_offset": cache_tag_json(mem_offset),
+            "size": size,
+        }),
+        Ir::Random { dst, max, min } => json!({
+            "op": "random",
+            "dst": cache_tag_json(dst),
+            "min": min,
+            "max": max,
+        }),
+        Ir::CmdFmt {
+            cmd,
+            selector,
+            args,
+        } => json!({
+            "op": "cmd_fmt",
+            "cmd": match cmd {
+                FmtCommand::Tellraw => "tellraw",
+                FmtCommand::Titleraw(position) => position,
+            },
+            "selector": selector,
+            "args": args.iter().map(format_argument_json).collect::<Vec<_>>(),
+        }),
+        Ir::SimulationAbort => json!({ "op": "simulation_abort" }),
+    }
+}
+
+fn format_argument_json(arg: &FormatArgument) -> Value {
+    match arg {
+        FormatArgument::Text(t) => json!({ "text": t }),
+        FormatArgument::CacheTag(ct) => json!({ "score": cache_tag_json(ct) }),
+        FormatArgument::ConstInt(int) => json!({ "int": int }),
+        FormatArgument::Style(style) => json!({ "style": style.code() }),
+        FormatArgument::Selector(sel) => json!({ "selector": sel }),
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Result};
+use rand::{rngs::StdRng, seq::SliceRandom};
+use serde_json::Value;
+
+/// 仿真世界中的实体
+#[derive(Clone, Debug)]
+pub struct Entity {
+    pub name: String,
+    /// 实体类型，不含`minecraft:`前缀
+    pub kind: String,
+    pub tags: BTreeSet<String>,
+    pub scores: BTreeMap<String, i32>,
+}
+
+/// 轻量的世界模型，用于在仿真中解析选择器
+#[derive(Clone, Debug)]
+pub struct World {
+    pub entities: Vec<Entity>,
+    /// 命令执行者（`@s`）在`entities`中的下标
+    pub executor: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MessageKind {
+    Chat,
+    Title,
+    Subtitle,
+    Actionbar,
+}
+
+/// 某位玩家收到的一条消息
+#[derive(Clone, Debug)]
+pub struct ChatMessage {
+    pub recipient: String,
+    pub kind: MessageKind,
+    /// 消息原文，样式以`§`格式代码表示
+    pub text: String,
+}
+
+impl Entity {
+    pub fn player(name: &str) -> Self {
+        Entity {
+            name: name.into(),
+            kind: "player".into(),
+            tags: BTreeSet::new(),
+            scores: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_player(&self) -> bool {
+        self.kind == "player"
+    }
+}
+
+impl Default for World {
+    /// 只有一名执行命令的玩家`Steve`
+    fn default() -> Self {
+        World {
+            entities: vec![Entity::player("Steve")],
+            executor: Some(0),
+        }
+    }
+}
+
+impl World {
+    /// 从JSON读取世界，格式如下：
+    ///
+    /// ```json
+    /// {
+    ///     "executor": "Steve",
+    ///     "entities": [
+    ///         { "name": "Steve", "type": "player", "tags": ["admin"], "scores": { "coins": 10 } },
+    ///         { "name": "Zombie", "type": "zombie" }
+    ///     ]
+    /// }
+    /// ```
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let mut entities = Vec::new();
+        for entity in value["entities"].as_array().into_iter().flatten() {
+            let Some(name) = entity["name"].as_str() else {
+                return Err(anyhow!("every entity in the world must have a `name`"));
+            };
+
+            let kind = entity["type"].as_str().unwrap_or("player");
+            let tags = entity["tags"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|tag| {
+                    tag.as_str()
+                        .map(String::from)
+                        .ok_or_else(|| anyhow!("tags of entity `{name}` must be strings"))
+                })
+                .collect::<Result<_>>()?;
+            let scores = entity["scores"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(objective, score)| {
+                    score
+                        .as_i64()
+                        .and_then(|s| i32::try_from(s).ok())
+                        .map(|s| (objective.clone(), s))
+                        .ok_or_else(|| {
+                            anyhow!("score `{objective}` of entity `{name}` must be an i32")
+                        })
+                })
+                .collect::<Result<_>>()?;
+
+            entities.push(Entity {
+                name: name.into(),
+                kind: strip_namespace(kind).into(),
+                tags,
+                scores,
+            });
+        }
+
+        let executor = match &value["executor"] {
+            Value::Null => entities.iter().position(Entity::is_player),
+            Value::String(name) => Some(
+                entities
+                    .iter()
+                    .position(|e| e.name == *name)
+                    .ok_or_else(|| anyhow!("executor `{name}` is not an entity of the world"))?,
+            ),
+            _ => return Err(anyhow!("`executor` must be the name of an entity")),
+        };
+
+        Ok(World { entities, executor })
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.entities.iter().position(|e| e.name == name)
+    }
+
+    /// 解析选择器（或实体名称），返回匹配实体的下标
+    pub fn select(&self, selector: &str, rng: &mut StdRng) -> Result<Vec<usize>> {
+        let selector: String = strip_whitespace(selector);
+        let Some(rest) = selector.strip_prefix('@') else {
+            return Ok(self.find(&unquote(&selector)).into_iter().collect());
+        };
+
+        let (variable, args) = match rest.split_once('[') {
+            Some((variable, args)) => match args.strip_suffix(']') {
+                Some(args) => (variable, split_top_level(args)),
+                None => return Err(anyhow!("unclosed selector `{selector}`")),
+            },
+            None => (rest, Vec::new()),
+        };
+
+        let mut filter = SelectorFilter::default();
+        for arg in args.iter().filter(|a| !a.is_empty()) {
+            let Some((key, value)) = arg.split_once('=') else {
+                return Err(anyhow!("invalid selector argument `{arg}`"));
+            };
+            filter.push(key, value)?;
+        }
+
+        let candidates: Vec<usize> = match variable {
+            "s" => self.executor.into_iter().collect(),
+            "a" | "p" | "r" => (0..self.entities.len())
+                .filter(|&i| self.entities[i].is_player())
+                .collect(),
+            "e" => (0..self.entities.len()).collect(),
+            _ => return Err(anyhow!("unknown selector variable `@{variable}`")),
+        };
+
+        let mut selected: Vec<usize> = candidates
+            .into_iter()
+            .filter(|&i| filter.matches(&self.entities[i]))
+            .collect();
+
+        let limit = match variable {
+            // 仿真世界中没有坐标，把执行者视为距离最近的玩家
+            "p" => {
+                if let Some(pos) = selected.iter().position(|&i| Some(i) == self.executor) {
+                    selected.swap(0, pos);
+                }
+                Some(filter.count.unwrap_or(1))
+            }
+            "r" => {
+                selected.shuffle(rng);
+                Some(filter.count.unwrap_or(1))
+            }
+            _ => filter.count,
+        };
+        if let Some(limit) = limit {
+            selected.truncate(limit);
+        }
+
+        Ok(selected)
+    }
+
+    /// 以选择器渲染实体名称，与游戏一致地以逗号分隔
+    pub fn display_names(&self, selector: &str, rng: &mut StdRng) -> Result<String> {
+        let names: Vec<&str> = self
+            .select(selector, rng)?
+            .into_iter()
+            .map(|i| &*self.entities[i].name)
+            .collect();
+        Ok(names.join(", "))
+    }
+}
+
+#[derive(Default)]
+struct SelectorFilter {
+    tags: Vec<(bool, String)>,
+    kinds: Vec<(bool, String)>,
+    names: Vec<(bool, String)>,
+    scores: Vec<(String, bool, ScoreRange)>,
+    count: Option<usize>,
+}
+
+impl SelectorFilter {
+    fn push(&mut self, key: &str, value: &str) -> Result<()> {
+        let (negated, plain) = match value.strip_prefix('!') {
+            Some(v) => (true, v),
+            None => (false, value),
+        };
+
+        match key {
+            "tag" => self.tags.push((negated, unquote(plain))),
+            "type" => self
+                .kinds
+                .push((negated, strip_namespace(&unquote(plain)).into())),
+            "name" => self.names.push((negated, unquote(plain))),
+            "c" => {
+                let count: i32 = value.parse()?;
+                self.count = Some(count.unsigned_abs() as usize);
+            }
+            "scores" => {
+                let Some(inner) = value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) else {
+                    return Err(anyhow!("invalid `scores` argument `{value}`"));
+                };
+
+                for score in split_top_level(inner).iter().filter(|s| !s.is_empty()) {
+                    let Some((objective, range)) = score.split_once('=') else {
+                        return Err(anyhow!("invalid score condition `{score}`"));
+                    };
+                    let (negated, range) = match range.strip_prefix('!') {
+                        Some(r) => (true, r),
+                        None => (false, range),
+                    };
+                    self.scores
+                        .push((objective.into(), negated, ScoreRange::parse(range)?));
+                }
+            }
+            // 仿真世界中没有坐标、朝向与游戏模式，忽略这些参数
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn matches(&self, entity: &Entity) -> bool {
+        let tags = self.tags.iter().all(|(negated, tag)| {
+            // `tag=`匹配没有标签的实体
+            let has = if tag.is_empty() {
+                entity.tags.is_empty()
+            } else {
+                entity.tags.contains(tag)
+            };
+            has != *negated
+        });
+
+        let kinds = self
+            .kinds
+            .iter()
+            .all(|(negated, kind)| (entity.kind == *kind) != *negated);
+        let names = self
+            .names
+            .iter()
+            .all(|(negated, name)| (entity.name == *name) != *negated);
+        let scores = self.scores.iter().all(|(objective, negated, range)| {
+            match entity.scores.get(objective) {
+                Some(score) => range.contains(*score) != *negated,
+                None => false,
+            }
+        });
+
+        tags && kinds && names && scores
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ScoreRange {
+    min: i32,
+    max: i32,
+}
+
+impl ScoreRange {
+    fn parse(s: &str) -> Result<Self> {
+        let parse_bound = |b: &str, default| {
+            if b.is_empty() {
+                Ok(default)
+            } else {
+                b.parse::<i32>()
+                    .map_err(|_| anyhow!("invalid score range `{s}`"))
+            }
+        };
+
+        match s.split_once("..") {
+            Some((min, max)) => Ok(ScoreRange {
+                min: parse_bound(min, i32::MIN)?,
+                max: parse_bound(max, i32::MAX)?,
+            }),
+            None => {
+                let value = parse_bound(s, 0)?;
+                Ok(ScoreRange {
+                    min: value,
+                    max: value,
+                })
+            }
+        }
+    }
+
+    fn contains(&self, value: i32) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+}
+
+fn strip_namespace(kind: &str) -> &str {
+    kind.strip_prefix("minecraft:").unwrap_or(kind)
+}
+
+fn unquote(s: &str) -> String {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+        .to_string()
+}
+
+/// 去掉引号外的空白，词法分析后的选择器各词元间带有空格
+fn strip_whitespace(s: &str) -> String {
+    let mut in_quote = false;
+    s.chars()
+        .filter(|c| {
+            if *c == '"' {
+                in_quote = !in_quote;
+            }
+            in_quote || !c.is_whitespace()
+        })
+        .collect()
+}
+
+/// 以不在括号或引号内的空白分割一条命令
+pub fn split_command(cmd: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut in_quote, mut start) = (0, false, None);
+
+    for (index, c) in cmd.char_indices() {
+        match c {
+            '"' => in_quote = !in_quote,
+            '{' | '[' if !in_quote => depth += 1,
+            '}' | ']' if !in_quote => depth -= 1,
+            c if c.is_whitespace() && !in_quote && depth == 0 => {
+                if let Some(start) = start.take() {
+                    parts.push(&cmd[start..index]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(index);
+    }
+
+    if let Some(start) = start {
+        parts.push(&cmd[start..]);
+    }
+    parts
+}
+
+/// 以不在括号或引号内的逗号分割
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut in_quote, mut start) = (0, false, 0);
+
+    for (index, c) in s.char_indices() {
+        match c {
+            '"' => in_quote = !in_quote,
+            '{' | '[' if !in_quote => depth += 1,
+            '}' | ']' if !in_quote => depth -= 1,
+            ',' if !in_quote && depth == 0 => {
+                parts.push(&s[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
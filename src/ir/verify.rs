@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Result};
+
+use crate::parse::span::ErrorList;
+
+use super::{is_table_sorted, BoolOprRhs, CacheTag, FormatArgument, Ir, Label, LabelMap};
+
+impl LabelMap<'_> {
+    /// 检查中间代码的一致性：调用的标签与使用的静态变量都已定义，匹配表有序。
+    ///
+    /// 这些错误都来自编译器自身，与源码无关。
+    pub fn verify(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if !self.mem_size.is_multiple_of(self.word_width) {
+            errors.push(anyhow!(
+                "the memory size ({}) is not a multiple of the word width ({})",
+                self.mem_size,
+                self.word_width
+            ));
+        }
+
+        for info in self.labels() {
+            let label = info.label;
+            let mut error =
+                |message: String| errors.push(anyhow!("internal error in `{label:?}`: {message}"));
+
+            for inst in &info.insts {
+                for target in called_labels(inst) {
+                    if !self.label_map.contains_key(&target) {
+                        error(format!("calls undefined label `{target:?}`"));
+                    }
+                }
+
+                for tag in cache_tags(inst) {
+                    if !matches!(tag, CacheTag::Regular(_)) && !self.static_map.contains_key(&tag) {
+                        error(format!("uses undefined static `{tag:?}`"));
+                    }
+                }
+
+                match inst {
+                    Ir::Table { sorted_arms, .. } if !is_table_sorted(sorted_arms) => {
+                        error("match table is not strictly sorted".into());
+                    }
+                    Ir::Random { min, max, .. } if min > max => {
+                        error(format!("random range {min}..={max} is empty"));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        ErrorList::into_result(errors)
+    }
+}
+
+fn called_labels<'a>(inst: &Ir<'a>) -> Vec<Label<'a>> {
+    match inst {
+        Ir::Call { label } => vec![*label],
+        Ir::Cond { then, .. } => vec![*then],
+        Ir::Table { sorted_arms, .. } => sorted_arms.iter().map(|(_, label)| *label).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn cache_tags<'a>(inst: &Ir<'a>) -> Vec<CacheTag<'a>> {
+    match inst {
+        Ir::Assign { dst, .. } | Ir::Increase { dst, .. } | Ir::Random { dst, .. } => vec![*dst],
+        Ir::Operation { dst, src, .. } | Ir::Not { dst, src } => vec![*dst, *src],
+        Ir::BoolOperation { dst, lhs, rhs, .. } => match rhs {
+            BoolOprRhs::CacheTag(rhs) => vec![*dst, *lhs, *rhs],
+            BoolOprRhs::Constant(_) => vec![*dst, *lhs],
+        },
+        Ir::Cond { cond, .. } | Ir::Table { cond, .. } => vec![*cond],
+        Ir::Load { mem_offset, .. } | Ir::Store { mem_offset, .. } => vec![*mem_offset],
+        Ir::CmdFmt { args, .. } => args
+            .iter()
+            .filter_map(|arg| match arg {
+                FormatArgument::CacheTag(tag) => Some(*tag),
+                _ => None,
+            })
+            .collect(),
+        Ir::Call { .. } | Ir::CmdRaw(_) | Ir::SimulationAbort => Vec::new(),
+    }
+}
@@ -0,0 +1,144 @@
+//! MCSH编译器的库接口，命令行程序只是它的一层包装。
+//!
+//! 编译结果借用源码，源码需要比[`Compilation`]活得更久。
+
+use anyhow::Result;
+
+use crate::{
+    atoi::{Atoi, DEFAULT_FIXED_SCALE},
+    ir::{compile::Files, LabelMap, DEFAULT_MEM_SIZE, DEFAULT_WORD_WIDTH},
+    parse::parse_file_recovered,
+};
+
+pub(crate) mod atoi;
+pub mod cli;
+pub(crate) mod diagnostics;
+pub(crate) mod format;
+pub(crate) mod formatter;
+pub mod ir;
+pub(crate) mod lsp;
+pub(crate) mod manifest;
+pub(crate) mod parse;
+pub(crate) mod project;
+mod runner;
+pub(crate) mod watch;
+
+pub use diagnostics::{CompileError, Diagnostic, Severity};
+
+/// 仿真器的选项与运行结果
+pub mod simulate {
+    pub use crate::{
+        ir::{
+            simulate::{
+                coverage::{ArmCoverage, CoverageReport, FunctionCoverage},
+                world::{ChatMessage, Entity, MessageKind, World},
+                SimulateOptions, SimulateResult,
+            },
+            ArmKind,
+        },
+        runner::{SimulationSummary, TestOutcome, TestReport},
+    };
+}
+
+#[derive(Clone, Debug)]
+pub struct CompileOptions {
+    /// 内存大小，必须是字宽的整数倍
+    pub mem_size: u32,
+    /// 每次读写内存的记分项数量
+    pub word_width: u32,
+    /// 定点数的倍数，必须是10到10000之间10的幂
+    pub fixed_scale: i32,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            mem_size: DEFAULT_MEM_SIZE,
+            word_width: DEFAULT_WORD_WIDTH,
+            fixed_scale: DEFAULT_FIXED_SCALE,
+        }
+    }
+}
+
+/// 一个源文件编译为中间代码的结果
+pub struct Compilation<'a> {
+    label_map: LabelMap<'a>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// 解析源码并编译为中间代码，失败时返回所有错误与警告。
+///
+/// 一次只编译一个源文件：MCSH没有模块与导入，一个程序就是一个文件（项目中为`src/main.mcsh`）。
+/// 每个程序都会生成`mcsh_init`与`MCSH`目录下的内部函数并使用相同的内部记分项，
+/// 多个源文件需要分别编译，安装到各自的行为包中
+pub fn compile(source: &str) -> Result<Compilation<'_>, CompileError> {
+    compile_with(source, &CompileOptions::default())
+}
+
+pub fn compile_with<'a>(
+    source: &'a str,
+    options: &CompileOptions,
+) -> Result<Compilation<'a>, CompileError> {
+    let (defs, parse_error) = parse_file_recovered(source).map_err(|err| CompileError {
+        diagnostics: Diagnostic::from_error(source, &err),
+    })?;
+
+    // 语法错误之外的定义仍然继续检查，以便一次报告所有错误
+    let mut atoi = Atoi::with_options(options);
+    let result = atoi.parse(&defs);
+    let mut diagnostics = Vec::new();
+    if let Some(err) = &parse_error {
+        diagnostics.extend(Diagnostic::from_error(source, err));
+    }
+    diagnostics.extend(
+        atoi.warnings()
+            .iter()
+            .map(|warning| Diagnostic::from_warning(source, warning)),
+    );
+
+    let label_map = match result {
+        Ok(()) if parse_error.is_none() => atoi.finish(),
+        Ok(()) => return Err(CompileError { diagnostics }),
+        Err(err) => {
+            diagnostics.extend(Diagnostic::from_error(source, &err));
+            return Err(CompileError { diagnostics });
+        }
+    };
+
+    if let Err(err) = label_map.verify() {
+        diagnostics.extend(Diagnostic::from_error(source, &err));
+        return Err(CompileError { diagnostics });
+    }
+
+    Ok(Compilation {
+        label_map,
+        warnings: diagnostics,
+    })
+}
+
+impl<'a> Compilation<'a> {
+    /// 中间代码，可用于自定义的分析
+    pub fn label_map(&self) -> &LabelMap<'a> {
+        &self.label_map
+    }
+
+    /// 用于自定义的中间代码处理，`files`与仿真都会使用修改后的结果，修改后可用[`LabelMap::verify`]检查
+    pub fn label_map_mut(&mut self) -> &mut LabelMap<'a> {
+        &mut self.label_map
+    }
+
+    /// 生成mcfunction文件，路径相对于行为包的`functions`目录
+    pub fn files(&self) -> Result<Files> {
+        self.label_map.compile_files()
+    }
+
+    /// 在新的仿真环境中运行一个导出函数
+    pub fn simulate(
+        &self,
+        function: &str,
+        options: &simulate::SimulateOptions,
+    ) -> Result<simulate::SimulateResult<'_>> {
+        let mut machine = self.label_map.simulator(options)?;
+        Ok(machine.call_pub(function))
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::BTreeSet,
+    ops::Range,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use crate::{
+    atoi::{
+        symbols::{SymbolKind, Symbols},
+        Atoi,
+    },
+    parse::{
+        parse_file_recovered,
+        span::{ErrorList, Span, SpannedError},
+    },
+};
+
+/// 一个文档的分析结果，位置均为字节偏移
+#[derive(Default)]
+pub struct Analysis {
+    pub diagnostics: Vec<(Range<usize>, String)>,
+    pub warnings: Vec<(Range<usize>, String)>,
+    pub definitions: Vec<Definition>,
+    /// 标识符的使用及其定义在`definitions`中的下标
+    pub references: Vec<(Range<usize>, usize)>,
+    /// 文档中出现的所有标识符，分析失败时用于补全
+    pub identifiers: BTreeSet<String>,
+}
+
+pub struct Definition {
+    pub range: Range<usize>,
+    pub name: String,
+    pub kind: SymbolKind,
+    pub value: Option<String>,
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        let mut analysis = Analysis {
+            identifiers: scan_identifiers(source),
+            ..Default::default()
+        };
+
+        let defs = match parse_file_recovered(source) {
+            Ok((defs, err)) => {
+                if let Some(err) = err {
+                    analysis.push_error(source, &err);
+                }
+                defs
+            }
+            Err(err) => {
+                analysis.push_error(source, &err);
+                return analysis;
+            }
+        };
+
+        // 编译器中仍有未实现的部分会panic，不能让它结束语言服务器
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut atoi = Atoi::new();
+            let result = atoi.parse(&defs);
+            let warnings: Vec<_> = atoi
+                .warnings()
+                .iter()
+                .filter_map(|w| Some((span_in(source, w.at)?, w.message.clone())))
+                .collect();
+            (result, atoi.symbols(), warnings)
+        }));
+
+        match result {
+            Ok((result, symbols, warnings)) => {
+                if let Err(err) = result {
+                    analysis.push_error(source, &err);
+                }
+                analysis.warnings = warnings;
+                analysis.read_symbols(source, &symbols);
+            }
+            Err(_) => analysis
+                .diagnostics
+                .push((0..0, "internal compiler error".into())),
+        }
+
+        analysis
+    }
+
+    fn push_error(&mut self, source: &str, err: &anyhow::Error) {
+        for err in ErrorList::flatten(err) {
+            let (range, message) = match SpannedError::find(err) {
+                Some(e) => (e.span.range_in(source).unwrap_or(0..0), e.message.clone()),
+                None => (0..0, format!("{err:#}")),
+            };
+            self.diagnostics.push((range, message));
+        }
+    }
+
+    fn read_symbols(&mut self, source: &str, symbols: &Symbols) {
+        for def in &symbols.definitions {
+            let Some(range) = span_in(source, def.name) else {
+                continue;
+            };
+            self.definitions.push(Definition {
+                range,
+                name: def.name.into(),
+                kind: def.kind,
+                value: def.value.clone(),
+            });
+        }
+
+        for reference in &symbols.references {
+            let (Some(usage), Some(def)) = (
+                span_in(source, reference.usage),
+                span_in(source, reference.definition),
+            ) else {
+                continue;
+            };
+
+            if let Some(index) = self.definitions.iter().position(|d| d.range == def) {
+                self.references.push((usage, index));
+            }
+        }
+    }
+
+    /// 光标所在的标识符对应的定义
+    pub fn definition_at(&self, offset: usize) -> Option<&Definition> {
+        let contains = |range: &Range<usize>| range.start <= offset && offset <= range.end;
+
+        self.references
+            .iter()
+            .find(|(usage, _)| contains(usage))
+            .map(|(_, index)| &self.definitions[*index])
+            .or_else(|| self.definitions.iter().find(|d| contains(&d.range)))
+    }
+}
+
+fn span_in(source: &str, fragment: &str) -> Option<Range<usize>> {
+    Span::of(fragment).range_in(source)
+}
+
+fn scan_identifiers(source: &str) -> BTreeSet<String> {
+    source
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'))
+        .map(String::from)
+        .collect()
+}
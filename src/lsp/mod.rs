@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::{atoi::symbols::SymbolKind, format::FormatStyle};
+
+use self::analysis::Analysis;
+
+mod analysis;
+
+const MACROS: &[&str] = &["print", "title", "run", "run_concat"];
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "min", "max", "random", "abs", "sign", "clamp", "pow", "sqrt", "gcd", "lerp", "sin", "cos",
+];
+const KEYWORDS: &[&str] = &[
+    "fn", "export", "let", "const", "static", "if", "else", "while", "for", "in", "loop", "match",
+    "return", "break", "continue", "debugger",
+];
+
+/// 以标准输入输出运行语言服务器，直到客户端发送`exit`
+pub fn run() -> Result<()> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(&mut input)? {
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    exit: bool,
+}
+
+struct Document {
+    text: String,
+    analysis: Analysis,
+}
+
+impl Server {
+    /// 处理一条消息，返回需要发送给客户端的消息
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        let Some(id) = message.get("id") else {
+            return self.notify(method, params);
+        };
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": { "triggerCharacters": ["#", "{"] },
+                },
+                "serverInfo": { "name": "mcsh" },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/definition" => Ok(self.definition(params)),
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/completion" => Ok(self.completion(params)),
+            _ => Err(json!({ "code": -32601, "message": format!("unknown method `{method}`") })),
+        };
+
+        vec![match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        }]
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let text = match method {
+            "exit" => {
+                self.exit = true;
+                return Vec::new();
+            }
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // 只支持全量同步，最后一项即为完整的文本
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish_diagnostics(uri, Vec::new())];
+            }
+            _ => None,
+        };
+
+        let Some(text) = text else {
+            return Vec::new();
+        };
+
+        let document = Document {
+            analysis: Analysis::new(text),
+            text: text.into(),
+        };
+        let errors = document.analysis.diagnostics.iter().map(|d| (d, 1));
+        let warnings = document.analysis.warnings.iter().map(|d| (d, 2));
+        let diagnostics = errors
+            .chain(warnings)
+            .map(|((range, message), severity)| {
+                json!({
+                    "range": to_lsp_range(&document.text, range.start, range.end),
+                    "severity": severity,
+                    "source": "mcsh",
+                    "message": message,
+                })
+            })
+            .collect();
+        self.documents.insert(uri.into(), document);
+
+        vec![publish_diagnostics(uri, diagnostics)]
+    }
+
+    /// 请求对应的文档与光标的字节偏移
+    fn locate<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a Document, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let document = self.documents.get(uri)?;
+        let position = &params["position"];
+        let offset = to_offset(
+            &document.text,
+            position["line"].as_u64()? as usize,
+            position["character"].as_u64()? as usize,
+        );
+        Some((uri, document, offset))
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some((uri, document, offset)) = self.locate(params) else {
+            return Value::Null;
+        };
+
+        match document.analysis.definition_at(offset) {
+            Some(def) => json!({
+                "uri": uri,
+                "range": to_lsp_range(&document.text, def.range.start, def.range.end),
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some((_, document, offset)) = self.locate(params) else {
+            return Value::Null;
+        };
+        let Some(def) = document.analysis.definition_at(offset) else {
+            return Value::Null;
+        };
+
+        let name = &def.name;
+        let signature = match (def.kind, &def.value) {
+            (SymbolKind::Function, _) => format!("fn {name}"),
+            (SymbolKind::Constant, Some(value)) => format!("const {name} = {value}"),
+            (SymbolKind::Static, Some(value)) => format!("static {name} = {value}"),
+            (SymbolKind::Constant, None) => format!("const {name}"),
+            (SymbolKind::Static, None) => format!("static {name}"),
+            (SymbolKind::Variable, _) => format!("let {name}"),
+            (SymbolKind::Argument, _) => format!("{name} // 参数"),
+        };
+
+        json!({
+            "contents": { "kind": "markdown", "value": format!("```mcsh\n{signature}\n```") },
+        })
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let Some((_, document, offset)) = self.locate(params) else {
+            return json!([]);
+        };
+
+        let line_start = document.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let items = match completion_context(&document.text[line_start..offset]) {
+            CompletionContext::Style => FormatStyle::ALL
+                .iter()
+                .map(|style| style.name())
+                .chain(FormatStyle::NICK_NAMES.iter().map(|(name, _)| *name))
+                .map(|name| json!({ "label": name, "kind": 21 }))
+                .collect(),
+            CompletionContext::FormatArgument => document
+                .analysis
+                .identifiers
+                .iter()
+                .map(|name| json!({ "label": name, "kind": 6 }))
+                .collect(),
+            CompletionContext::String => Vec::new(),
+            CompletionContext::Code => {
+                let macros = MACROS.iter().map(|name| {
+                    json!({ "label": format!("{name}!"), "insertText": format!("{name}!"), "kind": 3 })
+                });
+                let builtins = BUILTIN_FUNCTIONS
+                    .iter()
+                    .map(|name| json!({ "label": name, "kind": 3 }));
+                let keywords = KEYWORDS
+                    .iter()
+                    .map(|name| json!({ "label": name, "kind": 14 }));
+                let identifiers = document.analysis.identifiers.iter().map(|name| {
+                    let kind = match document
+                        .analysis
+                        .definitions
+                        .iter()
+                        .find(|d| d.name == *name)
+                    {
+                        Some(def) => completion_kind(def.kind),
+                        None => 1,
+                    };
+                    json!({ "label": name, "kind": kind })
+                });
+
+                macros
+                    .chain(builtins)
+                    .chain(keywords)
+                    .chain(identifiers)
+                    .collect()
+            }
+        };
+
+        Value::Array(items)
+    }
+}
+
+enum CompletionContext {
+    Code,
+    String,
+    /// 格式化字符串中的`{...}`
+    FormatArgument,
+    /// 格式化字符串中的`{#...}`
+    Style,
+}
+
+/// 根据光标所在行光标前的文本判断补全的内容
+fn completion_context(before: &str) -> CompletionContext {
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut argument_start = None;
+
+    for (index, c) in before.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => {
+                in_string = !in_string;
+                argument_start = None;
+            }
+            '{' if in_string => argument_start = Some(index + 1),
+            '}' if in_string => argument_start = None,
+            _ => {}
+        }
+    }
+
+    match argument_start {
+        _ if !in_string => CompletionContext::Code,
+        Some(start) if before[start..].starts_with('#') => CompletionContext::Style,
+        Some(_) => CompletionContext::FormatArgument,
+        None => CompletionContext::String,
+    }
+}
+
+fn completion_kind(kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Function => 3,
+        SymbolKind::Constant => 21,
+        SymbolKind::Static | SymbolKind::Variable | SymbolKind::Argument => 6,
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// 把UTF-16的行列位置转换为字节偏移
+fn to_offset(text: &str, line: usize, character: usize) -> usize {
+    let line_start = match line {
+        0 => 0,
+        _ => match text.match_indices('\n').nth(line - 1) {
+            Some((index, _)) => index + 1,
+            None => return text.len(),
+        },
+    };
+
+    let mut units = 0;
+    for (index, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// 把字节偏移转换为UTF-16的行列位置
+fn to_position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+fn to_lsp_range(text: &str, start: usize, end: usize) -> Value {
+    json!({ "start": to_position(text, start), "end": to_position(text, end) })
+}
+
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| anyhow!("message without `Content-Length` header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()?;
+    Ok(())
+}
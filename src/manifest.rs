@@ -10,6 +10,7 @@ use uuid::Uuid;
 
 use nom::sequence::tuple;
 
+#[derive(Clone)]
 pub struct McManifest {
     pub name: String,
     pub description: String,
@@ -5,7 +5,12 @@ use super::lexer::{Lexer, Token};
 #[derive(Debug, thiserror::Error)]
 pub enum McshError<'a> {
     #[error("expected {expected}, found {found}")]
-    Token { expected: &'a str, found: Token<'a> },
+    Token {
+        expected: &'a str,
+        found: Token<'a>,
+        /// 找到的词元在源码中对应的文本
+        at: &'a str,
+    },
 
     #[error("{0}")]
     Nom(NomError<Lexer<'a>>),
@@ -22,10 +27,31 @@ impl<'a> ParseError<Lexer<'a>> for McshError<'a> {
             Self::Token { .. } => other,
         }
     }
+
+    /// 保留解析得最远的分支的错误，它通常最接近真正的问题。位置相同时保留先尝试的分支中指明期望词元的错误
+    fn or(self, other: Self) -> Self {
+        let rank = |err: &Self| (err.position(), matches!(err, Self::Token { .. }));
+        if rank(&other) > rank(&self) {
+            other
+        } else {
+            self
+        }
+    }
 }
 
 impl<'a> McshError<'a> {
-    pub fn token(expected: &'a str, found: Token<'a>) -> Self {
-        Self::Token { expected, found }
+    fn position(&self) -> *const u8 {
+        match self {
+            Self::Token { at, .. } => at.as_ptr(),
+            Self::Nom(err) => err.input.peek_source().as_ptr(),
+        }
+    }
+
+    pub fn token(expected: &'a str, found: Token<'a>, at: &'a str) -> Self {
+        Self::Token {
+            expected,
+            found,
+            at,
+        }
     }
 }
@@ -1,26 +1,25 @@
 use std::{
-    cell::Cell,
-    fmt::{Display, Write},
+    cell::{Cell, RefCell},
+    fmt::Display,
     rc::Rc,
 };
 
-use anyhow::anyhow;
-use nom::{
-    combinator::{eof, value, verify},
-    sequence::terminated,
-    InputLength,
-};
+use nom::{combinator::eof, sequence::terminated, InputLength};
 
 pub use self::parse::*;
 
-use super::{IResult, McshError};
+use super::{span::SpannedError, IResult, McshError};
 
 mod parse;
 
 #[derive(Clone, Debug)]
 pub struct Lexer<'a> {
-    tokens: Rc<[Token<'a>]>,
+    tokens: Rc<[TokenTree<'a>]>,
     cursor: Cell<usize>,
+    /// 输入结束的位置，即文件末尾或组的右括号
+    eof: &'a str,
+    /// 解析中已经恢复的错误，由同一文件的所有词法器共享
+    errors: Rc<RefCell<Vec<anyhow::Error>>>,
 }
 
 impl InputLength for Lexer<'_> {
@@ -34,10 +33,10 @@ impl Display for Lexer<'_> {
         let mut first = true;
         for token in self.tokens.get(self.cursor.get()..).unwrap_or_default() {
             if first {
-                token.fmt(f)?;
+                token.token.fmt(f)?;
                 first = false;
             } else {
-                write!(f, " {token}")?;
+                write!(f, " {}", token.token)?;
             }
         }
         Ok(())
@@ -49,16 +48,43 @@ impl<'a> Lexer<'a> {
         let result = nom::combinator::map(terminated(parse_tokens, eof), |tokens| Self {
             tokens,
             cursor: Default::default(),
+            eof: &input[input.len()..],
+            errors: Default::default(),
         })(input);
 
         match result {
             Ok((_, lexer)) => Ok(lexer),
-            Err(err) => Err(anyhow!("{err}")),
+            Err(err) => {
+                let rest = match &err {
+                    nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+                    nom::Err::Incomplete(_) => "",
+                };
+                let Some(c) = rest.chars().next() else {
+                    return Err(SpannedError::at(rest, "unclosed delimiter at end of file"));
+                };
+                let message = match c {
+                    ')' | ']' | '}' => format!("unexpected closing delimiter `{c}`"),
+                    '"' => "unterminated string".into(),
+                    _ => format!("unexpected character `{c}`"),
+                };
+                Err(SpannedError::at(&rest[..c.len_utf8()], message))
+            }
         }
     }
 
     pub fn peek(&self) -> &Token<'a> {
-        self.tokens.get(self.cursor.get()).unwrap_or(&Token::Eof)
+        match self.tokens.get(self.cursor.get()) {
+            Some(tt) => &tt.token,
+            None => &Token::Eof,
+        }
+    }
+
+    /// 当前词元在源码中对应的文本
+    pub fn peek_source(&self) -> &'a str {
+        match self.tokens.get(self.cursor.get()) {
+            Some(tt) => tt.source,
+            None => self.eof,
+        }
     }
 
     pub fn step(&self, length: usize) {
@@ -66,37 +92,59 @@ impl<'a> Lexer<'a> {
             .set(self.tokens.len().min(self.cursor.get() + length))
     }
 
-    pub fn print_err(&self) -> anyhow::Error {
-        let index = self.cursor.get();
-        let len = self.tokens.len();
-        let mut output = String::new();
-
-        let before = &self.tokens[index.checked_sub(10).unwrap_or(0)..index];
-        let after = &self.tokens[(index + 1).min(len)..(index + 11).min(len)];
-
-        for t in before {
-            write!(output, "{t} ").unwrap();
-        }
+    /// 记录一个已经恢复的错误
+    pub fn report(&self, err: anyhow::Error) {
+        self.errors.borrow_mut().push(err);
+    }
 
-        output.push_str(">>>>>> ");
-        if let Some(t) = self.tokens.get(index) {
-            write!(output, "{t}").unwrap();
+    /// 取出记录的错误。回溯可能使同一处错误被记录多次，重复的只保留一个
+    pub fn take_errors(&self) -> Vec<anyhow::Error> {
+        let mut errors: Vec<anyhow::Error> = Vec::new();
+        for err in self.errors.take() {
+            let key = |e: &anyhow::Error| (SpannedError::find(e).map(|s| s.span), e.to_string());
+            if !errors.iter().any(|e| key(e) == key(&err)) {
+                errors.push(err);
+            }
         }
-        output.push_str(" <<<<<<");
+        errors
+    }
 
-        for t in after {
-            write!(output, " {t}").unwrap();
+    /// 出错后跳过当前语句：跳到下一个`;`或花括号组之后，`else`分支一并跳过
+    pub fn skip_statement(&self) {
+        loop {
+            match self.peek() {
+                Token::Eof => break,
+                Token::Punct(Punct::Semi) => {
+                    self.step(1);
+                    break;
+                }
+                Token::Group(group) if group.delimiter == Delimiter::Brace => {
+                    self.step(1);
+                    if !matches!(self.peek(), Token::Ident("else")) {
+                        break;
+                    }
+                }
+                _ => self.step(1),
+            }
         }
+    }
 
-        anyhow!("{output}")
+    pub fn cursor(&self) -> usize {
+        self.cursor.get()
     }
 }
 
-impl<'a> From<Rc<[Token<'a>]>> for Lexer<'a> {
-    fn from(value: Rc<[Token<'a>]>) -> Self {
+impl<'a> From<Rc<[TokenTree<'a>]>> for Lexer<'a> {
+    fn from(value: Rc<[TokenTree<'a>]>) -> Self {
+        let eof = match value.last() {
+            Some(last) => &last.source[last.source.len()..],
+            None => "",
+        };
         Lexer {
             tokens: value,
             cursor: Default::default(),
+            eof,
+            errors: Default::default(),
         }
     }
 }
@@ -110,34 +158,52 @@ pub fn keyword<'a>(kw: &'a str) -> impl Fn(Lexer<'a>) -> IResult<'a, ()> {
                 return Ok((input, ()));
             }
         }
-        error(kw, p)
+        error(kw, &input)
     }
 }
 
 pub fn group(delimiter: Delimiter) -> impl Fn(Lexer) -> IResult<Lexer> {
     move |input| {
-        let p = input.peek();
-        match p {
+        let source = input.peek_source();
+        match input.peek() {
             Token::Group(group) if group.delimiter == delimiter => {
-                input.step(1);
                 let inside = Lexer {
                     tokens: group.content.clone(),
                     cursor: Cell::new(0),
+                    eof: &source[source.len() - 1..],
+                    errors: input.errors.clone(),
                 };
+                input.step(1);
                 Ok((input, inside))
             }
-            _ => error(delimiter.display(), p),
+            _ => error(delimiter.display(), &input),
         }
     }
 }
 
-pub fn ident(input: Lexer) -> IResult<&str> {
+pub fn ident(input: Lexer<'_>) -> IResult<'_, &str> {
     let p = input.peek();
     if let &Token::Ident(ident) = p {
         input.step(1);
         Ok((input, ident))
     } else {
-        error("identifier", p)
+        error("identifier", &input)
+    }
+}
+
+/// 当前词元在源码中对应的文本，不消耗输入
+pub fn fragment(input: Lexer<'_>) -> IResult<'_, &str> {
+    let source = input.peek_source();
+    Ok((input, source))
+}
+
+pub fn loop_label(input: Lexer<'_>) -> IResult<'_, &str> {
+    let p = input.peek();
+    if let &Token::Label(label) = p {
+        input.step(1);
+        Ok((input, label))
+    } else {
+        error("loop label", &input)
     }
 }
 
@@ -147,12 +213,18 @@ pub fn punct(input: Lexer) -> IResult<Punct> {
         input.step(1);
         Ok((input, punct))
     } else {
-        error("punctuation", p)
+        error("punctuation", &input)
     }
 }
 
 pub fn specified_punct<'a>(expect: Punct) -> impl FnMut(Lexer<'a>) -> IResult<'a, Punct> {
-    value(expect, verify(punct, move |p| *p == expect))
+    move |input| match input.peek() {
+        &Token::Punct(punct) if punct == expect => {
+            input.step(1);
+            Ok((input, punct))
+        }
+        _ => error(expect.display(), &input),
+    }
 }
 
 pub fn integer(input: Lexer) -> IResult<i32> {
@@ -161,23 +233,34 @@ pub fn integer(input: Lexer) -> IResult<i32> {
         input.step(1);
         Ok((input, int))
     } else {
-        error("integer", p)
+        error("integer", &input)
+    }
+}
+
+pub fn decimal(input: Lexer<'_>) -> IResult<'_, &str> {
+    let p = input.peek();
+    if let &Token::Literal(Literal::Decimal(num)) = p {
+        input.step(1);
+        Ok((input, num))
+    } else {
+        error("decimal", &input)
     }
 }
 
-pub fn string(input: Lexer) -> IResult<&str> {
+pub fn string(input: Lexer<'_>) -> IResult<'_, &str> {
     let p = input.peek();
     if let &Token::Literal(Literal::Str(s)) = p {
         input.step(1);
         Ok((input, s))
     } else {
-        error("string", p)
+        error("string", &input)
     }
 }
 
-fn error<'a, O>(expected: &'a str, found: &Token<'a>) -> IResult<'a, O> {
+fn error<'a, O>(expected: &'a str, input: &Lexer<'a>) -> IResult<'a, O> {
     Err(nom::Err::Error(McshError::token(
         expected,
-        (*found).clone(),
+        input.peek().clone(),
+        input.peek_source(),
     )))
 }
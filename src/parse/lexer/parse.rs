@@ -6,8 +6,8 @@ use std::{
 use nom::{
     branch::alt,
     bytes::complete::{escaped, is_not, tag, take_until},
-    character::complete::{self, alpha1, alphanumeric1, multispace0, one_of},
-    combinator::{fail, map, opt, recognize, value},
+    character::complete::{self, alpha1, alphanumeric1, digit1, multispace0, one_of},
+    combinator::{consumed, cut, fail, map, opt, recognize, value},
     multi::{many0, many0_count},
     sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
@@ -16,16 +16,25 @@ use nom::{
 #[derive(Debug, Clone)]
 pub enum Token<'a> {
     Ident(&'a str),
+    /// 循环标签`'name`，包含开头的引号
+    Label(&'a str),
     Punct(Punct),
     Group(Group<'a>),
     Literal(Literal<'a>),
     Eof,
 }
 
+/// 词元及其在源码中对应的文本
+#[derive(Debug, Clone)]
+pub struct TokenTree<'a> {
+    pub token: Token<'a>,
+    pub source: &'a str,
+}
+
 #[derive(Debug, Clone)]
 pub struct Group<'a> {
     pub delimiter: Delimiter,
-    pub content: Rc<[Token<'a>]>,
+    pub content: Rc<[TokenTree<'a>]>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -66,6 +75,11 @@ macro_rules! punct {
 }
 
 punct! {
+    ShlEq "<<=",
+    ShrEq ">>=",
+    Shl "<<",
+    Shr ">>",
+    Arrow "->",
     Equal2 "==",
     NotEq "!=",
     LessEq "<=",
@@ -74,6 +88,15 @@ punct! {
     And2 "&&",
     Or2 "||",
     Swap "><",
+    PlusEq "+=",
+    MinusEq "-=",
+    StarEq "*=",
+    SlashEq "/=",
+    PercentEq "%=",
+    AndEq "&=",
+    OrEq "|=",
+    CaretEq "^=",
+    Dot2Eq "..=",
     Dot2 "..",
     Equal "=",
     Plus "+",
@@ -82,10 +105,15 @@ punct! {
     Slash "/",
     Percent "%",
     Semi ";",
+    Colon ":",
     Dot ".",
     Comma ",",
     LessThan "<",
     GreaterThan ">",
+    And "&",
+    Or "|",
+    Caret "^",
+    Tilde "~",
     Bang "!",
     At "@",
     Pound "#",
@@ -94,22 +122,37 @@ punct! {
 #[derive(Debug, Clone, Copy)]
 pub enum Literal<'a> {
     Int(i32),
+    /// 小数，按定点数的倍数转换后才能得到值
+    Decimal(&'a str),
     Str(&'a str),
 }
 
-pub fn parse_tokens(input: &str) -> IResult<&str, Rc<[Token]>> {
+pub fn parse_tokens(input: &str) -> IResult<&str, Rc<[TokenTree<'_>]>> {
     map(
-        preceded(parse_sep, many0(terminated(parse_token, parse_sep))),
+        preceded(
+            parse_sep,
+            many0(terminated(
+                map(consumed(parse_token), |(source, token)| TokenTree {
+                    token,
+                    source,
+                }),
+                parse_sep,
+            )),
+        ),
         |content| content.into_boxed_slice().into(),
     )(input)
 }
 
-pub fn parse_token(input: &str) -> IResult<&str, Token> {
+pub fn parse_token(input: &str) -> IResult<&str, Token<'_>> {
     alt((
         map(parse_ident, Token::Ident),
+        map(recognize(pair(tag("'"), parse_ident)), Token::Label),
         map(parse_group, Token::Group),
         map(parse_str, |s| Token::Literal(Literal::Str(s))),
         map(parse_punct, Token::Punct), // punct必须在int前，因为它需要解析数字前符号
+        map(recognize(tuple((digit1, tag("."), digit1))), |num| {
+            Token::Literal(Literal::Decimal(num))
+        }),
         map(complete::i32, |num| Token::Literal(Literal::Int(num))),
     ))(input)
 }
@@ -145,29 +188,25 @@ fn parse_sep(input: &str) -> IResult<&str, ()> {
     )(input)
 }
 
-pub fn parse_group(input: &str) -> IResult<&str, Group> {
+pub fn parse_group(input: &str) -> IResult<&str, Group<'_>> {
     let (input, delimiter) = alt((
         value(Delimiter::Paren, tag("(")),
         value(Delimiter::Bracket, tag("[")),
         value(Delimiter::Brace, tag("{")),
     ))(input)?;
 
-    let ret = map(
+    // 开括号之后的错误不再回溯，使错误指向无法解析的位置而不是开括号
+    map(
         terminated(
-            |input| {
-                let r = parse_tokens(input);
-                //dbg!(&r);
-                r
-            },
-            match delimiter {
+            parse_tokens,
+            cut(match delimiter {
                 Delimiter::Paren => tag(")"),
                 Delimiter::Bracket => tag("]"),
                 Delimiter::Brace => tag("}"),
-            },
+            }),
         ),
-        |content| Group { delimiter, content },
-    )(input);
-    ret
+        move |content| Group { delimiter, content },
+    )(input)
 }
 
 impl Display for Token<'_> {
@@ -175,7 +214,7 @@ impl Display for Token<'_> {
         match self {
             Self::Eof => write!(f, "end of input"),
             Self::Group(group) => write!(f, "{group}"),
-            Self::Ident(id) => write!(f, "{id}"),
+            Self::Ident(id) | Self::Label(id) => write!(f, "{id}"),
             Self::Literal(lit) => write!(f, "{lit}"),
             Self::Punct(punct) => write!(f, "{punct}"),
         }
@@ -202,7 +241,7 @@ impl Display for Group<'_> {
 
         write!(f, "{start} ")?;
         for t in self.content.iter() {
-            write!(f, " {t}")?;
+            write!(f, " {}", t.token)?;
         }
         write!(f, "{end}")
     }
@@ -218,6 +257,7 @@ impl Display for Literal<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Int(int) => int.fmt(f),
+            Self::Decimal(num) => num.fmt(f),
             Self::Str(s) => write!(f, "\"{s}\""),
         }
     }
@@ -3,12 +3,13 @@ use self::{
     lexer::{Lexer, Punct},
 };
 
-pub use parse_file::parse_file;
+pub use parse_file::{parse_file, parse_file_recovered};
 
 pub mod entity_selector;
 pub mod error;
 pub mod lexer;
 pub mod parse_file;
+pub mod span;
 
 pub type IResult<'a, O> = nom::IResult<Lexer<'a>, O, McshError<'a>>;
 pub type Block<'a> = Vec<Stmt<'a>>;
@@ -20,16 +21,56 @@ pub enum Definition<'a> {
     Static(ItemStatic<'a>),
 }
 
+/// 定义前的`#[name(args, ...)]`
+#[derive(Clone, Debug)]
+pub struct Attribute<'a> {
+    pub name: &'a str,
+    pub args: Vec<&'a str>,
+}
+
+/// 类型标注`: int`或`: fixed`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    /// 定点数，值为乘以倍数后的整数
+    Fixed,
+    /// 值只能是0或1
+    Bool,
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(Self::Int),
+            "fixed" => Some(Self::Fixed),
+            "bool" => Some(Self::Bool),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Int => "int",
+            Self::Fixed => "fixed",
+            Self::Bool => "bool",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ItemConstant<'a> {
+    pub attrs: Vec<Attribute<'a>>,
     pub name: &'a str,
+    pub ty: Option<Type>,
     pub expr: Expr<'a>,
 }
 
 #[derive(Clone, Debug)]
 pub struct ItemStatic<'a> {
+    pub attrs: Vec<Attribute<'a>>,
     pub export: bool,
     pub name: &'a str,
+    pub ty: Option<Type>,
     pub expr: Expr<'a>,
 }
 
@@ -37,13 +78,17 @@ pub struct ItemStatic<'a> {
 pub enum Stmt<'a> {
     Block(Vec<Stmt<'a>>),
     Assign(StmtAssign<'a>),
+    CompoundAssign(StmtCompoundAssign<'a>),
     While(StmtWhile<'a>),
+    For(StmtFor<'a>),
+    Loop(ExprLoop<'a>),
     If(StmtIf<'a>),
     Match(StmtMatch<'a>),
     Yield,
     Return(StmtReturn<'a>),
-    Break,
-    Continue,
+    Break(StmtBreak<'a>),
+    /// `continue;`或`continue 'label;`
+    Continue(Option<&'a str>),
     Expr(Expr<'a>),
     Swap(StmtSwap<'a>),
     Debugger,
@@ -55,11 +100,16 @@ pub enum Stmt<'a> {
 pub enum Expr<'a> {
     Var(&'a str),
     Integer(i32),
+    /// 小数字面量，如`1.25`
+    Decimal(&'a str),
+    /// `true`或`false`
+    Bool(bool),
     Binary(ExprBinary<'a>),
     Unary(ExprUnary<'a>),
     Call(ExprFnCall<'a>),
     Str(&'a str),
     Block(ExprBlock<'a>),
+    Loop(ExprLoop<'a>),
     MacroCall(MacroCall<'a>),
 }
 
@@ -79,15 +129,69 @@ pub struct StmtSwap<'a> {
 pub struct StmtAssign<'a> {
     pub is_bind: bool,
     pub name: &'a str,
+    /// 只有`let`可以标注类型
+    pub ty: Option<Type>,
+    pub expr: Expr<'a>,
+}
+
+/// `a += b;`、`a &= b;`等，以及`a max= b;`与`a min= b;`
+#[derive(Clone, Debug)]
+pub struct StmtCompoundAssign<'a> {
+    pub name: &'a str,
+    pub op: AssignOp,
     pub expr: Expr<'a>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssignOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Max,
+    Min,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
 #[derive(Clone, Debug)]
 pub struct StmtWhile<'a> {
+    pub label: Option<&'a str>,
     pub expr: Expr<'a>,
     pub body: Block<'a>,
 }
 
+/// `for var in start..end { }`，区间可以写成`(start..end).rev().step_by(step)`
+#[derive(Clone, Debug)]
+pub struct StmtFor<'a> {
+    pub label: Option<&'a str>,
+    pub var: &'a str,
+    pub start: Expr<'a>,
+    pub end: Expr<'a>,
+    /// `start..=end`
+    pub inclusive: bool,
+    pub rev: bool,
+    pub step: Option<Expr<'a>>,
+    pub body: Block<'a>,
+}
+
+/// `loop { }`，作为表达式时值由`break value;`给出
+#[derive(Clone, Debug)]
+pub struct ExprLoop<'a> {
+    pub label: Option<&'a str>,
+    pub body: Block<'a>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StmtBreak<'a> {
+    pub label: Option<&'a str>,
+    pub expr: Option<Expr<'a>>,
+}
+
 #[derive(Clone, Debug)]
 pub struct StmtIf<'a> {
     pub arms: Vec<(Expr<'a>, Block<'a>)>,
@@ -97,7 +201,37 @@ pub struct StmtIf<'a> {
 #[derive(Clone, Debug)]
 pub struct StmtMatch<'a> {
     pub expr: Expr<'a>,
-    pub sorted_arms: Vec<(Option<i32>, Stmt<'a>)>,
+    pub arms: Vec<MatchArm<'a>>,
+}
+
+/// `pattern | pattern if guard => stmt`
+#[derive(Clone, Debug)]
+pub struct MatchArm<'a> {
+    /// 分支开头的源码片段，用于报告错误
+    pub at: &'a str,
+    pub patterns: Vec<Pattern<'a>>,
+    pub guard: Option<Expr<'a>>,
+    pub body: Stmt<'a>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Pattern<'a> {
+    /// `..`，没有其他分支匹配时执行
+    Wildcard,
+    /// 整数或常量。不是常量的标识符会绑定被匹配的值
+    Value(PatValue<'a>),
+    /// `a..b`或`a..=b`，两端都可以省略
+    Range {
+        start: Option<PatValue<'a>>,
+        end: Option<PatValue<'a>>,
+        inclusive: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum PatValue<'a> {
+    Int(i32),
+    Ident(&'a str),
 }
 
 #[derive(Clone, Debug)]
@@ -132,8 +266,17 @@ pub struct MacroCall<'a> {
 
 #[derive(Clone, Debug)]
 pub struct ItemFn<'a> {
+    pub attrs: Vec<Attribute<'a>>,
     pub export: bool,
     pub name: &'a str,
-    pub args: Vec<&'a str>,
+    pub args: Vec<FnArg<'a>>,
+    /// `-> type`
+    pub ret: Option<Type>,
     pub body: Block<'a>,
 }
+
+#[derive(Clone, Copy, Debug)]
+pub struct FnArg<'a> {
+    pub name: &'a str,
+    pub ty: Option<Type>,
+}
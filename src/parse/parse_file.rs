@@ -1,21 +1,26 @@
 use anyhow::{anyhow, Result};
 use nom::{
     branch::alt,
-    combinator::{cut, eof, map, opt, value, verify},
+    combinator::{cut, eof, map, map_opt, not, opt, value, verify},
     multi::{many0, separated_list0, separated_list1},
     sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     Parser,
 };
 
-use crate::parse::error::McshError;
+use crate::parse::{
+    error::McshError,
+    span::{ErrorList, SpannedError},
+};
 
 use super::{
     lexer::{
-        group, ident, integer, keyword, punct, specified_punct, string, Delimiter, Lexer, Punct,
-        Token,
+        decimal, fragment, group, ident, integer, keyword, loop_label, punct, specified_punct,
+        string, Delimiter, Lexer, Punct, Token,
     },
-    Block, Definition, Expr, ExprBlock, ExprFnCall, ExprUnary, IResult, ItemConstant, ItemFn,
-    ItemStatic, MacroCall, Stmt, StmtAssign, StmtIf, StmtMatch, StmtReturn, StmtSwap, StmtWhile,
+    AssignOp, Attribute, Block, Definition, Expr, ExprBlock, ExprFnCall, ExprLoop, ExprUnary,
+    FnArg, IResult, ItemConstant, ItemFn, ItemStatic, MacroCall, MatchArm, PatValue, Pattern, Stmt,
+    StmtAssign, StmtBreak, StmtCompoundAssign, StmtFor, StmtIf, StmtMatch, StmtReturn, StmtSwap,
+    StmtWhile, Type,
 };
 
 #[cfg(debug_assertions)]
@@ -29,16 +34,61 @@ fn debug<'a, O, E>(mut parser: impl Parser<Lexer<'a>, O, E>) -> impl Parser<Lexe
     }
 }
 
-pub fn parse_file(file: &str) -> anyhow::Result<Vec<Definition>> {
+/// 解析文件。出错的定义和语句会被跳过，所有错误会一起返回
+pub fn parse_file(file: &str) -> anyhow::Result<Vec<Definition<'_>>> {
+    let (defs, err) = parse_file_recovered(file)?;
+    match err {
+        Some(err) => Err(err),
+        None => Ok(defs),
+    }
+}
+
+/// 与[`parse_file`]相同，但出错时仍返回成功解析的定义，以便继续检查它们。词法分析失败时没有可用的定义
+pub fn parse_file_recovered(
+    file: &str,
+) -> anyhow::Result<(Vec<Definition<'_>>, Option<anyhow::Error>)> {
     let lexer = Lexer::parse(file)?;
-    let (_, vec) = terminated(many0(parse_definition), eof)(lexer).map_err(|err| match err {
+    let (_, vec) = recover_many0("definition", parse_definition)(lexer.clone())
+        .map_err(|err| to_anyhow(err, "definition"))?;
+    Ok((vec, ErrorList::into_result(lexer.take_errors()).err()))
+}
+
+/// `expected`为正在解析的内容，用于没有指明期望词元的错误
+fn to_anyhow(err: nom::Err<McshError>, expected: &str) -> anyhow::Error {
+    match err {
         nom::Err::Incomplete(_) => unreachable!(),
         nom::Err::Error(err) | nom::Err::Failure(err) => match err {
-            McshError::Token { .. } => anyhow!("{err}"),
-            McshError::Nom(n) => n.input.print_err(),
+            McshError::Token { at, .. } => SpannedError::at(at, &err),
+            McshError::Nom(n) => SpannedError::at(
+                n.input.peek_source(),
+                format_args!("expected {expected}, found {}", n.input.peek()),
+            ),
         },
-    })?;
-    Ok(vec)
+    }
+}
+
+/// 与`many0`类似地解析到输入结束。出错时记录错误，跳过当前语句后继续
+fn recover_many0<'a, O>(
+    expected: &'static str,
+    mut parser: impl Parser<Lexer<'a>, O, McshError<'a>>,
+) -> impl FnMut(Lexer<'a>) -> IResult<'a, Vec<O>> {
+    move |mut input| {
+        let mut output = Vec::new();
+        while !matches!(input.peek(), Token::Eof) {
+            match parser.parse(input.clone()) {
+                Ok((rest, item)) if rest.cursor() > input.cursor() => {
+                    input = rest;
+                    output.push(item);
+                }
+                Ok(_) => input.skip_statement(),
+                Err(err) => {
+                    input.report(to_anyhow(err, expected));
+                    input.skip_statement();
+                }
+            }
+        }
+        Ok((input, output))
+    }
 }
 
 pub fn to_anyhow_result<O>(r: IResult<O>) -> Result<O> {
@@ -71,11 +121,19 @@ pub fn parse_definition(input: Lexer) -> IResult<Definition> {
         tuple((
             keyword("const"),
             ident,
+            opt(parse_type_annotation),
             specified_punct(Punct::Equal),
             parse_expr,
             specified_punct(Punct::Semi),
         )),
-        |(_, name, _, expr, _)| Definition::Constant(ItemConstant { name, expr }),
+        |(_, name, ty, _, expr, _)| {
+            Definition::Constant(ItemConstant {
+                attrs: Vec::new(),
+                name,
+                ty,
+                expr,
+            })
+        },
     );
 
     let parse_static = map(
@@ -83,18 +141,62 @@ pub fn parse_definition(input: Lexer) -> IResult<Definition> {
             map(opt(keyword("export")), |o| o.is_some()),
             keyword("static"),
             ident,
+            opt(parse_type_annotation),
             specified_punct(Punct::Equal),
             parse_expr,
             specified_punct(Punct::Semi),
         )),
-        |(export, _, name, _, expr, _)| Definition::Static(ItemStatic { export, name, expr }),
+        |(export, _, name, ty, _, expr, _)| {
+            Definition::Static(ItemStatic {
+                attrs: Vec::new(),
+                export,
+                name,
+                ty,
+                expr,
+            })
+        },
     );
 
-    alt((
-        parse_const,
-        parse_static,
-        map(parse_item_fn, Definition::Function),
-    ))(input)
+    map(
+        pair(
+            many0(parse_attribute),
+            alt((
+                parse_const,
+                parse_static,
+                map(parse_item_fn, Definition::Function),
+            )),
+        ),
+        |(attrs, mut def)| {
+            match &mut def {
+                Definition::Function(ItemFn { attrs: a, .. })
+                | Definition::Constant(ItemConstant { attrs: a, .. })
+                | Definition::Static(ItemStatic { attrs: a, .. }) => *a = attrs,
+            }
+            def
+        },
+    )(input)
+}
+
+pub fn parse_attribute(input: Lexer) -> IResult<Attribute> {
+    preceded(
+        specified_punct(Punct::Pound),
+        group(Delimiter::Bracket).and_then(terminated(
+            map(
+                pair(
+                    ident,
+                    opt(group(Delimiter::Paren).and_then(terminated(
+                        separated_list0(specified_punct(Punct::Comma), ident),
+                        eof,
+                    ))),
+                ),
+                |(name, args)| Attribute {
+                    name,
+                    args: args.unwrap_or_default(),
+                },
+            ),
+            eof,
+        )),
+    )(input)
 }
 
 pub fn parse_item_fn(input: Lexer) -> IResult<ItemFn> {
@@ -106,25 +208,43 @@ pub fn parse_item_fn(input: Lexer) -> IResult<ItemFn> {
                 tuple((
                     ident,
                     group(Delimiter::Paren).and_then(terminated(
-                        separated_list0(specified_punct(Punct::Comma), ident),
+                        separated_list0(
+                            specified_punct(Punct::Comma),
+                            map(pair(ident, opt(parse_type_annotation)), |(name, ty)| {
+                                FnArg { name, ty }
+                            }),
+                        ),
                         eof,
                     )),
+                    opt(preceded(specified_punct(Punct::Arrow), cut(parse_type))),
                     parse_block,
                 )),
             ),
         ),
-        |(export, (name, args, body))| ItemFn {
+        |(export, (name, args, ret, body))| ItemFn {
+            attrs: Vec::new(),
             export,
             name,
             args,
+            ret,
             body,
         },
     )(input)
 }
 
+/// `int`或`fixed`
+pub fn parse_type(input: Lexer) -> IResult<Type> {
+    map_opt(ident, Type::from_name)(input)
+}
+
+/// 名称之后的`: type`
+fn parse_type_annotation(input: Lexer) -> IResult<Type> {
+    preceded(specified_punct(Punct::Colon), cut(parse_type))(input)
+}
+
 pub fn parse_block(input: Lexer) -> IResult<Block> {
     group(Delimiter::Brace)
-        .and_then(terminated(many0(parse_stmt), eof))
+        .and_then(recover_many0("statement", parse_stmt))
         .parse(input)
 }
 
@@ -138,24 +258,110 @@ fn kw_stmt_parser<'a>(
 pub fn parse_stmt(input: Lexer) -> IResult<Stmt> {
     let parse_let = map(
         terminated(
-            pair(
-                opt(keyword("let")),
-                separated_pair(ident, specified_punct(Punct::Equal), parse_expr),
+            verify(
+                tuple((
+                    opt(keyword("let")),
+                    ident,
+                    opt(parse_type_annotation),
+                    specified_punct(Punct::Equal),
+                    parse_expr,
+                )),
+                // 只有`let`可以标注类型
+                |(bind, _, ty, _, _)| bind.is_some() || ty.is_none(),
             ),
             specified_punct(Punct::Semi),
         ),
-        |(bind, (name, expr))| {
+        |(bind, name, ty, _, expr)| {
             Stmt::Assign(StmtAssign {
                 is_bind: bind.is_some(),
                 name,
+                ty,
                 expr,
             })
         },
     );
 
+    let parse_compound_assign = map(
+        terminated(
+            tuple((ident, parse_assign_op, parse_expr)),
+            specified_punct(Punct::Semi),
+        ),
+        |(name, op, expr)| Stmt::CompoundAssign(StmtCompoundAssign { name, op, expr }),
+    );
+
     let parse_while = map(
         preceded(keyword("while"), cut(pair(parse_expr, parse_block))),
-        |(expr, body)| Stmt::While(StmtWhile { expr, body }),
+        |(expr, body)| {
+            Stmt::While(StmtWhile {
+                label: None,
+                expr,
+                body,
+            })
+        },
+    );
+
+    let parse_for = map(
+        preceded(
+            keyword("for"),
+            cut(tuple((
+                ident,
+                preceded(keyword("in"), parse_range),
+                parse_block,
+            ))),
+        ),
+        |(var, ((start, inclusive, end), rev, step), body)| {
+            Stmt::For(StmtFor {
+                label: None,
+                var,
+                start,
+                end,
+                inclusive,
+                rev,
+                step,
+                body,
+            })
+        },
+    );
+
+    // 带标签的循环，标签写在循环开头
+    let parse_loops = map(
+        pair(
+            opt(terminated(loop_label, specified_punct(Punct::Colon))),
+            alt((
+                parse_while,
+                parse_for,
+                map(parse_loop, |(_, body)| {
+                    Stmt::Loop(ExprLoop { label: None, body })
+                }),
+            )),
+        ),
+        |(label, mut stmt)| {
+            match &mut stmt {
+                Stmt::While(StmtWhile { label: l, .. })
+                | Stmt::For(StmtFor { label: l, .. })
+                | Stmt::Loop(ExprLoop { label: l, .. }) => *l = label,
+                _ => unreachable!(),
+            }
+            stmt
+        },
+    );
+
+    let parse_break = map(
+        delimited(
+            keyword("break"),
+            pair(opt(loop_label), opt(parse_expr)),
+            specified_punct(Punct::Semi),
+        ),
+        |(label, expr)| Stmt::Break(StmtBreak { label, expr }),
+    );
+
+    let parse_continue = map(
+        delimited(
+            keyword("continue"),
+            opt(loop_label),
+            specified_punct(Punct::Semi),
+        ),
+        Stmt::Continue,
     );
 
     let parse_if = {
@@ -177,31 +383,12 @@ pub fn parse_stmt(input: Lexer) -> IResult<Stmt> {
             cut(pair(
                 parse_expr,
                 group(Delimiter::Brace).and_then(terminated(
-                    separated_list0(
-                        specified_punct(Punct::Comma),
-                        separated_pair(
-                            alt((
-                                map(
-                                    pair(opt(specified_punct(Punct::Minus)), integer),
-                                    |(minus, int)| Some(int * if minus.is_some() { -1 } else { 1 }),
-                                ),
-                                map(specified_punct(Punct::Dot2), |_| None),
-                            )),
-                            specified_punct(Punct::FatArrow),
-                            parse_stmt,
-                        ),
-                    ),
+                    separated_list0(specified_punct(Punct::Comma), parse_match_arm),
                     eof,
                 )),
             )),
         ),
-        |(expr, mut arms)| {
-            arms.sort_by_key(|&(x, _)| x);
-            Stmt::Match(StmtMatch {
-                expr,
-                sorted_arms: arms,
-            })
-        },
+        |(expr, arms)| Stmt::Match(StmtMatch { expr, arms }),
     );
 
     let parse_swap = map(
@@ -228,12 +415,13 @@ pub fn parse_stmt(input: Lexer) -> IResult<Stmt> {
 
     alt((
         kw_stmt_parser(Stmt::Yield, "yield"),
-        kw_stmt_parser(Stmt::Break, "break"),
-        kw_stmt_parser(Stmt::Continue, "continue"),
+        parse_break,
+        parse_continue,
         kw_stmt_parser(Stmt::Debugger, "debugger"),
         map(parse_block, Stmt::Block),
         parse_let,
-        parse_while,
+        parse_compound_assign,
+        parse_loops,
         parse_if,
         parse_return,
         parse_swap,
@@ -247,6 +435,117 @@ pub fn parse_stmt(input: Lexer) -> IResult<Stmt> {
     ))(input)
 }
 
+fn parse_match_arm(input: Lexer) -> IResult<MatchArm> {
+    map(
+        tuple((
+            fragment,
+            separated_list1(specified_punct(Punct::Or), parse_pattern),
+            opt(preceded(keyword("if"), parse_expr)),
+            preceded(specified_punct(Punct::FatArrow), parse_stmt),
+        )),
+        |(at, patterns, guard, body)| MatchArm {
+            at,
+            patterns,
+            guard,
+            body,
+        },
+    )(input)
+}
+
+fn parse_pattern(input: Lexer) -> IResult<Pattern> {
+    let range = verify(
+        tuple((
+            opt(parse_pat_value),
+            alt((
+                value(true, specified_punct(Punct::Dot2Eq)),
+                value(false, specified_punct(Punct::Dot2)),
+            )),
+            opt(parse_pat_value),
+        )),
+        // `a..=`没有终点
+        |(_, inclusive, end)| !inclusive || end.is_some(),
+    );
+
+    alt((
+        map(range, |(start, inclusive, end)| match (start, end) {
+            (None, None) => Pattern::Wildcard,
+            _ => Pattern::Range {
+                start,
+                end,
+                inclusive,
+            },
+        }),
+        map(parse_pat_value, Pattern::Value),
+    ))(input)
+}
+
+fn parse_pat_value(input: Lexer) -> IResult<PatValue> {
+    alt((
+        map(
+            pair(opt(specified_punct(Punct::Minus)), integer),
+            |(minus, int)| PatValue::Int(if minus.is_some() { -int } else { int }),
+        ),
+        // `.. if guard`中的`if`不是区间的终点
+        map(preceded(not(keyword("if")), ident), PatValue::Ident),
+    ))(input)
+}
+
+/// `loop { }`，不含标签
+fn parse_loop(input: Lexer) -> IResult<((), Block)> {
+    pair(keyword("loop"), cut(parse_block))(input)
+}
+
+type Range<'a> = (Expr<'a>, bool, Expr<'a>);
+
+/// `a..b`或`a..=b`，带括号时可以接`.rev()`和`.step_by(n)`
+fn parse_range(input: Lexer) -> IResult<(Range, bool, Option<Expr>)> {
+    let bounds = |input| {
+        tuple((
+            parse_expr,
+            alt((
+                value(false, specified_punct(Punct::Dot2)),
+                value(true, specified_punct(Punct::Dot2Eq)),
+            )),
+            parse_expr,
+        ))(input)
+    };
+    let method = |name| {
+        preceded(
+            pair(specified_punct(Punct::Dot), keyword(name)),
+            group(Delimiter::Paren),
+        )
+    };
+
+    alt((
+        tuple((
+            group(Delimiter::Paren).and_then(terminated(bounds, eof)),
+            map(opt(method("rev").and_then(eof)), |rev| rev.is_some()),
+            opt(method("step_by").and_then(terminated(parse_expr, eof))),
+        )),
+        map(bounds, |range| (range, false, None)),
+    ))(input)
+}
+
+fn parse_assign_op(input: Lexer) -> IResult<AssignOp> {
+    let compound = |p, op| value(op, specified_punct(p));
+    let named = |kw, op| value(op, pair(keyword(kw), specified_punct(Punct::Equal)));
+
+    alt((
+        compound(Punct::PlusEq, AssignOp::Add),
+        compound(Punct::MinusEq, AssignOp::Sub),
+        compound(Punct::StarEq, AssignOp::Mul),
+        compound(Punct::SlashEq, AssignOp::Div),
+        compound(Punct::PercentEq, AssignOp::Rem),
+        compound(Punct::AndEq, AssignOp::BitAnd),
+        compound(Punct::OrEq, AssignOp::BitOr),
+        compound(Punct::CaretEq, AssignOp::BitXor),
+        compound(Punct::ShlEq, AssignOp::Shl),
+        compound(Punct::ShrEq, AssignOp::Shr),
+        named("max", AssignOp::Max),
+        named("min", AssignOp::Min),
+    ))(input)
+}
+
 pub fn parse_expr(input: Lexer) -> IResult<Expr> {
     let binop = verify(punct, |p| infix_binding_power(*p).is_some());
 
@@ -268,10 +567,23 @@ fn parse_atomic_expr(input: Lexer) -> IResult<Expr> {
     );
 
     let atomic_expr = alt((
+        map(
+            pair(
+                opt(terminated(loop_label, specified_punct(Punct::Colon))),
+                parse_loop,
+            ),
+            |(label, (_, body))| Expr::Loop(ExprLoop { label, body }),
+        ),
         group(Delimiter::Paren).and_then(parse_expr),
         //parse_expr_block,
         map(integer, Expr::Integer),
+        map(decimal, Expr::Decimal),
         map(string, Expr::Str),
+        map_opt(ident, |name| match name {
+            "true" => Some(Expr::Bool(true)),
+            "false" => Some(Expr::Bool(false)),
+            _ => None,
+        }),
         map(
             pair(
                 ident,
@@ -291,6 +603,7 @@ fn parse_atomic_expr(input: Lexer) -> IResult<Expr> {
             alt((
                 pair(specified_punct(Punct::Bang), parse_atomic_expr),
                 pair(specified_punct(Punct::Minus), parse_atomic_expr),
+                pair(specified_punct(Punct::Tilde), parse_atomic_expr),
             )),
             |(op, expr)| {
                 Expr::Unary(ExprUnary {
@@ -334,8 +647,12 @@ fn infix_binding_power(punct: Punct) -> Option<(u8, u8)> {
         Punct::And2 => 2,
         Punct::Equal2 | Punct::NotEq => 3,
         Punct::LessThan | Punct::LessEq | Punct::GreaterThan | Punct::GreaterEq => 4,
-        Punct::Plus | Punct::Minus => 5,
-        Punct::Star | Punct::Slash | Punct::Percent => 6,
+        Punct::Or => 5,
+        Punct::Caret => 6,
+        Punct::And => 7,
+        Punct::Shl | Punct::Shr => 8,
+        Punct::Plus | Punct::Minus => 9,
+        Punct::Star | Punct::Slash | Punct::Percent => 10,
         _ => return None,
     };
 
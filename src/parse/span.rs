@@ -0,0 +1,98 @@
+use std::{fmt::Display, ops::Range};
+
+/// 源码中的一段文本。
+///
+/// 语法树中的名称等都是源码的切片，因此只保存切片的地址即可在之后定位，
+/// 不需要在解析时传递源码。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    pub fn of(fragment: &str) -> Self {
+        let start = fragment.as_ptr() as usize;
+        Span {
+            start,
+            end: start + fragment.len(),
+        }
+    }
+
+    /// 在`source`中的字节区间，不是`source`的切片时返回`None`
+    pub fn range_in(&self, source: &str) -> Option<Range<usize>> {
+        let base = source.as_ptr() as usize;
+        if self.start < base || self.end > base + source.len() {
+            return None;
+        }
+        Some(self.start - base..self.end - base)
+    }
+}
+
+/// 带有源码位置的错误
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct SpannedError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl SpannedError {
+    pub fn at(fragment: &str, message: impl Display) -> anyhow::Error {
+        SpannedError {
+            message: message.to_string(),
+            span: Span::of(fragment),
+        }
+        .into()
+    }
+
+    /// 为还没有位置的错误附加位置
+    pub fn attach(err: anyhow::Error, fragment: &str) -> anyhow::Error {
+        if Self::find(&err).is_some() {
+            err
+        } else {
+            Self::at(fragment, format!("{err:#}"))
+        }
+    }
+
+    pub fn find(err: &anyhow::Error) -> Option<&SpannedError> {
+        err.chain().find_map(|e| e.downcast_ref())
+    }
+}
+
+/// 一次编译中的多个错误，依次显示
+#[derive(Debug)]
+pub struct ErrorList(pub Vec<anyhow::Error>);
+
+impl Display for ErrorList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, err) in self.0.iter().enumerate() {
+            if index != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{err:#}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ErrorList {}
+
+impl ErrorList {
+    /// 没有错误时返回`Ok`，只有一个错误时直接返回它
+    pub fn into_result(mut errors: Vec<anyhow::Error>) -> anyhow::Result<()> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.pop().unwrap()),
+            _ => Err(ErrorList(errors).into()),
+        }
+    }
+
+    /// 把错误展开为其中的每一个错误
+    pub fn flatten(err: &anyhow::Error) -> Vec<&anyhow::Error> {
+        match err.downcast_ref::<ErrorList>() {
+            Some(list) => list.0.iter().collect(),
+            None => vec![err],
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{manifest::McManifest, CompileOptions};
+
+pub const PROJECT_FILE: &str = "mcsh.json";
+/// 项目的源文件，相对于项目目录
+pub const MAIN_FILE: &str = "src/main.mcsh";
+const MAIN_TEMPLATE: &str = r#"// 在游戏中运行`/function hello`
+export fn hello() {
+    print!(@a, "{#green}Hello, world!");
+}
+
+fn add(a, b) {
+    return a + b;
+}
+
+// `mcsh test`会运行所有以`test_`开头的导出函数，返回0表示通过
+export fn test_add() {
+    if add(1, 2) != 3 {
+        return 1;
+    }
+    return 0;
+}
+"#;
+const PLACEHOLDER_ICON: &[u8] = include_bytes!("../assets/pack_icon.png");
+
+/// 指定`com.mojang`目录的环境变量，优先于项目文件中的设置
+pub const MINECRAFT_DIR_ENV: &str = "MCSH_MINECRAFT_DIR";
+
+/// 项目文件`mcsh.json`，保存行为包信息与本机的开发设置
+pub struct Project {
+    /// 项目文件所在的目录
+    pub root: PathBuf,
+    pub manifest: McManifest,
+    /// 内存与定点数的设置
+    pub options: CompileOptions,
+    pub dev: DevConfig,
+}
+
+#[derive(Default)]
+pub struct DevConfig {
+    /// `com.mojang`目录
+    pub minecraft_dir: Option<PathBuf>,
+    /// 安装后启用该行为包的存档文件夹名称
+    pub world: Option<String>,
+}
+
+impl Project {
+    /// 使用新生成的UUID与默认设置
+    pub fn new(root: PathBuf, name: &str) -> Self {
+        Self {
+            root,
+            manifest: McManifest {
+                name: name.to_string(),
+                description: format!("{name} behavior pack"),
+                version: [1, 0, 0],
+                min_engine_version: [1, 20, 0],
+                header_uuid: Uuid::new_v4(),
+                module_uuid: Uuid::new_v4(),
+            },
+            options: CompileOptions::default(),
+            dev: DevConfig::default(),
+        }
+    }
+
+    /// 从源文件所在的目录开始向上查找项目文件
+    pub fn find(source: &Path) -> Result<Option<Self>> {
+        match source.parent() {
+            Some(dir) => Self::find_in(dir),
+            None => Ok(None),
+        }
+    }
+
+    /// 从`dir`开始向上查找项目文件
+    pub fn find_in(dir: &Path) -> Result<Option<Self>> {
+        for dir in dir.ancestors() {
+            let path = dir.join(PROJECT_FILE);
+            if path.is_file() {
+                return Self::load(&path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let root = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Self::from_json(&value, root)
+            .map_err(|err| anyhow!("invalid project file `{}`: {err}", path.display()))
+    }
+
+    fn from_json(value: &Value, root: PathBuf) -> Result<Self> {
+        let manifest = McManifest {
+            name: get_str(value, "name")?.to_string(),
+            description: match value.get("description") {
+                Some(_) => get_str(value, "description")?.to_string(),
+                None => String::new(),
+            },
+            version: get_version(value, "version")?,
+            min_engine_version: get_version(value, "min_engine_version")?,
+            header_uuid: get_uuid(value, "header_uuid")?,
+            module_uuid: get_uuid(value, "module_uuid")?,
+        };
+
+        let defaults = CompileOptions::default();
+        let (mem_size, word_width) = match value.get("memory") {
+            Some(memory) => (
+                get_u32_or(memory, "size", defaults.mem_size)?,
+                get_u32_or(memory, "word_width", defaults.word_width)?,
+            ),
+            None => (defaults.mem_size, defaults.word_width),
+        };
+        let fixed_scale = get_u32_or(value, "fixed_scale", defaults.fixed_scale as u32)?;
+        let options = CompileOptions {
+            mem_size,
+            word_width,
+            fixed_scale: check_fixed_scale(fixed_scale)?,
+        };
+
+        let dev = match value.get("dev") {
+            Some(dev) => DevConfig {
+                minecraft_dir: match dev.get("minecraft_dir") {
+                    Some(_) => Some(get_str(dev, "minecraft_dir")?.into()),
+                    None => None,
+                },
+                world: match dev.get("world") {
+                    Some(_) => Some(get_str(dev, "world")?.to_string()),
+                    None => None,
+                },
+            },
+            None => DevConfig::default(),
+        };
+
+        Ok(Self {
+            root,
+            manifest,
+            options,
+            dev,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let McManifest {
+            name,
+            description,
+            version,
+            min_engine_version,
+            header_uuid,
+            module_uuid,
+        } = &self.manifest;
+
+        let mut dev = serde_json::Map::new();
+        if let Some(dir) = &self.dev.minecraft_dir {
+            dev.insert("minecraft_dir".into(), json!(dir));
+        }
+        if let Some(world) = &self.dev.world {
+            dev.insert("world".into(), json!(world));
+        }
+
+        let json = json!({
+            "name": name,
+            "description": description,
+            "version": version,
+            "min_engine_version": min_engine_version,
+            "header_uuid": header_uuid,
+            "module_uuid": module_uuid,
+            "memory": {
+                "size": self.options.mem_size,
+                "word_width": self.options.word_width,
+            },
+            "fixed_scale": self.options.fixed_scale,
+            "dev": dev,
+        });
+        format!("{json:#}")
+    }
+
+    /// 在`root`创建新项目，`root`必须不存在或为空目录
+    pub fn create(root: &Path) -> Result<Self> {
+        let Some(name) = root.file_name().and_then(|name| name.to_str()) else {
+            return Err(anyhow!(
+                "`{}` cannot be used as a project name",
+                root.display()
+            ));
+        };
+        if root.exists() && fs::read_dir(root)?.next().is_some() {
+            return Err(anyhow!(
+                "destination `{}` already exists and is not empty",
+                root.display()
+            ));
+        }
+
+        let project = Self::new(root.to_path_buf(), name);
+        fs::create_dir_all(root.join("src"))?;
+        fs::write(root.join(PROJECT_FILE), project.to_json())?;
+        fs::write(project.main_file(), MAIN_TEMPLATE)?;
+        fs::write(root.join(".gitignore"), "/mcsh_out\n")?;
+        // 占位图标，替换为自己的图标即可
+        fs::write(root.join("pack_icon.png"), PLACEHOLDER_ICON)?;
+        Ok(project)
+    }
+
+    pub fn main_file(&self) -> PathBuf {
+        self.root.join(MAIN_FILE)
+    }
+
+    pub fn minecraft_dir(&self) -> Result<PathBuf> {
+        match env::var_os(MINECRAFT_DIR_ENV) {
+            Some(dir) => Ok(dir.into()),
+            None => self.dev.minecraft_dir.clone().ok_or_else(|| {
+                anyhow!(
+                    "the `com.mojang` directory is unknown, set `dev.minecraft_dir` \
+                    in `{PROJECT_FILE}` or the environment variable `{MINECRAFT_DIR_ENV}`"
+                )
+            }),
+        }
+    }
+
+    /// 项目目录中的`pack_icon.png`
+    pub fn pack_icon(&self) -> Option<PathBuf> {
+        Some(self.root.join("pack_icon.png")).filter(|path| path.is_file())
+    }
+
+    /// 在存档的`world_behavior_packs.json`中启用该行为包，替换之前的同一个包
+    pub fn enable_in_world(&self, world_dir: &Path) -> Result<()> {
+        if !world_dir.is_dir() {
+            return Err(anyhow!("world `{}` does not exist", world_dir.display()));
+        }
+
+        let path = world_dir.join("world_behavior_packs.json");
+        let mut packs: Vec<Value> = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+
+        let pack_id = self.manifest.header_uuid.to_string();
+        packs.retain(|pack| pack["pack_id"] != pack_id.as_str());
+        packs.push(json!({
+            "pack_id": pack_id,
+            "version": self.manifest.version,
+        }));
+        fs::write(path, format!("{:#}", Value::Array(packs)))?;
+        Ok(())
+    }
+}
+
+fn get_str<'a>(value: &'a Value, key: &str) -> Result<&'a str> {
+    value[key]
+        .as_str()
+        .ok_or_else(|| anyhow!("expected `{key}` to be a string"))
+}
+
+fn get_version(value: &Value, key: &str) -> Result<[u32; 3]> {
+    let version: Option<Vec<u32>> = value[key].as_array().and_then(|parts| {
+        parts
+            .iter()
+            .map(|part| part.as_u64()?.try_into().ok())
+            .collect()
+    });
+    version
+        .and_then(|version| version.try_into().ok())
+        .ok_or_else(|| anyhow!("expected `{key}` to be a version like `[1, 0, 0]`"))
+}
+
+fn get_u32_or(value: &Value, key: &str, default: u32) -> Result<u32> {
+    match value.get(key) {
+        Some(n) => n
+            .as_u64()
+            .and_then(|n| n.try_into().ok())
+            .ok_or_else(|| anyhow!("expected `{key}` to be a non-negative integer")),
+        None => Ok(default),
+    }
+}
+
+/// 定点数的倍数必须是10的幂，才能按小数显示
+fn check_fixed_scale(scale: u32) -> Result<i32> {
+    match i32::try_from(scale) {
+        // 相乘时两个余数的乘积小于倍数的平方，不能超过`i32`的范围
+        Ok(scale) if (1..=4).any(|n| 10i32.pow(n) == scale) => Ok(scale),
+        _ => Err(anyhow!(
+            "expected `fixed_scale` to be a power of 10 from 10 to 10000, found {scale}"
+        )),
+    }
+}
+
+fn get_uuid(value: &Value, key: &str) -> Result<Uuid> {
+    Ok(get_str(value, key)?.parse()?)
+}
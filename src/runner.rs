@@ -0,0 +1,99 @@
+use anyhow::Result;
+
+use crate::{
+    ir::simulate::{coverage::CoverageReport, world::World, SimulateOptions, SimulateResult},
+    Compilation,
+};
+
+/// 多次调用结束后的仿真状态
+pub struct SimulationSummary<'a> {
+    /// 所有导出静态变量的值，按名称排序
+    pub statics: Vec<(&'a str, Option<i32>)>,
+    pub world: World,
+    pub coverage: CoverageReport<'a>,
+}
+
+/// 一个测试函数的运行结果
+pub struct TestOutcome<'a> {
+    pub name: &'a str,
+    /// 失败的原因，测试通过时为`None`
+    pub failure: Option<String>,
+    pub log: String,
+}
+
+pub struct TestReport<'a> {
+    pub tests: Vec<TestOutcome<'a>>,
+    /// 所有测试合计的覆盖率
+    pub coverage: CoverageReport<'a>,
+}
+
+impl<'a> TestReport<'a> {
+    pub fn failures(&self) -> impl Iterator<Item = &TestOutcome<'a>> {
+        self.tests.iter().filter(|test| test.failure.is_some())
+    }
+}
+
+impl<'a> Compilation<'a> {
+    /// 在同一个仿真环境中依次运行导出函数，`(函数名, 次数)`，`mcsh_init`将重置环境。
+    ///
+    /// 每次调用的结果交给`on_call`，某次调用出错时不再运行之后的函数
+    pub fn simulate_calls<'c>(
+        &self,
+        calls: &[(&'c str, u32)],
+        options: &SimulateOptions,
+        mut on_call: impl FnMut(&'c str, SimulateResult<'_>) -> Result<()>,
+    ) -> Result<SimulationSummary<'_>> {
+        let mut machine = self.label_map.simulator(options)?;
+
+        'outer: for &(function, count) in calls {
+            for _ in 0..count {
+                let result = machine.call_pub(function);
+                let failed = result.result.is_err();
+                on_call(function, result)?;
+                if failed {
+                    break 'outer;
+                }
+            }
+        }
+
+        Ok(SimulationSummary {
+            statics: machine.exported_statics(),
+            world: machine.world().clone(),
+            coverage: machine.coverage_report(),
+        })
+    }
+
+    /// 运行名称以`test_`开头的导出函数，返回0视为通过。
+    ///
+    /// 每个测试开始时都会重置环境并恢复初始的世界
+    pub fn run_tests(
+        &self,
+        filter: Option<&str>,
+        options: &SimulateOptions,
+    ) -> Result<TestReport<'_>> {
+        let mut machine = self.label_map.simulator(options)?;
+
+        let tests = self
+            .label_map
+            .exported_functions()
+            .into_iter()
+            .filter(|name| name.starts_with("test_"))
+            .filter(|name| filter.is_none_or(|f| name.contains(f)))
+            .map(|name| {
+                machine.restart();
+                let SimulateResult { result, log, .. } = machine.call_pub(name);
+                let failure = match result {
+                    Ok(0) => None,
+                    Ok(value) => Some(format!("返回了{value}")),
+                    Err(err) => Some(err.to_string()),
+                };
+                TestOutcome { name, failure, log }
+            })
+            .collect();
+
+        Ok(TestReport {
+            tests,
+            coverage: machine.coverage_report(),
+        })
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    fs, io,
+    path::Path,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+
+use crate::ir::compile::Files;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+#[derive(Default, Debug)]
+pub struct SyncStats {
+    pub written: u32,
+    pub removed: u32,
+}
+
+/// 使`dst`中的文件与`files`一致，只写入内容有变化的文件，删除`files`中没有的文件
+pub fn sync_files(files: &Files, dst: &Path) -> io::Result<SyncStats> {
+    let mut stats = SyncStats::default();
+    if dst.is_dir() {
+        remove_stale(files, dst, Path::new(""), &mut stats)?;
+    }
+
+    for (path, content) in files {
+        let path = dst.join(path);
+        if fs::read(&path).ok().as_ref() == Some(content) {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+        stats.written += 1;
+    }
+
+    Ok(stats)
+}
+
+/// 删除`files`中没有的文件，以及因此变空的目录
+fn remove_stale(files: &Files, dir: &Path, prefix: &Path, stats: &mut SyncStats) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let relative = prefix.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            remove_stale(files, &entry.path(), &relative, stats)?;
+            if fs::read_dir(entry.path())?.next().is_none() {
+                fs::remove_dir(entry.path())?;
+            }
+        } else if !files.contains_key(&relative) {
+            fs::remove_file(entry.path())?;
+            stats.removed += 1;
+        }
+    }
+    Ok(())
+}
+
+/// 运行`f`，之后每当文件被修改时再次运行。以轮询方式检查修改，不会返回
+pub fn watch(path: &Path, mut f: impl FnMut() -> Result<()>) -> Result<()> {
+    loop {
+        let modified = modified_time(path);
+        if let Err(err) = f() {
+            eprintln!("error: {err:#}");
+        }
+        eprintln!("正在等待`{}`被修改……", path.display());
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = modified_time(path);
+            if current.is_some() && current != modified {
+                break;
+            }
+        }
+    }
+}
+
+/// 文件暂时不存在时（例如编辑器以重命名的方式保存）返回`None`
+fn modified_time(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
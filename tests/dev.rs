@@ -0,0 +1,111 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use serde_json::Value;
+
+/// 每个测试独占的临时目录
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("mcsh-dev-{}-{name}", std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn mcsh(dir: &Path, minecraft_dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mcsh"))
+        .args(args)
+        .current_dir(dir)
+        .env("MCSH_MINECRAFT_DIR", minecraft_dir)
+        .output()
+        .unwrap()
+}
+
+/// 在`root`中创建名为`demo`的项目
+fn new_project(root: &Path) -> PathBuf {
+    let output = mcsh(root, root, &["new", "demo"]);
+    assert!(output.status.success());
+    root.join("demo")
+}
+
+fn rename_project(project: &Path, name: &str) {
+    let path = project.join("mcsh.json");
+    let mut json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    json["name"] = name.into();
+    fs::write(&path, json.to_string()).unwrap();
+}
+
+#[test]
+fn install_into_minecraft_dir() {
+    let root = temp_dir("install");
+    let minecraft_dir = root.join("com.mojang");
+    let world_dir = minecraft_dir.join("minecraftWorlds").join("test_world");
+    fs::create_dir_all(&world_dir).unwrap();
+    let project = new_project(&root);
+
+    let pack_dir = minecraft_dir
+        .join("development_behavior_packs")
+        .join("demo");
+    let stale = pack_dir.join("functions").join("stale.mcfunction");
+    fs::create_dir_all(stale.parent().unwrap()).unwrap();
+    fs::write(&stale, "say old").unwrap();
+
+    let output = mcsh(&project, &minecraft_dir, &["dev", "--world", "test_world"]);
+    assert!(output.status.success(), "{output:?}");
+
+    assert!(pack_dir.join("manifest.json").is_file());
+    assert!(pack_dir.join("pack_icon.png").is_file());
+    assert!(pack_dir
+        .join("functions")
+        .join("hello.mcfunction")
+        .is_file());
+    // 上次安装留下的文件被删除
+    assert!(!stale.exists());
+
+    let packs: Value = serde_json::from_str(
+        &fs::read_to_string(world_dir.join("world_behavior_packs.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(packs.as_array().unwrap().len(), 1);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn reject_names_outside_the_pack_directory() {
+    let root = temp_dir("names");
+    let minecraft_dir = root.join("com.mojang");
+    let packs_dir = minecraft_dir.join("development_behavior_packs");
+    let worlds_dir = minecraft_dir.join("minecraftWorlds");
+    // 名称为`..`时会同步到`com.mojang`并删除存档
+    let world = worlds_dir.join("keep").join("level.dat");
+    fs::create_dir_all(world.parent().unwrap()).unwrap();
+    fs::write(&world, "level").unwrap();
+    fs::create_dir_all(&packs_dir).unwrap();
+
+    let project = new_project(&root);
+    for name in ["..", ".", "", "a/b", "../minecraftWorlds"] {
+        rename_project(&project, name);
+        let output = mcsh(&project, &minecraft_dir, &["dev"]);
+        assert!(!output.status.success(), "`{name}` was accepted");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("cannot be used as a directory name")
+        );
+    }
+
+    let project = new_project(&temp_dir("world-name"));
+    for world in ["..", "../../development_behavior_packs"] {
+        let output = mcsh(&project, &minecraft_dir, &["dev", "--world", world]);
+        assert!(!output.status.success(), "world `{world}` was accepted");
+    }
+    // 名称检查失败时不会写入任何文件
+    assert_eq!(fs::read_to_string(&world).unwrap(), "level");
+    assert_eq!(fs::read_dir(&packs_dir).unwrap().count(), 0);
+
+    fs::remove_dir_all(project.parent().unwrap()).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,198 @@
+use mcsh::simulate::SimulateOptions;
+
+const SOURCE: &str = "
+export static counter = 0;
+
+export fn bump() {
+    counter += 1;
+    return counter;
+}
+
+export fn test_pass() {
+    return 0;
+}
+
+export fn test_fail() {
+    return 3;
+}
+";
+
+#[test]
+fn compile_reports_errors() {
+    let err = mcsh::compile("export fn f() { return x; }").err().unwrap();
+    assert_eq!(err.error_count(), 1);
+    assert_eq!(err.diagnostics[0].severity, mcsh::Severity::Error);
+}
+
+#[test]
+fn compile_reports_errors_after_syntax_errors() {
+    let source = "
+export fn f() {
+    let >>>>>> = <<<<<<;
+    return 1;
+}
+
+export fn g() { return y; }
+";
+    let err = mcsh::compile(source).err().unwrap();
+    let messages: Vec<_> = err.diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "expected identifier, found >>",
+            "identifier `y` is not defined"
+        ]
+    );
+
+    let err = mcsh::compile("export fn f() { let x = 1 $ 2; }")
+        .err()
+        .unwrap();
+    assert_eq!(err.diagnostics[0].message, "unexpected character `$`");
+}
+
+#[test]
+fn simulate_calls_share_state() {
+    let compilation = mcsh::compile(SOURCE).unwrap();
+    let mut results = Vec::new();
+    let summary = compilation
+        .simulate_calls(
+            &[
+                ("bump", 2),
+                ("mcsh_init", 1),
+                ("bump", 1),
+                ("missing", 1),
+                ("bump", 1),
+            ],
+            &SimulateOptions::default(),
+            |function, call| {
+                results.push((function, call.result.ok()));
+                Ok(())
+            },
+        )
+        .unwrap();
+
+    // 调用出错后不再运行之后的函数
+    assert_eq!(
+        results,
+        [
+            ("bump", Some(1)),
+            ("bump", Some(2)),
+            ("mcsh_init", Some(0)),
+            ("bump", Some(1)),
+            ("missing", None),
+        ]
+    );
+    assert_eq!(summary.statics, [("counter", Some(1))]);
+}
+
+#[test]
+fn run_tests_with_filter() {
+    let compilation = mcsh::compile(SOURCE).unwrap();
+    let options = SimulateOptions::default();
+
+    let report = compilation.run_tests(None, &options).unwrap();
+    let names: Vec<_> = report.tests.iter().map(|test| test.name).collect();
+    assert_eq!(names, ["test_fail", "test_pass"]);
+    let failures: Vec<_> = report.failures().map(|test| test.name).collect();
+    assert_eq!(failures, ["test_fail"]);
+
+    let report = compilation.run_tests(Some("pass"), &options).unwrap();
+    assert_eq!(report.tests.len(), 1);
+    assert_eq!(report.failures().count(), 0);
+}
+
+#[test]
+fn custom_pass_on_label_map() {
+    use mcsh::ir::Ir;
+
+    let mut compilation = mcsh::compile("export fn answer() { return 41; }").unwrap();
+    assert_eq!(compilation.label_map().exported_functions(), ["answer"]);
+
+    for info in compilation.label_map_mut().labels_mut() {
+        for ir in &mut info.insts {
+            if let Ir::Assign {
+                value: value @ 41, ..
+            } = ir
+            {
+                *value = 42;
+            }
+        }
+    }
+    compilation.label_map().verify().unwrap();
+
+    let result = compilation
+        .simulate("answer", &SimulateOptions::default())
+        .unwrap();
+    assert_eq!(result.result.unwrap(), 42);
+}
+
+#[test]
+fn sin_uses_fixed_scale() {
+    let source = "
+const HALF = sin(30);
+
+export fn sin_45() {
+    let d = 45;
+    return int(sin(d) * 10000);
+}
+
+export fn half() {
+    return int(HALF * 100);
+}
+";
+    for (fixed_scale, sin_45) in [(10, 7000), (1000, 7070), (10000, 7071)] {
+        let options = mcsh::CompileOptions {
+            fixed_scale,
+            ..Default::default()
+        };
+        let compilation = mcsh::compile_with(source, &options).unwrap();
+        let run = |function| {
+            compilation
+                .simulate(function, &SimulateOptions::default())
+                .unwrap()
+                .result
+                .unwrap()
+        };
+        assert_eq!(run("sin_45"), sin_45, "fixed_scale = {fixed_scale}");
+        assert_eq!(run("half"), 50, "fixed_scale = {fixed_scale}");
+    }
+}
+
+#[test]
+fn print_limits_runtime_fixed_and_bool_values() {
+    let source = |values: &str| {
+        format!("export fn f() {{ let a = 1.5; let b = true; print!(@a, \"{values}\"); }}")
+    };
+    let five = source("{a} {b} {a} {b} {a}");
+    assert!(mcsh::compile(&five).is_ok());
+
+    let six = source("{a} {b} {a} {b} {a} {b}");
+    let err = mcsh::compile(&six).err().unwrap();
+    assert!(err.diagnostics[0].message.contains("at most 5"));
+}
+
+#[test]
+fn match_releases_its_registers() {
+    // 中间代码中用到的最大缓存寄存器编号
+    let max_register = |matches: usize| {
+        let arm = "match n + 1 { x if x > 1 => { n = x; }, .. => {} }";
+        let source = format!(
+            "export fn f() {{ let n = 1; {} return n; }}",
+            arm.repeat(matches)
+        );
+        let compilation = mcsh::compile(&source).unwrap();
+        let insts: Vec<_> = compilation
+            .label_map()
+            .labels()
+            .into_iter()
+            .flat_map(|info| &info.insts)
+            .collect();
+        let text = format!("{insts:?}");
+        text.split("Regular(")
+            .skip(1)
+            .map(|rest| rest[..rest.find(')').unwrap()].parse::<u32>().unwrap())
+            .max()
+            .unwrap()
+    };
+    assert_eq!(max_register(1), max_register(3));
+}
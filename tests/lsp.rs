@@ -0,0 +1,131 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Command, Stdio},
+};
+
+use serde_json::{json, Value};
+
+const URI: &str = "file:///main.mcsh";
+
+const SOURCE: &str = "const LIMIT = 2 * 5;
+
+export fn main() {
+    let count = LIMIT;
+    print!(@a, \"{count}\");
+}
+";
+
+fn frame(message: &Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+fn read_messages(output: impl Read) -> Vec<Value> {
+    let mut reader = BufReader::new(output);
+    let mut messages = Vec::new();
+    loop {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                return messages;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = Some(value.parse().unwrap());
+            }
+        }
+        let mut body = vec![0; length.expect("message without `Content-Length`")];
+        reader.read_exact(&mut body).unwrap();
+        messages.push(serde_json::from_slice(&body).unwrap());
+    }
+}
+
+fn request(id: u32, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn at(line: u32, character: u32) -> Value {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+#[test]
+fn stdio_session() {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_mcsh"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let messages = [
+        request(1, "initialize", json!({ "capabilities": {} })),
+        json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": { "uri": URI, "languageId": "mcsh", "version": 1, "text": SOURCE },
+            },
+        }),
+        // `let count = LIMIT;`中的`LIMIT`
+        request(2, "textDocument/hover", at(3, 16)),
+        request(3, "textDocument/definition", at(3, 16)),
+        // `print!`一行缩进之后的位置
+        request(4, "textDocument/completion", at(4, 4)),
+        request(5, "shutdown", Value::Null),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ];
+    let mut stdin = server.stdin.take().unwrap();
+    for message in &messages {
+        stdin.write_all(frame(message).as_bytes()).unwrap();
+    }
+    drop(stdin);
+
+    let replies = read_messages(server.stdout.take().unwrap());
+    assert!(server.wait().unwrap().success());
+    let reply = |id: u32| {
+        replies
+            .iter()
+            .find(|reply| reply["id"] == id)
+            .unwrap_or_else(|| panic!("no reply to request {id}"))["result"]
+            .clone()
+    };
+
+    let capabilities = &reply(1)["capabilities"];
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["definitionProvider"], true);
+
+    let diagnostics = replies
+        .iter()
+        .find(|reply| reply["method"] == "textDocument/publishDiagnostics")
+        .unwrap();
+    assert_eq!(diagnostics["params"]["uri"], URI);
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    let hover = reply(2)["contents"]["value"].as_str().unwrap().to_string();
+    assert!(hover.contains("const LIMIT = 10"), "{hover}");
+
+    let definition = reply(3);
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(
+        definition["range"],
+        json!({ "start": { "line": 0, "character": 6 }, "end": { "line": 0, "character": 11 } })
+    );
+
+    let completion = reply(4);
+    let labels: Vec<_> = completion
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    for label in ["count", "LIMIT", "main", "print!", "while"] {
+        assert!(labels.contains(&label), "`{label}` is not completed");
+    }
+
+    assert_eq!(reply(5), Value::Null);
+}
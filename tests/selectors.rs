@@ -0,0 +1,98 @@
+use mcsh::simulate::{SimulateOptions, SimulationSummary, World};
+use serde_json::json;
+
+const SOURCE: &str = r#"
+export fn tag_winners() {
+    run!["tag @e[scores={points=10..}] add winner", "tag @a[tag=winner,name=Steve] remove winner"];
+}
+
+export fn reward_red() {
+    run!["scoreboard players add @a[tag=red] points 5", "scoreboard players set @e[type=zombie] points 0"];
+}
+
+export fn announce() {
+    run![" say  red team: @a[tag=red]", "say winner is @p[scores={points=20..}]"];
+}
+"#;
+
+fn world() -> World {
+    World::from_json(&json!({
+        "entities": [
+            { "name": "Steve", "tags": ["red"], "scores": { "points": 12 } },
+            { "name": "Alex", "scores": { "points": 3 } },
+            { "name": "Bob", "tags": ["red"], "scores": { "points": 20 } },
+            { "name": "Zombie", "type": "minecraft:zombie", "scores": { "points": 30 } },
+        ]
+    }))
+    .unwrap()
+}
+
+fn run<'a>(
+    compilation: &'a mcsh::Compilation,
+    function: &str,
+    mut on_message: impl FnMut(&str, &str),
+) -> SimulationSummary<'a> {
+    let options = SimulateOptions {
+        world: world(),
+        ..Default::default()
+    };
+    compilation
+        .simulate_calls(&[(function, 1)], &options, |_, call| {
+            call.result?;
+            for message in &call.messages {
+                on_message(&message.recipient, &message.text);
+            }
+            Ok(())
+        })
+        .unwrap()
+}
+
+fn entity<'w>(world: &'w World, name: &str) -> &'w mcsh::simulate::Entity {
+    world.entities.iter().find(|e| e.name == name).unwrap()
+}
+
+#[test]
+fn tag_selects_each_entity() {
+    let compilation = mcsh::compile(SOURCE).unwrap();
+    let world = run(&compilation, "tag_winners", |_, _| {}).world;
+
+    let winner = |name| entity(&world, name).tags.contains("winner");
+    assert!(!winner("Steve"));
+    assert!(!winner("Alex"));
+    assert!(winner("Bob"));
+    assert!(winner("Zombie"));
+}
+
+#[test]
+fn scoreboard_selects_each_entity() {
+    let compilation = mcsh::compile(SOURCE).unwrap();
+    let world = run(&compilation, "reward_red", |_, _| {}).world;
+
+    let points = |name| entity(&world, name).scores["points"];
+    assert_eq!(points("Steve"), 17);
+    assert_eq!(points("Alex"), 3);
+    assert_eq!(points("Bob"), 25);
+    assert_eq!(points("Zombie"), 0);
+}
+
+#[test]
+fn say_reaches_every_player() {
+    let compilation = mcsh::compile(SOURCE).unwrap();
+    let mut messages = Vec::new();
+    run(&compilation, "announce", |recipient, text| {
+        messages.push(format!("{recipient}: {text}"));
+    });
+
+    // 执行者是第一名玩家，实体不会收到消息
+    assert_eq!(
+        messages,
+        [
+            "Steve: [Steve] red team: Steve, Bob",
+            "Alex: [Steve] red team: Steve, Bob",
+            "Bob: [Steve] red team: Steve, Bob",
+            "Steve: [Steve] winner is Bob",
+            "Alex: [Steve] winner is Bob",
+            "Bob: [Steve] winner is Bob",
+        ]
+    );
+}